@@ -0,0 +1,6 @@
+//! The C interface of `brainfuck::ffi` as a shared and a static library,
+//! a crate of its own so that `brainfuck` itself still builds without `std`
+//!
+//! Build it with `cargo build -p brainfuck-ffi` and include `include/brainfuck.h`.
+
+pub use brainfuck::ffi::*;
@@ -0,0 +1,181 @@
+#![warn(clippy::all)]
+
+//! The `brainfuck!` macro, re-exported by `brainfuck` with its `macros` feature
+//!
+//! Programs are evaluated by a small interpreter of their own here, since this crate can't
+//! depend on `brainfuck` when `brainfuck` depends on it. It behaves like `brainfuck` with
+//! its default settings and `Eof::Zero`, and counts instructions the same way.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse::Parser, punctuated::Punctuated, LitStr, Token};
+
+/// The most instructions a program may execute at compile time
+/// before it is assumed not to terminate
+const FUEL: u64 = 100_000_000;
+
+/// Runs a brainfuck program at compile time and expands to its output as a `&'static [u8]`.
+///
+/// An optional second string is used as the program's input,
+/// after which `,` reads zeroes.
+/// Programs that execute more than 100 000 000 instructions fail to compile.
+///
+/// ```ignore
+/// let hello: &[u8] = brainfuck!("++++++++[>+++++++++<-]>.");
+/// let echo: &[u8] = brainfuck!(",[.,]", "echo");
+/// ```
+#[proc_macro]
+pub fn brainfuck(input: TokenStream) -> TokenStream {
+    match expand(input.into()) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: proc_macro2::TokenStream) -> syn::Result<proc_macro2::TokenStream> {
+    let args = Punctuated::<LitStr, Token![,]>::parse_terminated.parse2(input)?;
+    let mut args = args.into_iter();
+    let src = args
+        .next()
+        .ok_or_else(|| syn::Error::new(Span::call_site(), "expected a brainfuck program"))?;
+    let stdin = args.next().map(|lit| lit.value()).unwrap_or_default();
+    if let Some(extra) = args.next() {
+        return Err(syn::Error::new(extra.span(), "unexpected argument"));
+    }
+
+    let output = evaluate(src.value().as_bytes(), stdin.as_bytes())
+        .map_err(|e| syn::Error::new(src.span(), e))?;
+    let output = syn::LitByteStr::new(&output, src.span());
+
+    Ok(quote!(#output as &'static [u8]))
+}
+
+/// A 1-based line and column in the program
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+/// A command of the program and where it is
+struct Command {
+    byte: u8,
+    pos: Position,
+    /// The index of the matching bracket, if this is one
+    jump: usize,
+}
+
+fn evaluate(src: &[u8], input: &[u8]) -> Result<Vec<u8>, String> {
+    let commands = parse(src)?;
+    let mut tape = vec![0u8];
+    let mut pointer = 0;
+    let mut input = input.iter();
+    let mut output = Vec::new();
+    let mut instructions = 0;
+
+    let mut pc = 0;
+    while let Some(&Command { byte, pos, jump }) = commands.get(pc) {
+        if instructions == FUEL {
+            return Err(format!(
+                "program didn't finish within {FUEL} instructions, stopped at {}:{}",
+                pos.line, pos.column
+            ));
+        }
+        instructions += 1;
+        match byte {
+            b'>' => {
+                pointer += 1;
+                if pointer == tape.len() {
+                    tape.push(0);
+                }
+            }
+            b'<' => {
+                pointer = pointer.checked_sub(1).ok_or_else(|| {
+                    format!(
+                        "brainfuck error: cell pointer overflowed limit at {}:{}",
+                        pos.line, pos.column
+                    )
+                })?;
+            }
+            b'+' => tape[pointer] = tape[pointer].wrapping_add(1),
+            b'-' => tape[pointer] = tape[pointer].wrapping_sub(1),
+            b'.' => output.push(tape[pointer]),
+            b',' => tape[pointer] = input.next().copied().unwrap_or(0),
+            b'[' if tape[pointer] == 0 => pc = jump,
+            b']' if tape[pointer] != 0 => pc = jump,
+            _ => (),
+        }
+        pc += 1;
+    }
+    Ok(output)
+}
+
+/// The commands of `src` with their matching brackets
+fn parse(src: &[u8]) -> Result<Vec<Command>, String> {
+    let mut commands = Vec::new();
+    let mut open = Vec::new();
+    let mut pos = Position { line: 1, column: 1 };
+    for &byte in src {
+        if b"+-<>.,[]".contains(&byte) {
+            let i = commands.len();
+            commands.push(Command { byte, pos, jump: 0 });
+            if byte == b'[' {
+                open.push(i);
+            } else if byte == b']' {
+                let begin = open.pop().ok_or_else(|| {
+                    format!(
+                        "brainfuck error: cannot end a loop when none has been started at {}:{}",
+                        pos.line, pos.column
+                    )
+                })?;
+                commands[begin].jump = i;
+                commands[i].jump = begin;
+            }
+        }
+        if byte == b'\n' {
+            pos.line += 1;
+            pos.column = 1;
+        } else {
+            pos.column += 1;
+        }
+    }
+    if let Some(&begin) = open.first() {
+        let pos = commands[begin].pos;
+        return Err(format!(
+            "brainfuck error: ended with unended loops started at {}:{}",
+            pos.line, pos.column
+        ));
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+
+    #[test]
+    fn runs_out_of_fuel() {
+        let e = evaluate(b"+\n[]", b"").unwrap_err();
+        assert_eq!(
+            e,
+            "program didn't finish within 100000000 instructions, stopped at 2:2"
+        );
+    }
+
+    #[test]
+    fn fails_like_brainfuck() {
+        assert_eq!(
+            evaluate(b"+[", b"").unwrap_err(),
+            "brainfuck error: ended with unended loops started at 1:2"
+        );
+        assert_eq!(
+            evaluate(b"]", b"").unwrap_err(),
+            "brainfuck error: cannot end a loop when none has been started at 1:1"
+        );
+        assert_eq!(
+            evaluate(b"\n <", b"").unwrap_err(),
+            "brainfuck error: cell pointer overflowed limit at 2:2"
+        );
+    }
+}
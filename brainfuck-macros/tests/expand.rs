@@ -0,0 +1,45 @@
+use brainfuck::{run_slice, Eof, FnIo, State};
+use brainfuck_macros::brainfuck;
+
+/// What `brainfuck` prints running `src` with `input` the way the macro does
+fn interpreted(src: &str, input: &[u8]) -> Vec<u8> {
+    let mut state = State::default();
+    state.set_eof(Eof::Zero);
+    let mut input = input.iter().copied();
+    let mut output = Vec::new();
+    let mut io = FnIo::new(|| input.next(), |byte| output.push(byte));
+    run_slice(src.as_bytes(), &mut state, &mut io).unwrap();
+    output
+}
+
+#[test]
+fn expands_to_the_output() {
+    const HELLO: &[u8] = brainfuck!("++++++++[>+++++++++<-]>.");
+    assert_eq!(HELLO, b"H");
+    assert_eq!(HELLO, interpreted("++++++++[>+++++++++<-]>.", b""));
+}
+
+#[test]
+fn reads_the_input_then_zeroes() {
+    let echo: &[u8] = brainfuck!(",[.,]", "echo");
+    assert_eq!(echo, b"echo");
+    let wrapped: &[u8] = brainfuck!(",,-.", "a");
+    assert_eq!(wrapped, [255]);
+    assert_eq!(wrapped, interpreted(",,-.", b"a"));
+}
+
+#[test]
+fn prints_nothing() {
+    let empty: &[u8] = brainfuck!("comments [-] only");
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn nested_loops() {
+    let hi: &[u8] = brainfuck!("++++++++++[>+++++++>++++++++++<<-]>++.>+++++.");
+    assert_eq!(hi, b"Hi");
+    assert_eq!(
+        hi,
+        interpreted("++++++++++[>+++++++>++++++++++<<-]>++.>+++++.", b"")
+    );
+}
@@ -0,0 +1,6 @@
+//! The Python bindings of `brainfuck::python` as an extension module,
+//! a crate of its own so that `brainfuck` itself still builds without Python
+//!
+//! Build it with `maturin develop -m brainfuck-python/Cargo.toml`.
+
+pub use brainfuck::python::*;
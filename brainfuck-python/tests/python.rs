@@ -0,0 +1,55 @@
+use pyo3::prelude::*;
+use pyo3::types::IntoPyDict;
+
+/// Runs the Python `code` with the module imported as `brainfuck`
+fn run_python(code: &std::ffi::CStr) {
+    Python::initialize();
+    Python::attach(|py| {
+        let module = pyo3::wrap_pymodule!(brainfuck_python::init)(py);
+        let globals = [("brainfuck", module)].into_py_dict(py).unwrap();
+        if let Err(e) = py.run(code, Some(&globals), None) {
+            e.display(py);
+            panic!("{e}");
+        }
+    });
+}
+
+#[test]
+fn programs_run_with_their_input() {
+    run_python(
+        cr#"
+assert brainfuck.run(",[.,]", b"hi") == b"hi"
+assert brainfuck.run("++[->+++<]>.") == b"\x06"
+try:
+    brainfuck.run("+]")
+    assert False
+except brainfuck.BrainfuckError as e:
+    assert "loop" in str(e), e
+"#,
+    );
+}
+
+#[test]
+fn interpreters_step() {
+    run_python(
+        cr#"
+interpreter = brainfuck.Interpreter("+>,.", input=b"a")
+assert interpreter.step()
+assert (interpreter.pc, interpreter.cell(0)) == (1, 1)
+assert interpreter.step()
+assert interpreter.pointer == 1
+assert interpreter.step()
+assert interpreter.take_output() == b""
+assert not interpreter.step()
+assert interpreter.finished
+assert interpreter.take_output() == b"a"
+
+interpreter = brainfuck.Interpreter("<")
+try:
+    interpreter.step()
+    assert False
+except brainfuck.BrainfuckError:
+    pass
+"#,
+    );
+}
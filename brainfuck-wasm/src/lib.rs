@@ -0,0 +1,6 @@
+//! The JavaScript bindings of `brainfuck::wasm` as a WebAssembly module,
+//! a crate of its own so that `brainfuck` itself still builds as a library only
+//!
+//! Build it with `wasm-pack build brainfuck-wasm`.
+
+pub use brainfuck::wasm::*;
@@ -0,0 +1,43 @@
+#ifndef BRAINFUCK_H
+#define BRAINFUCK_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define BF_OK 0
+#define BF_STOPPED 1
+#define BF_NO_LOOP_STARTED 2
+#define BF_UNENDED_LOOP 3
+#define BF_POINTER_OVERFLOW 4
+#define BF_END_OF_INPUT 5
+#define BF_UNINITIALIZED_READ 6
+#define BF_IO_ERROR 7
+#define BF_INVALID_ARGUMENT 8
+#define BF_INPUT_LIMIT 9
+#define BF_LOOP_LIMIT 10
+
+/* What `,` does once the input has ended */
+#define BF_EOF_ERROR 0
+#define BF_EOF_ZERO 1
+#define BF_EOF_MINUS_ONE 2
+#define BF_EOF_UNCHANGED 3
+#define BF_EOF_TERMINATE 4
+
+typedef struct BfInterpreter BfInterpreter;
+
+/* Returns the next input byte, -1 at the end of input or -2 on errors */
+typedef int (*BfReadFn)(void *user_data);
+/* Returns 0 on success */
+typedef int (*BfWriteFn)(void *user_data, uint8_t byte);
+
+BfInterpreter *bf_interpreter_new(size_t cell_limit, bool wrap);
+void bf_interpreter_free(BfInterpreter *interpreter);
+int bf_interpreter_set_io(BfInterpreter *interpreter, BfReadFn read, BfWriteFn write, void *user_data);
+int bf_interpreter_set_eof(BfInterpreter *interpreter, int eof);
+int bf_interpreter_run(BfInterpreter *interpreter, const uint8_t *src, size_t len);
+int bf_interpreter_finish(const BfInterpreter *interpreter);
+size_t bf_interpreter_pointer(const BfInterpreter *interpreter);
+uint8_t bf_interpreter_cell(const BfInterpreter *interpreter, size_t index);
+
+#endif
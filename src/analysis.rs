@@ -0,0 +1,109 @@
+use alloc::vec::Vec;
+
+use crate::{
+    lint::{Lint, LintKind},
+    Instruction, Program,
+};
+
+/// Finds loops that provably never end and code that is provably never reached
+pub fn analyze(program: &Program) -> Vec<Lint> {
+    let instructions = program.instructions();
+    let mut lints = Vec::new();
+
+    for (i, ins) in instructions.iter().enumerate() {
+        if let Instruction::LoopBegin(end) = *ins {
+            if never_ends(&instructions[i + 1..end]) {
+                lints.push(Lint {
+                    kind: LintKind::InfiniteLoop,
+                    position: program.position(i).unwrap_or_default(),
+                });
+            }
+        }
+    }
+
+    if let Some(pc) = first_unreachable(instructions) {
+        lints.push(Lint {
+            kind: LintKind::Unreachable,
+            position: program.position(pc).unwrap_or_default(),
+        });
+    }
+
+    lints
+}
+
+/// Whether a loop body without inner loops leaves the pointer where it was
+/// without ever changing the cell the loop tests
+fn never_ends(body: &[Instruction]) -> bool {
+    let mut offset = 0isize;
+    for ins in body {
+        match ins {
+            Instruction::PtrIncr => offset += 1,
+            Instruction::PtrDecr => offset -= 1,
+            Instruction::Incr | Instruction::Decr | Instruction::In if offset == 0 => return false,
+            Instruction::LoopBegin(_) | Instruction::LoopEnd(_) => return false,
+            _ => (),
+        }
+    }
+    offset == 0
+}
+
+/// Follows the program from the start for as long as the pointer and current cell are known,
+/// returning the instruction after a loop that is entered and never ends
+fn first_unreachable(instructions: &[Instruction]) -> Option<usize> {
+    // Known values of cells, which start out as zero. Values are tracked as sums of
+    // increments, where anything between -128 and 127 other than 0 is non-zero for every cell type.
+    let mut cells: Vec<Option<i64>> = Vec::new();
+    let mut pointer = 0usize;
+
+    let mut pc = 0;
+    while pc < instructions.len() {
+        if cells.len() <= pointer {
+            cells.resize(pointer + 1, Some(0));
+        }
+        let cell = &mut cells[pointer];
+        match instructions[pc] {
+            Instruction::Incr => *cell = cell.map(|n| n + 1),
+            Instruction::Decr => *cell = cell.map(|n| n - 1),
+            Instruction::In => *cell = None,
+            Instruction::Out => (),
+            Instruction::PtrIncr => pointer += 1,
+            Instruction::PtrDecr => pointer = pointer.checked_sub(1)?,
+            Instruction::LoopBegin(end) => {
+                let body = &instructions[pc + 1..end];
+                match *cell {
+                    Some(0) => pc = end,
+                    Some(n) if (-128..128).contains(&n) && never_ends(body) => {
+                        return (end + 1 < instructions.len()).then_some(end + 1);
+                    }
+                    _ => {
+                        // The loop ends with the current cell at zero, but the rest is unknown
+                        // unless the pointer ends up where it was
+                        if !body_is_balanced(body) {
+                            return None;
+                        }
+                        cells.iter_mut().for_each(|c| *c = None);
+                        cells[pointer] = Some(0);
+                        pc = end;
+                    }
+                }
+            }
+            Instruction::LoopEnd(_) => (),
+        }
+        pc += 1;
+    }
+
+    None
+}
+
+fn body_is_balanced(body: &[Instruction]) -> bool {
+    let mut offset = 0isize;
+    for ins in body {
+        match ins {
+            Instruction::PtrIncr => offset += 1,
+            Instruction::PtrDecr => offset -= 1,
+            Instruction::LoopBegin(_) => return false,
+            _ => (),
+        }
+    }
+    offset == 0
+}
@@ -0,0 +1,452 @@
+//! A small structured language that compiles to brainfuck
+//!
+//! ```text
+//! var n digit
+//! set n 3
+//! set digit '1'
+//! while n {
+//!     print digit
+//!     add digit 1
+//!     sub n 1
+//! }
+//! if digit {
+//!     print "\ndone\n"
+//! }
+//! ```
+//!
+//! Every variable is a cell. The statements are `var` followed by names, `set`, `add` and `sub`
+//! followed by a variable and a number or `'c'` character, `copy from to`, `read` followed by
+//! a variable, `print` followed by a variable or a string, and `while` and `if` followed by
+//! a variable and a block in braces. A condition is true when its variable isn't zero.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Display};
+
+use crate::{generate, minify::minify, Position};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnexpectedCharacter(char),
+    UnterminatedString,
+    ExpectedStatement,
+    UnknownStatement(String),
+    UnknownVariable(String),
+    Redeclared(String),
+    ExpectedVariable,
+    ExpectedNumber,
+    /// `print` wasn't followed by a variable or a string
+    ExpectedValue,
+    /// A condition wasn't followed by `{`
+    ExpectedBlock,
+    /// More after a statement on the same line
+    ExpectedNewline,
+    /// `}` without a block to end
+    UnmatchedBrace,
+    UnclosedBlock,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub kind: AsmErrorKind,
+    pub position: Position,
+}
+
+impl Display for AsmErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsmErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character `{c}`"),
+            AsmErrorKind::UnterminatedString => write!(f, "string is never closed"),
+            AsmErrorKind::ExpectedStatement => write!(f, "expected a statement"),
+            AsmErrorKind::UnknownStatement(s) => write!(f, "no statement called `{s}`"),
+            AsmErrorKind::UnknownVariable(name) => write!(f, "no variable named `{name}`"),
+            AsmErrorKind::Redeclared(name) => write!(f, "variable `{name}` is already declared"),
+            AsmErrorKind::ExpectedVariable => write!(f, "expected a variable"),
+            AsmErrorKind::ExpectedNumber => write!(f, "expected a number"),
+            AsmErrorKind::ExpectedValue => write!(f, "expected a variable or a string"),
+            AsmErrorKind::ExpectedBlock => write!(f, "expected `{{` to start a block"),
+            AsmErrorKind::ExpectedNewline => write!(f, "expected the statement to end"),
+            AsmErrorKind::UnmatchedBrace => write!(f, "`}}` has no block to end"),
+            AsmErrorKind::UnclosedBlock => write!(f, "block is never closed"),
+        }
+    }
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.kind, self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AsmError {}
+
+/// Compiles `src` to brainfuck
+pub fn compile(src: &[u8]) -> Result<Vec<u8>, AsmError> {
+    let mut parser = Parser {
+        lexer: Lexer {
+            src,
+            i: 0,
+            pos: Position::START,
+        },
+        variables: Vec::new(),
+        peeked: None,
+    };
+    let program = parser.block(None)?;
+
+    let vars = parser.variables.len();
+    let mut emitter = Emitter {
+        out: Vec::new(),
+        pointer: 0,
+        scratch: vars + temps(&program),
+    };
+    emitter.block(&program, vars);
+    Ok(minify(&emitter.out, true))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(i64),
+    Str(Vec<u8>),
+    Open,
+    Close,
+    Newline,
+    End,
+}
+
+struct Lexer<'a> {
+    src: &'a [u8],
+    i: usize,
+    pos: Position,
+}
+
+impl Lexer<'_> {
+    fn bump(&mut self) -> Option<u8> {
+        let byte = *self.src.get(self.i)?;
+        self.i += 1;
+        self.pos.advance(byte);
+        Some(byte)
+    }
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.i).copied()
+    }
+    fn error(&self, kind: AsmErrorKind, position: Position) -> AsmError {
+        AsmError { kind, position }
+    }
+    /// The next token and where it starts
+    fn next(&mut self) -> Result<(Token, Position), AsmError> {
+        loop {
+            match self.peek() {
+                Some(b'#') => {
+                    while !matches!(self.peek(), Some(b'\n') | None) {
+                        self.bump();
+                    }
+                }
+                Some(b) if b != b'\n' && b.is_ascii_whitespace() => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+
+        let start = self.pos;
+        let Some(byte) = self.bump() else {
+            return Ok((Token::End, start));
+        };
+        let token = match byte {
+            b'\n' => Token::Newline,
+            b'{' => Token::Open,
+            b'}' => Token::Close,
+            b'"' => {
+                let mut s = Vec::new();
+                loop {
+                    match self.bump() {
+                        Some(b'"') => break,
+                        Some(b'\\') => s.push(match self.bump() {
+                            Some(b'n') => b'\n',
+                            Some(b't') => b'\t',
+                            Some(b'r') => b'\r',
+                            Some(b'0') => 0,
+                            Some(b) if b != b'\n' => b,
+                            _ => return Err(self.error(AsmErrorKind::UnterminatedString, start)),
+                        }),
+                        Some(b'\n') | None => {
+                            return Err(self.error(AsmErrorKind::UnterminatedString, start))
+                        }
+                        Some(b) => s.push(b),
+                    }
+                }
+                Token::Str(s)
+            }
+            b'\'' => match (self.bump(), self.bump()) {
+                (Some(c), Some(b'\'')) => Token::Number(i64::from(c)),
+                _ => return Err(self.error(AsmErrorKind::ExpectedNumber, start)),
+            },
+            b'-' | b'0'..=b'9' => {
+                let mut digits = String::from(byte as char);
+                while let Some(b @ b'0'..=b'9') = self.peek() {
+                    digits.push(b as char);
+                    self.bump();
+                }
+                match digits.parse() {
+                    Ok(n) => Token::Number(n),
+                    Err(_) => return Err(self.error(AsmErrorKind::ExpectedNumber, start)),
+                }
+            }
+            b if b.is_ascii_alphabetic() || b == b'_' => {
+                let mut word = String::from(b as char);
+                while let Some(b) = self.peek() {
+                    if !(b.is_ascii_alphanumeric() || b == b'_') {
+                        break;
+                    }
+                    word.push(b as char);
+                    self.bump();
+                }
+                Token::Word(word)
+            }
+            _ => {
+                // Report the whole character rather than its first byte
+                let rest = &self.src[self.i - 1..];
+                let len = rest.len().min(4);
+                let c = (1..=len)
+                    .find_map(|n| core::str::from_utf8(&rest[..n]).ok())
+                    .and_then(|s| s.chars().next())
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                return Err(self.error(AsmErrorKind::UnexpectedCharacter(c), start));
+            }
+        };
+        Ok((token, start))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Stmt {
+    /// Clears the variable and adds to it
+    Set(usize, i64),
+    Add(usize, i64),
+    /// Copies the first variable into the second
+    Copy(usize, usize),
+    Print(usize),
+    PrintText(Vec<u8>),
+    Read(usize),
+    While(usize, Vec<Stmt>),
+    If(usize, Vec<Stmt>),
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    variables: Vec<String>,
+    peeked: Option<(Token, Position)>,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Result<(Token, Position), AsmError> {
+        match self.peeked.take() {
+            Some(token) => Ok(token),
+            None => self.lexer.next(),
+        }
+    }
+    fn error(&self, kind: AsmErrorKind, position: Position) -> AsmError {
+        AsmError { kind, position }
+    }
+    /// Parses statements until the end of the block opened at `open`,
+    /// or the end of the source if there is none
+    fn block(&mut self, open: Option<Position>) -> Result<Vec<Stmt>, AsmError> {
+        let mut stmts = Vec::new();
+        loop {
+            let (token, position) = self.next()?;
+            let word = match token {
+                Token::Newline => continue,
+                Token::End => match open {
+                    Some(open) => return Err(self.error(AsmErrorKind::UnclosedBlock, open)),
+                    None => return Ok(stmts),
+                },
+                Token::Close => match open {
+                    Some(_) => return Ok(stmts),
+                    None => return Err(self.error(AsmErrorKind::UnmatchedBrace, position)),
+                },
+                Token::Word(word) => word,
+                _ => return Err(self.error(AsmErrorKind::ExpectedStatement, position)),
+            };
+
+            match &*word {
+                "var" => {
+                    let (mut token, mut position) = self.next()?;
+                    if !matches!(token, Token::Word(_)) {
+                        return Err(self.error(AsmErrorKind::ExpectedVariable, position));
+                    }
+                    while let Token::Word(name) = token {
+                        if self.variables.contains(&name) {
+                            return Err(self.error(AsmErrorKind::Redeclared(name), position));
+                        }
+                        self.variables.push(name);
+                        (token, position) = self.next()?;
+                    }
+                    self.peeked = Some((token, position));
+                }
+                "set" => stmts.push(Stmt::Set(self.variable()?, self.number()?)),
+                "add" => stmts.push(Stmt::Add(self.variable()?, self.number()?)),
+                "sub" => stmts.push(Stmt::Add(self.variable()?, -self.number()?)),
+                "copy" => stmts.push(Stmt::Copy(self.variable()?, self.variable()?)),
+                "read" => stmts.push(Stmt::Read(self.variable()?)),
+                "print" => match self.next()? {
+                    (Token::Str(s), _) => stmts.push(Stmt::PrintText(s)),
+                    (Token::Word(name), position) => {
+                        stmts.push(Stmt::Print(self.lookup(name, position)?));
+                    }
+                    (_, position) => return Err(self.error(AsmErrorKind::ExpectedValue, position)),
+                },
+                "while" | "if" => {
+                    let cond = self.variable()?;
+                    let (token, open) = self.next()?;
+                    if token != Token::Open {
+                        return Err(self.error(AsmErrorKind::ExpectedBlock, open));
+                    }
+                    let body = self.block(Some(open))?;
+                    stmts.push(match &*word {
+                        "while" => Stmt::While(cond, body),
+                        _ => Stmt::If(cond, body),
+                    });
+                }
+                _ => return Err(self.error(AsmErrorKind::UnknownStatement(word), position)),
+            }
+
+            match self.next()? {
+                (Token::Newline, _) => (),
+                token @ (Token::End | Token::Close, _) => self.peeked = Some(token),
+                (_, position) => return Err(self.error(AsmErrorKind::ExpectedNewline, position)),
+            }
+        }
+    }
+    fn variable(&mut self) -> Result<usize, AsmError> {
+        match self.next()? {
+            (Token::Word(name), position) => self.lookup(name, position),
+            (_, position) => Err(self.error(AsmErrorKind::ExpectedVariable, position)),
+        }
+    }
+    fn lookup(&self, name: String, position: Position) -> Result<usize, AsmError> {
+        self.variables
+            .iter()
+            .position(|v| *v == name)
+            .ok_or_else(|| self.error(AsmErrorKind::UnknownVariable(name), position))
+    }
+    fn number(&mut self) -> Result<i64, AsmError> {
+        match self.next()? {
+            (Token::Number(n), _) => Ok(n),
+            (_, position) => Err(self.error(AsmErrorKind::ExpectedNumber, position)),
+        }
+    }
+}
+
+/// The most temporary cells `stmts` need at once
+fn temps(stmts: &[Stmt]) -> usize {
+    stmts
+        .iter()
+        .map(|stmt| match stmt {
+            Stmt::Copy(..) => 1,
+            Stmt::While(_, body) => temps(body),
+            Stmt::If(_, body) => 2.max(1 + temps(body)),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Writes brainfuck for statements, with variables in the first cells, then temporary cells
+/// and then the cells `generate::text` uses for printing strings
+struct Emitter {
+    out: Vec<u8>,
+    pointer: usize,
+    scratch: usize,
+}
+
+impl Emitter {
+    fn move_to(&mut self, cell: usize) {
+        if cell > self.pointer {
+            self.out
+                .extend(core::iter::repeat_n(b'>', cell - self.pointer));
+        } else {
+            self.out
+                .extend(core::iter::repeat_n(b'<', self.pointer - cell));
+        }
+        self.pointer = cell;
+    }
+    fn add(&mut self, cell: usize, n: i64) {
+        self.move_to(cell);
+        let cmd = if n < 0 { b'-' } else { b'+' };
+        self.out
+            .extend(core::iter::repeat_n(cmd, n.unsigned_abs() as usize));
+    }
+    fn clear(&mut self, cell: usize) {
+        self.move_to(cell);
+        self.out.extend(b"[-]");
+    }
+    /// Emits `body` as a loop on `cell`
+    fn while_nonzero(&mut self, cell: usize, body: impl FnOnce(&mut Self)) {
+        self.move_to(cell);
+        self.out.push(b'[');
+        body(self);
+        self.move_to(cell);
+        self.out.push(b']');
+    }
+    /// Emits `stmts`, with `temp` being the first temporary cell that is free
+    fn block(&mut self, stmts: &[Stmt], temp: usize) {
+        for stmt in stmts {
+            self.statement(stmt, temp);
+        }
+    }
+    fn statement(&mut self, stmt: &Stmt, temp: usize) {
+        match *stmt {
+            Stmt::Set(var, n) => {
+                self.clear(var);
+                self.add(var, n);
+            }
+            Stmt::Add(var, n) => self.add(var, n),
+            Stmt::Copy(from, to) => self.copy(from, to, temp),
+            Stmt::Print(var) => {
+                self.move_to(var);
+                self.out.push(b'.');
+            }
+            Stmt::PrintText(ref text) => {
+                let (code, end) = generate::text_ending_at(text);
+                self.move_to(self.scratch);
+                self.out.extend(code);
+                self.pointer = self.scratch + end;
+                for cell in 1..=generate::CELLS {
+                    self.clear(self.scratch + cell);
+                }
+            }
+            Stmt::Read(var) => {
+                self.move_to(var);
+                self.out.push(b',');
+            }
+            Stmt::While(cond, ref body) => {
+                self.while_nonzero(cond, |this| this.block(body, temp));
+            }
+            Stmt::If(cond, ref body) => {
+                let flag = temp;
+                self.copy(cond, flag, temp + 1);
+                self.while_nonzero(flag, |this| {
+                    this.out.extend(b"[-]");
+                    this.block(body, temp + 1);
+                });
+            }
+        }
+    }
+    /// Sets `to` to `from` using the cell `temp`, which has to be zero
+    fn copy(&mut self, from: usize, to: usize, temp: usize) {
+        if from == to {
+            return;
+        }
+        self.clear(to);
+        self.while_nonzero(from, |this| {
+            this.out.push(b'-');
+            this.add(to, 1);
+            this.add(temp, 1);
+        });
+        self.while_nonzero(temp, |this| {
+            this.out.push(b'-');
+            this.add(from, 1);
+        });
+    }
+}
@@ -0,0 +1,90 @@
+//! What the bindings to other languages share: programs running on buffered input and output,
+//! reading 0 past the end of the input like playgrounds usually do
+
+use std::collections::VecDeque;
+use std::mem::take;
+
+use crate::{run_program, BfIo, Cell, Eof, Program, Result, State};
+
+/// The input the program reads from and the output it has printed
+struct Buffers {
+    input: VecDeque<u8>,
+    output: Vec<u8>,
+}
+
+impl Buffers {
+    fn new(input: &[u8]) -> Self {
+        Buffers {
+            input: input.iter().copied().collect(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl BfIo for Buffers {
+    fn read(&mut self) -> Result<Option<u8>> {
+        Ok(self.input.pop_front())
+    }
+    fn write(&mut self, byte: u8) -> Result<()> {
+        self.output.push(byte);
+        Ok(())
+    }
+}
+
+fn state() -> State {
+    let mut state = State::default();
+    state.set_eof(Eof::Zero);
+    state
+}
+
+/// Runs `source` with `input`, returning what it printed
+pub(crate) fn run(source: &[u8], input: &[u8]) -> Result<Vec<u8>> {
+    let program = Program::parse(source)?;
+    let mut io = Buffers::new(input);
+    run_program(&program, &mut state(), &mut io)?;
+    Ok(io.output)
+}
+
+/// A program that runs an instruction at a time
+pub(crate) struct Stepper {
+    program: Program,
+    state: State,
+    io: Buffers,
+    pc: usize,
+}
+
+impl Stepper {
+    /// Parses `source`, failing if its loops don't match
+    pub(crate) fn new(source: &[u8], input: &[u8]) -> Result<Self> {
+        Ok(Stepper {
+            program: Program::parse(source)?,
+            state: state(),
+            io: Buffers::new(input),
+            pc: 0,
+        })
+    }
+    /// Runs the next instruction, returning whether there are any left
+    pub(crate) fn step(&mut self) -> Result<bool> {
+        if !self.finished() {
+            self.pc = self.program.step(&mut self.state, self.pc, &mut self.io)?;
+        }
+        Ok(!self.finished())
+    }
+    pub(crate) fn finished(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+    /// The index of the next instruction
+    pub(crate) fn pc(&self) -> usize {
+        self.pc
+    }
+    pub(crate) fn pointer(&self) -> usize {
+        self.state.cell_pointer
+    }
+    pub(crate) fn cell(&self, index: usize) -> u8 {
+        self.state.get(index).value()
+    }
+    /// Takes what the program has printed since the last time
+    pub(crate) fn take_output(&mut self) -> Vec<u8> {
+        take(&mut self.io.output)
+    }
+}
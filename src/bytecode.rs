@@ -0,0 +1,1046 @@
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Display},
+    ops::Range,
+};
+
+#[cfg(feature = "metrics")]
+use crate::telemetry;
+use crate::{
+    BfIo, Cell, EndReason, Instruction, Position, Program, Result, RunReport, State, UninitReads,
+};
+
+/// How much `Bytecode::compile_with` optimizes a program, each level doing what the ones
+/// before it do as well
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OptLevel {
+    /// An op for every command
+    None,
+    /// Runs of `+` and `-` and of moves one way are merged into single ops
+    Runs,
+    /// Common loops like `[-]`, `[->+<]` and `[>]` are run as single ops
+    Loops,
+    /// Pairs of ops that often follow each other are fused into superinstructions
+    #[default]
+    Full,
+}
+
+/// An operation of optimized `Bytecode`, usually standing in for several commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Op {
+    /// A run of `+` and `-`
+    Add(i32),
+    /// A run of `<` or `>`
+    Move(isize),
+    Out,
+    In,
+    /// `[-]` or `[+]`, adding the given -1 or 1 until the cell is zero
+    Clear(i32),
+    /// Clears, moves by one in the direction of the first number and clears again until it
+    /// has moved that far, like `[-]>[-]>[-]`, adding the second number like `Clear`
+    ClearRange(isize, i32),
+    /// A loop that only moves, like `[>]` or `[<<]`, finding the first zero cell that far apart
+    Scan(isize),
+    /// A loop counting the current cell down while adding the given -1 or 1 to the cell that far
+    /// away, like `[->+<]` or `[<->-]`, which moves the value over
+    Transfer(isize, i32),
+    /// Transfers like `[-<+>]`, moves by one in the direction of the first number and transfers
+    /// again until it has moved that far, like `[-<+>]>[-<+>]`, shifting the range of cells
+    /// over by the second number, which points the other way
+    TransferRange(isize, isize),
+    /// `[`, jumping past the op at the given index if the current cell is zero
+    JumpIfZero(usize),
+    /// `]`, jumping back past the op at the given index if the current cell isn't zero
+    JumpIfNonZero(usize),
+    /// `Add` followed by `Move`
+    AddMove(i32, isize),
+    /// `Move` followed by `JumpIfNonZero`, like at the end of most loops
+    MoveJumpIfNonZero(isize, usize),
+    /// `AddMove` followed by `JumpIfNonZero`, like the end of `[->+<]`
+    AddMoveJumpIfNonZero(i32, isize, usize),
+}
+
+impl Op {
+    /// The index an op jumps to, if it jumps
+    fn target(self) -> Option<usize> {
+        match self {
+            Op::JumpIfZero(target)
+            | Op::JumpIfNonZero(target)
+            | Op::MoveJumpIfNonZero(_, target)
+            | Op::AddMoveJumpIfNonZero(_, _, target) => Some(target),
+            _ => None,
+        }
+    }
+    /// The tag and arguments the op is saved as by `Bytecode::to_bytes`
+    fn encode(self) -> (u8, [i64; 3]) {
+        match self {
+            Op::Add(n) => (0, [n.into(), 0, 0]),
+            Op::Move(n) => (1, [n as i64, 0, 0]),
+            Op::Out => (2, [0; 3]),
+            Op::In => (3, [0; 3]),
+            Op::Clear(add) => (4, [add.into(), 0, 0]),
+            Op::ClearRange(n, add) => (5, [n as i64, add.into(), 0]),
+            Op::Scan(n) => (6, [n as i64, 0, 0]),
+            Op::JumpIfZero(target) => (7, [target as i64, 0, 0]),
+            Op::JumpIfNonZero(target) => (8, [target as i64, 0, 0]),
+            Op::AddMove(n, m) => (9, [n.into(), m as i64, 0]),
+            Op::MoveJumpIfNonZero(n, target) => (10, [n as i64, target as i64, 0]),
+            Op::AddMoveJumpIfNonZero(n, m, target) => (11, [n.into(), m as i64, target as i64]),
+            Op::Transfer(offset, add) => (12, [offset as i64, add.into(), 0]),
+            Op::TransferRange(n, offset) => (13, [n as i64, offset as i64, 0]),
+        }
+    }
+    fn decode(tag: u8, [a, b, c]: [i64; 3]) -> Option<Self> {
+        let n = |arg: i64| isize::try_from(arg).ok();
+        let target = |arg: i64| usize::try_from(arg).ok();
+        let clear = |arg: i64| matches!(arg, 1 | -1).then_some(arg as i32);
+        Some(match tag {
+            0 => Op::Add(i32::try_from(a).ok()?),
+            1 => Op::Move(n(a)?),
+            2 => Op::Out,
+            3 => Op::In,
+            4 => Op::Clear(clear(a)?),
+            5 => Op::ClearRange(n(a)?, clear(b)?),
+            6 => Op::Scan(n(a)?),
+            7 => Op::JumpIfZero(target(a)?),
+            8 => Op::JumpIfNonZero(target(a)?),
+            9 => Op::AddMove(i32::try_from(a).ok()?, n(b)?),
+            10 => Op::MoveJumpIfNonZero(n(a)?, target(b)?),
+            11 => Op::AddMoveJumpIfNonZero(i32::try_from(a).ok()?, n(b)?, target(c)?),
+            12 => Op::Transfer(n(a).filter(|&offset| offset != 0)?, clear(b)?),
+            13 => {
+                let (n, offset) = (n(a)?, n(b)?);
+                if n == 0 || offset == 0 || n.signum() == offset.signum() {
+                    return None;
+                }
+                Op::TransferRange(n, offset)
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// The part of the source an op was compiled from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: Position,
+    /// The position of the last command
+    pub end: Position,
+    /// How many commands the op replaces
+    pub commands: u32,
+}
+
+/// A `Program` optimized by merging runs of commands and common loops into single ops
+///
+/// Pairs of ops that often follow each other are then fused into superinstructions,
+/// so that hot loops like `[->+<]` take fewer ops to run.
+///
+/// Running bytecode gives the same results as running the program,
+/// except that errors in merged ops point at where the op starts.
+///
+/// Clearing ranges, transferring cells and scanning for zero cells works on the tape as a slice when
+/// uninitialized reads are allowed and the range doesn't need to wrap around.
+///
+/// Deserializing bytecode checks it like `from_bytes` does.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "UncheckedBytecode")
+)]
+pub struct Bytecode {
+    ops: Vec<Op>,
+    spans: Vec<Span>,
+}
+
+/// `Bytecode` as it's deserialized, before its ops are checked
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct UncheckedBytecode {
+    ops: Vec<Op>,
+    spans: Vec<Span>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedBytecode> for Bytecode {
+    type Error = &'static str;
+
+    fn try_from(bytecode: UncheckedBytecode) -> core::result::Result<Self, Self::Error> {
+        let UncheckedBytecode { ops, spans } = bytecode;
+        if spans.len() != ops.len() {
+            return Err("bytecode needs a span for every op");
+        }
+        // Ops that can't be saved with their arguments couldn't have been compiled either
+        if ops.iter().any(|&op| {
+            let (tag, args) = op.encode();
+            Op::decode(tag, args) != Some(op)
+        }) {
+            return Err("bytecode has an op with arguments it can't have");
+        }
+        if !jumps_pair_up(&ops) {
+            return Err("bytecode has jumps that don't pair up");
+        }
+        Ok(Bytecode { ops, spans })
+    }
+}
+
+/// The start of bytecode saved by `Bytecode::to_bytes`, ending with the version of the format
+const MAGIC: &[u8; 4] = b"bfc\x03";
+/// How many bytes each op takes up when saved: its tag, three arguments,
+/// the two positions of its span and how many commands it replaces
+const SAVED_OP_SIZE: usize = 1 + 3 * 8 + 6 * 8 + 4;
+
+impl Bytecode {
+    #[inline]
+    pub fn compile(program: &Program) -> Self {
+        Bytecode::compile_with(program, OptLevel::Full)
+    }
+    /// Compiles `program` only optimizing it as much as `level` does
+    #[inline]
+    pub fn compile_with(program: &Program, level: OptLevel) -> Self {
+        Bytecode::compile_range(program, 0..program.len(), level)
+    }
+    /// Compiles only the instructions of `program` in `range`, which has to contain whole loops
+    pub(crate) fn compile_range(program: &Program, range: Range<usize>, level: OptLevel) -> Self {
+        let mut ops = Vec::new();
+        let mut spans: Vec<Span> = Vec::new();
+        let mut loop_starts = Vec::new();
+        let instructions = &program.instructions()[range.clone()];
+
+        let mut i = 0;
+        while i < instructions.len() {
+            let start = i;
+            let mut op = match instructions[i] {
+                Instruction::Incr if level == OptLevel::None => Op::Add(1),
+                Instruction::Decr if level == OptLevel::None => Op::Add(-1),
+                Instruction::PtrIncr if level == OptLevel::None => Op::Move(1),
+                Instruction::PtrDecr if level == OptLevel::None => Op::Move(-1),
+                Instruction::Incr | Instruction::Decr => {
+                    let mut n = 0i32;
+                    while let Some(ins @ (Instruction::Incr | Instruction::Decr)) =
+                        instructions.get(i)
+                    {
+                        n = n.wrapping_add(if *ins == Instruction::Incr { 1 } else { -1 });
+                        i += 1;
+                    }
+                    i -= 1;
+                    Op::Add(n)
+                }
+                // A run only goes one way, so that it passes the lowest and highest cell it
+                // reaches on the way to where it ends and the cell limit is checked for them
+                direction @ (Instruction::PtrIncr | Instruction::PtrDecr) => {
+                    let mut n = 0isize;
+                    while instructions.get(i) == Some(&direction) {
+                        n += if direction == Instruction::PtrIncr {
+                            1
+                        } else {
+                            -1
+                        };
+                        i += 1;
+                    }
+                    i -= 1;
+                    Op::Move(n)
+                }
+                Instruction::Out => Op::Out,
+                Instruction::In => Op::In,
+                Instruction::LoopBegin(_) => {
+                    let scanned = scan(&instructions[i + 1..]).filter(|_| level >= OptLevel::Loops);
+                    let transferred =
+                        transfer(&instructions[i + 1..]).filter(|_| level >= OptLevel::Loops);
+                    match (&instructions[i..], scanned, transferred) {
+                        (
+                            [_, add @ (Instruction::Incr | Instruction::Decr), Instruction::LoopEnd(_), ..],
+                            _,
+                            _,
+                        ) if level >= OptLevel::Loops => {
+                            i += 2;
+                            Op::Clear(if *add == Instruction::Incr { 1 } else { -1 })
+                        }
+                        (_, Some(n), _) => {
+                            i += n.unsigned_abs() + 1;
+                            Op::Scan(n)
+                        }
+                        (_, _, Some((offset, add))) => {
+                            i += 2 * offset.unsigned_abs() + 3;
+                            Op::Transfer(offset, add)
+                        }
+                        _ => {
+                            loop_starts.push(ops.len());
+                            // Patched once the end is found
+                            Op::JumpIfZero(0)
+                        }
+                    }
+                }
+                Instruction::LoopEnd(_) => {
+                    // Programs always have matching loops
+                    Op::JumpIfNonZero(loop_starts.pop().unwrap_or_default())
+                }
+            };
+            let mut span = Span {
+                start: program.position(range.start + start).unwrap_or_default(),
+                end: program.position(range.start + i).unwrap_or_default(),
+                commands: (i + 1 - start) as u32,
+            };
+            // Merge `[-]>[-]` and `[-<+>]>[-<+>]` into ranges, if the move between them is
+            // a single command, so that the range knows how many commands it runs
+            if let [.., previous, Op::Move(step @ (1 | -1))] = ops[..] {
+                let moved = spans[spans.len() - 1];
+                if let Some(range) =
+                    extend_range(previous, step, op).filter(|_| moved.commands == 1)
+                {
+                    op = range;
+                    ops.truncate(ops.len() - 2);
+                    let first = spans[spans.len() - 2];
+                    spans.truncate(spans.len() - 2);
+                    span.start = first.start;
+                    span.commands += first.commands + moved.commands;
+                }
+            }
+            let fused = ops.last().and_then(|&last| fuse(last, op));
+            if let Some(fused) = fused.filter(|_| level == OptLevel::Full) {
+                op = fused;
+                ops.pop();
+                let first = spans.pop().unwrap_or(span);
+                span.start = first.start;
+                span.commands += first.commands;
+            }
+            if let Op::JumpIfNonZero(begin)
+            | Op::MoveJumpIfNonZero(_, begin)
+            | Op::AddMoveJumpIfNonZero(_, _, begin) = op
+            {
+                ops[begin] = Op::JumpIfZero(ops.len());
+            }
+            ops.push(op);
+            spans.push(span);
+            i += 1;
+        }
+
+        Bytecode { ops, spans }
+    }
+    #[inline]
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+    /// The source the op at `index` was compiled from
+    #[inline]
+    pub fn span(&self, index: usize) -> Option<Span> {
+        self.spans.get(index).copied()
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+    /// Saves the bytecode as bytes that `from_bytes` reads back,
+    /// so that a program doesn't have to be compiled again every time it's run
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 8 + self.ops.len() * SAVED_OP_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(self.ops.len() as u64).to_le_bytes());
+        for (op, span) in self.ops.iter().zip(&self.spans) {
+            let (tag, args) = op.encode();
+            bytes.push(tag);
+            for arg in args {
+                bytes.extend_from_slice(&arg.to_le_bytes());
+            }
+            for pos in [span.start, span.end] {
+                for n in [pos.offset, pos.line, pos.column] {
+                    bytes.extend_from_slice(&(n as u64).to_le_bytes());
+                }
+            }
+            bytes.extend_from_slice(&span.commands.to_le_bytes());
+        }
+        bytes
+    }
+    /// Reads bytecode saved by `to_bytes`.
+    /// `None` if `bytes` isn't bytecode saved in this version of the format,
+    /// or has jumps that don't pair up like the brackets they were compiled from.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let rest = bytes.strip_prefix(MAGIC)?;
+        let (len, saved_ops) = rest.split_first_chunk::<8>()?;
+        let len = usize::try_from(u64::from_le_bytes(*len)).ok()?;
+        if len.checked_mul(SAVED_OP_SIZE)? != saved_ops.len() {
+            return None;
+        }
+        let mut ops = Vec::with_capacity(len);
+        let mut spans = Vec::with_capacity(len);
+        for chunk in saved_ops.chunks_exact(SAVED_OP_SIZE) {
+            let (&tag, fields) = chunk.split_first().unwrap();
+            let mut fields = fields
+                .chunks_exact(8)
+                .map(|n| i64::from_le_bytes(n.try_into().unwrap()));
+            let mut field = || fields.next().unwrap();
+            ops.push(Op::decode(tag, [field(), field(), field()])?);
+            let mut position = || {
+                Some(Position {
+                    offset: usize::try_from(field()).ok()?,
+                    line: usize::try_from(field()).ok()?,
+                    column: usize::try_from(field()).ok()?,
+                })
+            };
+            let (start, end) = (position()?, position()?);
+            let commands = u32::from_le_bytes(chunk[SAVED_OP_SIZE - 4..].try_into().unwrap());
+            spans.push(Span {
+                start,
+                end,
+                commands,
+            });
+        }
+        jumps_pair_up(&ops).then_some(Bytecode { ops, spans })
+    }
+    /// Runs all of the bytecode on `state`.
+    /// Instructions are counted as the commands they replace would have been,
+    /// so clearing a cell counts every iteration of its `[-]`.
+    ///
+    /// Each op is first turned into a pointer to the function that runs it, so running
+    /// an op is a single indirect call instead of a `match` on every op.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "run", skip_all, fields(engine = "bytecode"), err)
+    )]
+    pub fn run<C: Cell, I: BfIo>(&self, state: &mut State<C>, io: &mut I) -> Result<RunReport> {
+        #[cfg(feature = "metrics")]
+        let mut metered = telemetry::Run::new(state, "bytecode");
+        #[cfg(feature = "metrics")]
+        let state = &mut *metered;
+        state.start();
+        let instructions = state.instructions;
+        let bytes_read = state.bytes_read;
+        let bytes_written = state.bytes_written;
+        state.peak_pointer = state.cell_pointer;
+
+        let result = self.threaded().run(state, io);
+        state.ended_by_input(result)?;
+
+        Ok(RunReport {
+            end: EndReason::EndOfSource,
+            instructions: state.instructions - instructions,
+            bytes_read: state.bytes_read - bytes_read,
+            bytes_written: state.bytes_written - bytes_written,
+            peak_pointer: state.peak_pointer,
+        })
+    }
+    /// Turns each op into the function that runs it, see `run`
+    pub(crate) fn threaded<C: Cell, I: BfIo>(&self) -> ThreadedCode<C, I> {
+        ThreadedCode {
+            ops: self.ops.iter().map(|&op| op.into()).collect(),
+            spans: self.spans.clone(),
+        }
+    }
+}
+
+/// Bytecode with every op turned into the function that runs it
+pub(crate) struct ThreadedCode<C: Cell, I: BfIo> {
+    ops: Vec<Threaded<C, I>>,
+    spans: Vec<Span>,
+}
+
+impl<C: Cell, I: BfIo> ThreadedCode<C, I> {
+    /// Runs all of the code on `state` without starting a new run
+    pub(crate) fn run(&self, state: &mut State<C>, io: &mut I) -> Result<()> {
+        let mut pc = 0;
+        while let Some(op) = self.ops.get(pc) {
+            let span = &self.spans[pc];
+            state.position = span.start;
+            state.instructions += u64::from(span.commands);
+            let next = (op.run)(state, io, op.args, pc)?;
+            if next <= pc {
+                // Jumped back past the `[` of the loop
+                state.loop_repeated(self.spans[next - 1].start)?;
+            }
+            pc = next;
+        }
+        Ok(())
+    }
+}
+
+/// The arguments of an op, all but the first one only used by fused ops
+type Args = (isize, isize, isize);
+/// Runs an op with its arguments, returning the index of the op to run next
+type Handler<C, I> = fn(&mut State<C>, &mut I, Args, usize) -> Result<usize>;
+
+/// An op as the function that runs it, see `Bytecode::run`
+struct Threaded<C: Cell, I: BfIo> {
+    run: Handler<C, I>,
+    args: Args,
+}
+
+impl<C: Cell, I: BfIo> From<Op> for Threaded<C, I> {
+    fn from(op: Op) -> Self {
+        let (run, args): (Handler<C, I>, Args) = match op {
+            Op::Add(n) => (add, (n as isize, 0, 0)),
+            Op::Move(n) => (move_by, (n, 0, 0)),
+            Op::Out => (output, (0, 0, 0)),
+            Op::In => (input, (0, 0, 0)),
+            Op::Clear(add) => (clear, (add as isize, 0, 0)),
+            Op::ClearRange(n, add) => (clear_range, (n, add as isize, 0)),
+            Op::Scan(n) => (scan_for_zero, (n, 0, 0)),
+            Op::Transfer(offset, add) => (transfer_value, (offset, add as isize, 0)),
+            Op::TransferRange(n, offset) => (transfer_range, (n, offset, 0)),
+            Op::JumpIfZero(end) => (jump_if_zero, (end as isize, 0, 0)),
+            Op::JumpIfNonZero(begin) => (jump_if_non_zero, (begin as isize, 0, 0)),
+            Op::AddMove(n, m) => (add_move, (n as isize, m, 0)),
+            Op::MoveJumpIfNonZero(n, begin) => (move_jump_if_non_zero, (n, begin as isize, 0)),
+            Op::AddMoveJumpIfNonZero(n, m, begin) => {
+                (add_move_jump_if_non_zero, (n as isize, m, begin as isize))
+            }
+        };
+        Threaded { run, args }
+    }
+}
+
+fn add<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    _: &mut I,
+    (n, _, _): Args,
+    pc: usize,
+) -> Result<usize> {
+    state.get_mut_cur().add(n as i32);
+    Ok(pc + 1)
+}
+
+fn move_by<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    _: &mut I,
+    (n, _, _): Args,
+    pc: usize,
+) -> Result<usize> {
+    move_pointer(state, n)?;
+    Ok(pc + 1)
+}
+
+fn output<C: Cell, I: BfIo>(state: &mut State<C>, io: &mut I, _: Args, pc: usize) -> Result<usize> {
+    state.output(io)?;
+    Ok(pc + 1)
+}
+
+fn input<C: Cell, I: BfIo>(state: &mut State<C>, io: &mut I, _: Args, pc: usize) -> Result<usize> {
+    state.input(io)?;
+    Ok(pc + 1)
+}
+
+fn clear<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    _: &mut I,
+    (add, _, _): Args,
+    pc: usize,
+) -> Result<usize> {
+    // The op counted as the `[`, the `-` and the `]` once, but the last two
+    // happen once for each iteration
+    state.instructions -= 2;
+    let steps = state.read_cur()?.steps_to_zero(add > 0);
+    state.instructions = state.instructions.saturating_add(steps.saturating_mul(2));
+    if steps > 0 {
+        *state.get_mut_cur() = C::default();
+    }
+    Ok(pc + 1)
+}
+
+fn jump_if_zero<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    _: &mut I,
+    (end, _, _): Args,
+    pc: usize,
+) -> Result<usize> {
+    if state.read_cur()?.is_zero() {
+        Ok(end as usize + 1)
+    } else {
+        // The op is at the `[`
+        state.loop_entered(state.position);
+        Ok(pc + 1)
+    }
+}
+
+fn jump_if_non_zero<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    _: &mut I,
+    (begin, _, _): Args,
+    pc: usize,
+) -> Result<usize> {
+    if state.read_cur()?.is_zero() {
+        state.loop_exited();
+        Ok(pc + 1)
+    } else {
+        state.checkpoint()?;
+        Ok(begin as usize + 1)
+    }
+}
+
+fn add_move<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    _: &mut I,
+    (n, m, _): Args,
+    pc: usize,
+) -> Result<usize> {
+    state.get_mut_cur().add(n as i32);
+    move_pointer(state, m)?;
+    Ok(pc + 1)
+}
+
+fn move_jump_if_non_zero<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    io: &mut I,
+    (n, begin, _): Args,
+    pc: usize,
+) -> Result<usize> {
+    move_pointer(state, n)?;
+    jump_if_non_zero(state, io, (begin, 0, 0), pc)
+}
+
+fn add_move_jump_if_non_zero<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    io: &mut I,
+    (n, m, begin): Args,
+    pc: usize,
+) -> Result<usize> {
+    state.get_mut_cur().add(n as i32);
+    move_pointer(state, m)?;
+    jump_if_non_zero(state, io, (begin, 0, 0), pc)
+}
+
+fn move_pointer<C: Cell>(state: &mut State<C>, n: isize) -> Result<()> {
+    for _ in 0..n.unsigned_abs() {
+        if n < 0 {
+            state.pointer_sub()?;
+        } else {
+            state.pointer_add()?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the jump at the start of every loop goes to the one at its end and back,
+/// and the loops nest, like the ops compiled from brackets that match
+fn jumps_pair_up(ops: &[Op]) -> bool {
+    let mut starts = Vec::new();
+    for (i, op) in ops.iter().enumerate() {
+        match (op, op.target()) {
+            (Op::JumpIfZero(end), _) => {
+                if *end <= i || ops.get(*end).and_then(|op| op.target()) != Some(i) {
+                    return false;
+                }
+                starts.push(i);
+            }
+            (_, Some(start)) if starts.pop() != Some(start) => return false,
+            _ => (),
+        }
+    }
+    starts.is_empty()
+}
+
+/// The range op doing `previous`, moving by `step` and doing `op`, if they make one
+fn extend_range(previous: Op, step: isize, op: Op) -> Option<Op> {
+    match (previous, op) {
+        (Op::Clear(cleared), Op::Clear(add)) if cleared == add => Some(Op::ClearRange(step, add)),
+        (Op::ClearRange(len, cleared), Op::Clear(add))
+            if cleared == add && len.signum() == step =>
+        {
+            Some(Op::ClearRange(len + step, add))
+        }
+        // Only ranges moving their cells against the steps shift them over
+        (Op::Transfer(moved, 1), Op::Transfer(offset, 1))
+            if moved == offset && offset.signum() == -step =>
+        {
+            Some(Op::TransferRange(step, offset))
+        }
+        (Op::TransferRange(len, moved), Op::Transfer(offset, 1))
+            if moved == offset && len.signum() == step =>
+        {
+            Some(Op::TransferRange(len + step, offset))
+        }
+        _ => None,
+    }
+}
+
+/// The superinstruction doing `first` and then `second`, if there is one
+fn fuse(first: Op, second: Op) -> Option<Op> {
+    match (first, second) {
+        (Op::Add(n), Op::Move(m)) => Some(Op::AddMove(n, m)),
+        (Op::Move(n), Op::JumpIfNonZero(begin)) => Some(Op::MoveJumpIfNonZero(n, begin)),
+        (Op::AddMove(n, m), Op::JumpIfNonZero(begin)) => {
+            Some(Op::AddMoveJumpIfNonZero(n, m, begin))
+        }
+        _ => None,
+    }
+}
+
+/// How far a loop with `body` moves each iteration if it only moves in one direction
+fn scan(body: &[Instruction]) -> Option<isize> {
+    let dir = *body.first()?;
+    let n = body.iter().take_while(|ins| **ins == dir).count();
+    match (dir, body.get(n)?) {
+        (Instruction::PtrIncr, Instruction::LoopEnd(_)) => Some(n as isize),
+        (Instruction::PtrDecr, Instruction::LoopEnd(_)) => Some(-(n as isize)),
+        _ => None,
+    }
+}
+
+/// How far away a loop with `body` adds to or subtracts from another cell and the -1 or 1
+/// it adds, if it does so while counting the current cell down, like `[->+<]` or `[>-<-]`
+fn transfer(body: &[Instruction]) -> Option<(isize, i32)> {
+    let (counted_first, body) = match body.split_first()? {
+        (Instruction::Decr, rest) => (true, rest),
+        _ => (false, body),
+    };
+    let (offset, there) = moves(body);
+    let add = match body.get(there)? {
+        Instruction::Incr => 1,
+        Instruction::Decr => -1,
+        _ => return None,
+    };
+    let (back, returned) = moves(&body[there + 1..]);
+    let rest = &body[there + 1 + returned..];
+    let end = match rest {
+        [end, ..] if counted_first => end,
+        [Instruction::Decr, end, ..] => end,
+        _ => return None,
+    };
+    (offset != 0 && back == -offset && matches!(end, Instruction::LoopEnd(_)))
+        .then_some((offset, add))
+}
+
+/// How far the moves at the start of `body` go if they go one way, and how many there are
+fn moves(body: &[Instruction]) -> (isize, usize) {
+    let Some(&dir @ (Instruction::PtrIncr | Instruction::PtrDecr)) = body.first() else {
+        return (0, 0);
+    };
+    let n = body.iter().take_while(|ins| **ins == dir).count();
+    if dir == Instruction::PtrIncr {
+        (n as isize, n)
+    } else {
+        (-(n as isize), n)
+    }
+}
+
+/// Where moving `n` from the pointer lands if the tape can be used as a slice for the way there,
+/// meaning uninitialized reads don't have to be checked and nothing wraps around
+fn slice_end<C: Cell>(state: &State<C>, n: isize) -> Option<usize> {
+    if state.uninit_reads != UninitReads::Allow || state.cells_limit.wraps() {
+        return None;
+    }
+    let end = state.cell_pointer.checked_add_signed(n)?;
+    match state.cells_limit.limit() {
+        Some(limit) if end >= limit => None,
+        _ => Some(end),
+    }
+}
+
+fn clear_range<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    _: &mut I,
+    (n, add, _): Args,
+    pc: usize,
+) -> Result<usize> {
+    // Counted like `clear` for every cell
+    state.instructions -= 2 * (n.unsigned_abs() as u64 + 1);
+    let iterations = |cell: &C| cell.steps_to_zero(add > 0).saturating_mul(2);
+    if let Some(end) = slice_end(state, n) {
+        let (low, high) = if n < 0 {
+            (end, state.cell_pointer)
+        } else {
+            (state.cell_pointer, end)
+        };
+        // Cells past the end of the tape are already zero
+        let high = (high + 1).min(state.cells.len());
+        if low < high {
+            for cell in &state.cells[low..high] {
+                state.instructions = state.instructions.saturating_add(iterations(cell));
+            }
+            state.cells[low..high].fill(C::default());
+        }
+        state.cell_pointer = end;
+        state.peak_pointer = state.peak_pointer.max(end);
+        return Ok(pc + 1);
+    }
+
+    for i in 0..=n.unsigned_abs() {
+        if i > 0 {
+            if n < 0 {
+                state.pointer_sub()?;
+            } else {
+                state.pointer_add()?;
+            }
+        }
+        let cell = state.read_cur()?;
+        state.instructions = state.instructions.saturating_add(iterations(&cell));
+        if !cell.is_zero() {
+            *state.get_mut_cur() = C::default();
+        }
+    }
+    Ok(pc + 1)
+}
+
+/// Moves by `n` until the current cell is zero
+fn scan_for_zero<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    _: &mut I,
+    (n, _, _): Args,
+    pc: usize,
+) -> Result<usize> {
+    let step = n.unsigned_abs();
+    // The op counted as the `[`, the moves and the `]` once, but the moves and the `]`
+    // happen once for each iteration
+    state.instructions -= step as u64 + 1;
+    let found = scan_slice(state, n).and_then(|iterations| {
+        let end = slice_end(state, n * iterations as isize)?;
+        Some((iterations, end))
+    });
+    if let Some((iterations, end)) = found {
+        state.cell_pointer = end;
+        state.peak_pointer = state.peak_pointer.max(end);
+        state.instructions += iterations as u64 * (step as u64 + 1);
+        return Ok(pc + 1);
+    }
+
+    while !state.read_cur()?.is_zero() {
+        move_pointer(state, n)?;
+        state.checkpoint()?;
+        state.instructions += step as u64 + 1;
+    }
+    Ok(pc + 1)
+}
+
+/// How many times `scan_for_zero` moves, if the zero cell can be found in the tape as a slice
+fn scan_slice<C: Cell>(state: &State<C>, n: isize) -> Option<usize> {
+    slice_end(state, 0)?;
+    let (cells, from, step) = (&state.cells, state.cell_pointer, n.unsigned_abs());
+    if from >= cells.len() {
+        return Some(0);
+    }
+    if n > 0 {
+        // Past the end of the tape every cell is zero
+        let past_end = (cells.len() - from).div_ceil(step);
+        let found = if step == 1 {
+            first_zero(&cells[from..], false)
+        } else {
+            cells[from..].iter().step_by(step).position(Cell::is_zero)
+        };
+        Some(found.unwrap_or(past_end))
+    } else if step == 1 {
+        first_zero(&cells[..=from], true)
+    } else {
+        cells[..=from]
+            .iter()
+            .rev()
+            .step_by(step)
+            .position(Cell::is_zero)
+    }
+}
+
+/// How many cells come before the first zero one in `cells`, or the last one if `rev`
+///
+/// The cells are checked in chunks without stopping at every cell,
+/// which the compiler can vectorize, before finding the zero in the chunk that has one.
+fn first_zero<C: Cell>(cells: &[C], rev: bool) -> Option<usize> {
+    const CHUNK: usize = 64;
+    let has_zero =
+        |(_, chunk): &(usize, &[C])| chunk.iter().fold(false, |zero, c| zero | c.is_zero());
+    let (i, chunk) = if rev {
+        cells.rchunks(CHUNK).enumerate().find(has_zero)?
+    } else {
+        cells.chunks(CHUNK).enumerate().find(has_zero)?
+    };
+    let found = if rev {
+        chunk.iter().rev().position(Cell::is_zero)
+    } else {
+        chunk.iter().position(Cell::is_zero)
+    };
+    Some(i * CHUNK + found?)
+}
+
+/// Counts the current cell down while adding `add` to the cell `offset` away
+fn transfer_value<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    _: &mut I,
+    (offset, add, _): Args,
+    pc: usize,
+) -> Result<usize> {
+    let per_iteration = 2 * offset.unsigned_abs() as u64 + 3;
+    // The op counted every command once, but all but the `[` happen once for each iteration
+    state.instructions -= per_iteration;
+    transfer_cell(state, offset, add > 0, per_iteration)?;
+    Ok(pc + 1)
+}
+
+/// Runs the loop of a `Transfer` on the current cell, counting `per_iteration` instructions
+/// for each of its iterations
+fn transfer_cell<C: Cell>(
+    state: &mut State<C>,
+    offset: isize,
+    up: bool,
+    per_iteration: u64,
+) -> Result<()> {
+    if let Some(to) = slice_end(state, offset) {
+        let from = state.cell_pointer;
+        // Past the end of the tape the cell is zero, and the loop never gets to the other cell
+        let Some(cell) = state
+            .cells
+            .get(from)
+            .filter(|cell| !cell.is_zero())
+            .cloned()
+        else {
+            return Ok(());
+        };
+        let iterations = cell.steps_to_zero(false);
+        state.instructions = state
+            .instructions
+            .saturating_add(iterations.saturating_mul(per_iteration));
+        if state.cells.len() <= to {
+            state.cells.resize(to + 1, C::default());
+        }
+        state.cells[to].add_cell(&cell, up);
+        state.cells[from] = C::default();
+        state.peak_pointer = state.peak_pointer.max(to);
+        return Ok(());
+    }
+
+    while !state.read_cur()?.is_zero() {
+        state.get_mut_cur().decrement();
+        move_pointer(state, offset)?;
+        state.get_mut_cur().add(if up { 1 } else { -1 });
+        move_pointer(state, -offset)?;
+        state.checkpoint()?;
+        state.instructions = state.instructions.saturating_add(per_iteration);
+    }
+    Ok(())
+}
+
+/// Transfers every cell of a range the way `transfer_value` does, moving by one in between
+fn transfer_range<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    _: &mut I,
+    (n, offset, _): Args,
+    pc: usize,
+) -> Result<usize> {
+    let per_iteration = 2 * offset.unsigned_abs() as u64 + 3;
+    // Counted like `transfer_value` for every cell
+    state.instructions -= per_iteration * (n.unsigned_abs() as u64 + 1);
+    // The cells moved to lie between the ends of the range and the first cell moved
+    if let (Some(end), Some(_)) = (slice_end(state, n), slice_end(state, offset)) {
+        shift_slice(state, n, offset, per_iteration);
+        state.cell_pointer = end;
+        state.peak_pointer = state.peak_pointer.max(end);
+        return Ok(pc + 1);
+    }
+
+    for i in 0..=n.unsigned_abs() {
+        if i > 0 {
+            move_pointer(state, n.signum())?;
+        }
+        transfer_cell(state, offset, true, per_iteration)?;
+    }
+    Ok(pc + 1)
+}
+
+/// Runs a `TransferRange` of `n` cells from the pointer on the tape as a slice,
+/// without moving the pointer
+///
+/// Each cell moves to one the range has already cleared, unless it's one of the first
+/// `offset` cells, so those are added to the cells outside the range and the rest
+/// are shifted over by rotating the range, clearing the cells left behind.
+fn shift_slice<C: Cell>(state: &mut State<C>, n: isize, offset: isize, per_iteration: u64) {
+    let from = state.cell_pointer;
+    let (low, high) = if n < 0 {
+        (from - n.unsigned_abs(), from + 1)
+    } else {
+        (from, from + n.unsigned_abs() + 1)
+    };
+    let len = high - low;
+    let first = len.min(offset.unsigned_abs());
+    if state.cells.len() < high {
+        state.cells.resize(high, C::default());
+    }
+    let iterations = state.cells[low..high]
+        .iter()
+        .map(|cell| cell.steps_to_zero(false))
+        .fold(0u64, u64::saturating_add);
+    state.instructions = state
+        .instructions
+        .saturating_add(iterations.saturating_mul(per_iteration));
+
+    if n < 0 {
+        // The loops only get to the cells after the range for the first cells that aren't zero
+        let Some(last) = state.cells[high - first..high]
+            .iter()
+            .rposition(|cell| !cell.is_zero())
+        else {
+            state.cells[low..high].rotate_right(first);
+            return;
+        };
+        let reached = high - first + last + offset.unsigned_abs();
+        state.peak_pointer = state.peak_pointer.max(reached);
+        if state.cells.len() <= reached {
+            state.cells.resize(reached + 1, C::default());
+        }
+        let (range, after) = state.cells.split_at_mut(high);
+        let targets = &mut after[offset.unsigned_abs() - first..][..=last];
+        for (to, cell) in targets.iter_mut().zip(&range[high - first..]) {
+            to.add_cell(cell, true);
+        }
+        range[low..].rotate_right(first);
+        range[low..low + first].fill(C::default());
+    } else {
+        let (before, range) = state.cells.split_at_mut(low);
+        let range = &mut range[..len];
+        let targets = &mut before[low - offset.unsigned_abs()..][..first];
+        for (to, cell) in targets.iter_mut().zip(&range[..first]) {
+            to.add_cell(cell, true);
+        }
+        range.rotate_left(first);
+        range[len - first..].fill(C::default());
+    }
+}
+
+impl From<&Program> for Bytecode {
+    #[inline]
+    fn from(program: &Program) -> Self {
+        Bytecode::compile(program)
+    }
+}
+
+/// Lists the ops one per line with their index and the source they came from
+impl Display for Bytecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .ops
+            .len()
+            .saturating_sub(1)
+            .checked_ilog10()
+            .unwrap_or(0) as usize
+            + 1;
+        for (i, (op, span)) in self.ops.iter().zip(&self.spans).enumerate() {
+            let text = match *op {
+                Op::Add(n) => alloc::format!("add {n}"),
+                Op::Move(n) => alloc::format!("move {n}"),
+                Op::Out => "out".into(),
+                Op::In => "in".into(),
+                Op::Clear(add) => alloc::format!("clear {add}"),
+                Op::ClearRange(n, add) => alloc::format!("clear {add} range {n}"),
+                Op::Scan(n) => alloc::format!("scan {n}"),
+                Op::Transfer(offset, add) => alloc::format!("transfer {add} to {offset}"),
+                Op::TransferRange(n, offset) => alloc::format!("shift {offset} range {n}"),
+                Op::JumpIfZero(target) => alloc::format!("jz {:>width$}", target + 1),
+                Op::JumpIfNonZero(target) => alloc::format!("jnz {:>width$}", target + 1),
+                Op::AddMove(n, m) => alloc::format!("add {n}, move {m}"),
+                Op::MoveJumpIfNonZero(n, target) => {
+                    alloc::format!("move {n}, jnz {:>width$}", target + 1)
+                }
+                Op::AddMoveJumpIfNonZero(n, m, target) => {
+                    alloc::format!("add {n}, move {m}, jnz {:>width$}", target + 1)
+                }
+            };
+            write!(f, "{i:>width$}  {text:<24}{}", span.start)?;
+            if span.commands > 1 {
+                write!(f, "-{}  ({} commands)", span.end, span.commands)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use core::{
+    fmt::{Debug, Display, LowerHex},
+    num::Wrapping,
+    str::FromStr,
+};
+
+/// A value on the tape
+///
+/// The default value is what every cell starts out as and is considered zero.
+pub trait Cell: Clone + Default + PartialEq + Debug + Send + 'static {
+    /// The plain number a cell holds, used when inspecting the tape
+    type Value: Copy + PartialEq + Debug + Display + LowerHex + FromStr;
+
+    /// `+`
+    fn increment(&mut self);
+    /// `-`
+    fn decrement(&mut self);
+    /// `n` increments at once, or decrements if `n` is negative
+    fn add(&mut self, n: i32) {
+        for _ in 0..n.unsigned_abs() {
+            if n < 0 {
+                self.decrement();
+            } else {
+                self.increment();
+            }
+        }
+    }
+    /// What moving `other` onto this cell with `[->+<]` does, or `[->-<]` if not `up`:
+    /// as many increments or decrements as it takes `-` for `other` to become zero
+    fn add_cell(&mut self, other: &Self, up: bool) {
+        for _ in 0..other.steps_to_zero(false) {
+            if up {
+                self.increment();
+            } else {
+                self.decrement();
+            }
+        }
+    }
+    /// Whether a loop on this cell ends
+    fn is_zero(&self) -> bool;
+    /// How many `+` if `up`, or `-` otherwise, it takes for the cell to become zero,
+    /// which is how many iterations `[+]` or `[-]` run for
+    fn steps_to_zero(&self, up: bool) -> u64 {
+        let mut cell = self.clone();
+        let mut steps = 0;
+        while !cell.is_zero() {
+            if up {
+                cell.increment();
+            } else {
+                cell.decrement();
+            }
+            steps += 1;
+        }
+        steps
+    }
+    /// The cell written by `,`
+    fn from_byte(byte: u8) -> Self;
+    /// The byte written by `.`
+    fn to_byte(&self) -> u8;
+    fn value(&self) -> Self::Value;
+    /// The cell holding `value`, like one saved from `value`
+    fn from_value(value: Self::Value) -> Self;
+}
+
+macro_rules! impl_wrapping_cell {
+    ($($t:ty),*) => {$(
+        impl Cell for Wrapping<$t> {
+            type Value = $t;
+
+            #[inline]
+            fn increment(&mut self) {
+                *self += Wrapping(1);
+            }
+            #[inline]
+            fn decrement(&mut self) {
+                *self -= Wrapping(1);
+            }
+            #[inline]
+            fn add(&mut self, n: i32) {
+                *self += Wrapping(n as $t);
+            }
+            #[inline]
+            fn add_cell(&mut self, other: &Self, up: bool) {
+                if up {
+                    *self += *other;
+                } else {
+                    *self -= *other;
+                }
+            }
+            #[inline]
+            fn is_zero(&self) -> bool {
+                self.0 == 0
+            }
+            #[inline]
+            fn steps_to_zero(&self, up: bool) -> u64 {
+                let value = if up { self.0.wrapping_neg() } else { self.0 };
+                // The value's bits as an unsigned number
+                value as u64 & (u64::MAX >> (64 - <$t>::BITS))
+            }
+            #[inline]
+            fn from_byte(byte: u8) -> Self {
+                Wrapping(byte as $t)
+            }
+            #[inline]
+            fn to_byte(&self) -> u8 {
+                self.0 as u8
+            }
+            #[inline]
+            fn value(&self) -> $t {
+                self.0
+            }
+            #[inline]
+            fn from_value(value: $t) -> Self {
+                Wrapping(value)
+            }
+        }
+    )*};
+}
+
+impl_wrapping_cell!(u8, u16, u32, u64, i8, i16, i32, i64);
@@ -0,0 +1,90 @@
+//! Shows the tape as a program runs, redrawing it after every few instructions
+
+use std::io::{stderr, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use brainfuck::{BfIo, Cell, InOuter, Layout, Program, Result, State};
+
+use super::TAPE_EXCERPT;
+
+/// How many lines a frame takes up
+const FRAME_LINES: usize = 2;
+
+pub struct Options {
+    /// How long each frame is shown
+    pub delay: Duration,
+    /// How many instructions are run between frames
+    pub every: u64,
+}
+
+pub fn run<W: Write, R: Read, C: Cell>(
+    program: &Program,
+    state: &mut State<C>,
+    io: &mut InOuter<W, R>,
+    options: &Options,
+) -> Result<()> {
+    let mut err = stderr().lock();
+    let mut io = Watched {
+        inner: io,
+        wrote: false,
+    };
+    // Frames are only redrawn in place if the program hasn't printed anything since the last one
+    let mut redraw = false;
+    let mut steps = 0;
+    let mut pc = 0;
+    while pc < program.len() {
+        let executed = pc;
+        pc = program.step(state, pc, &mut io)?;
+        steps += 1;
+        if steps % options.every != 0 && pc < program.len() {
+            continue;
+        }
+
+        io.inner.flush()?;
+        if io.wrote {
+            writeln!(err)?;
+        } else if redraw {
+            write!(err, "\x1b[{FRAME_LINES}F\x1b[J")?;
+        }
+        draw(&mut err, program, state, executed, steps)?;
+        io.wrote = false;
+        redraw = true;
+        thread::sleep(options.delay);
+    }
+    Ok(())
+}
+
+fn draw<C: Cell>(
+    out: &mut impl Write,
+    program: &Program,
+    state: &State<C>,
+    executed: usize,
+    steps: u64,
+) -> std::io::Result<()> {
+    let command = program.instructions()[executed].command();
+    let position = program.position(executed).unwrap_or_default();
+    writeln!(out, "step {steps:<8} {command:?} at {position}")?;
+    let first = state.cell_pointer.saturating_sub(TAPE_EXCERPT);
+    writeln!(
+        out,
+        "{first:>6}: {}",
+        state.dump(Layout::Inline).around(TAPE_EXCERPT)
+    )
+}
+
+/// Notes whether the program has written anything
+struct Watched<'a, I> {
+    inner: &'a mut I,
+    wrote: bool,
+}
+
+impl<I: BfIo> BfIo for Watched<'_, I> {
+    fn read(&mut self) -> Result<Option<u8>> {
+        self.inner.read()
+    }
+    fn write(&mut self, byte: u8) -> Result<()> {
+        self.wrote = true;
+        self.inner.write(byte)
+    }
+}
@@ -0,0 +1,18 @@
+use std::fs;
+use std::io::{stdout, Write};
+
+use brainfuck::{asm::compile, diagnostic::Diagnostic};
+
+use super::{Failure, Result};
+
+pub fn run(path: &str) -> Result<()> {
+    let src = fs::read(path)?;
+    let program = compile(&src)
+        .map_err(|e| Failure::Source(super::render(&Diagnostic::from(&e), path, &src)))?;
+
+    let mut out = stdout().lock();
+    out.write_all(&program)?;
+    writeln!(out)?;
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! Running a program once for every file in a directory with `--inputs`,
+//! writing what it prints to a file of the same name in `--outputs`
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use brainfuck::{
+    run_slice, Cell, Error, Flush, InOuter, Meter, Newline, Program, ResourceUsage, State,
+};
+
+use super::{Failure, Halt, Result};
+
+/// Runs `src` on a copy of `template` for every file in `inputs`, with the `newlines`
+/// of the input and output files, reporting the runs that fail and what all of them used to stderr.
+/// Fails if any of them did.
+pub fn run<C: Cell>(
+    src: &[u8],
+    template: &State<C>,
+    inputs: &Path,
+    outputs: &Path,
+    newlines: (Newline, Newline),
+    locate: impl Fn(Error) -> Failure,
+) -> Result<()> {
+    // Fails once for a program that can't run on any input, instead of for each one
+    Program::parse(src).map_err(&locate)?;
+
+    let mut paths = fs::read_dir(inputs)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    paths.retain(|path| path.is_file());
+    paths.sort();
+    fs::create_dir_all(outputs)?;
+
+    let mut total = ResourceUsage {
+        instructions: 0,
+        bytes_read: 0,
+        bytes_written: 0,
+        peak_tape_bytes: 0,
+        wall_time: Duration::ZERO,
+        cpu_time: Some(Duration::ZERO),
+    };
+    let mut failed = 0;
+    for input in &paths {
+        let output = outputs.join(input.file_name().unwrap());
+        let mut state = template.clone();
+        let mut io = InOuter::new(
+            BufWriter::new(File::create(&output)?),
+            BufReader::new(File::open(input)?),
+        );
+        io.set_flush(Flush::Full);
+        io.set_newlines(newlines.0, newlines.1);
+
+        let meter = Meter::start(&state);
+        let result = run_slice(src, &mut state, &mut io).map(drop);
+        let flushed = io.flush();
+        let usage = meter.finish(&state);
+        total.instructions += usage.instructions;
+        total.bytes_read += usage.bytes_read;
+        total.bytes_written += usage.bytes_written;
+        total.peak_tape_bytes = total.peak_tape_bytes.max(usage.peak_tape_bytes);
+        total.wall_time += usage.wall_time;
+        total.cpu_time = total.cpu_time.zip(usage.cpu_time).map(|(a, b)| a + b);
+
+        let halt = Halt::of(&state);
+        if let Err(e) = result
+            .and(flushed)
+            .and_then(|()| state.evaluate().map(drop))
+        {
+            failed += 1;
+            eprintln!("{}:", input.display());
+            locate(e).halted_at(halt).report();
+        }
+    }
+
+    let runs = paths.len();
+    let s = if runs == 1 { "" } else { "s" };
+    eprintln!(
+        "{runs} run{s}, {} succeeded, {failed} failed",
+        runs - failed
+    );
+    eprintln!("{total}");
+    if failed > 0 {
+        return Err(Failure::Source(format!(
+            "error: {failed} of {runs} inputs failed\n"
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Bytecode compiled by `--compiled`, saved in the user's cache directory so that running
+//! the same program again skips parsing and optimizing it
+//!
+//! Cached bytecode is named after a hash of the source, the version of the interpreter
+//! that compiled it and the options it was compiled with. Bytecode doesn't depend on how
+//! the tape is configured, so every configuration runs from the same file.
+//! Bytecode that fails to load, like a corrupt file, is compiled and saved again.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use brainfuck::{
+    bytecode::{Bytecode, OptLevel},
+    Program, Result,
+};
+
+/// The bytecode of `src` compiled at `level`, from the cache if it has been compiled before.
+/// Failing to read or write the cache only means compiling it.
+pub fn bytecode(src: &[u8], level: OptLevel, cached: bool) -> Result<Bytecode> {
+    let path = cached.then(|| cache_path(src, level)).flatten();
+    if let Some(bytecode) = path
+        .as_ref()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|bytes| Bytecode::from_bytes(&bytes))
+    {
+        return Ok(bytecode);
+    }
+
+    let bytecode = Bytecode::compile_with(&Program::parse(src)?, level);
+    if let Some(path) = path {
+        // Written next to it first so that other runs never read it half written
+        let partial = path.with_extension(format!("{}.partial", std::process::id()));
+        let saved = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&partial, bytecode.to_bytes()))
+            .and_then(|()| fs::rename(&partial, &path));
+        if saved.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+    }
+    Ok(bytecode)
+}
+
+fn cache_path(src: &[u8], level: OptLevel) -> Option<PathBuf> {
+    let cache_dir = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    }?;
+
+    let options = [level as u8];
+    let key = hash(src, FNV_OFFSET);
+    let key = hash(env!("CARGO_PKG_VERSION").as_bytes(), hash(&options, key));
+    Some(cache_dir.join("brainfuck").join(format!("{key:016x}.bfc")))
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// FNV-1a, which unlike `DefaultHasher` hashes the same in every build,
+/// continuing from the hash `state`
+fn hash(bytes: &[u8], state: u64) -> u64 {
+    bytes.iter().fold(state, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(FNV_PRIME)
+    })
+}
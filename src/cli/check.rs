@@ -0,0 +1,89 @@
+use std::fs;
+use std::num::NonZeroUsize;
+
+use brainfuck::{
+    analysis::analyze,
+    diagnostic::Diagnostic,
+    differential::{self, Checked},
+    lint::lint,
+    preprocess::Preprocessor,
+    CellsLimit, Eof, Program, State,
+};
+
+use super::{Failure, Result};
+
+/// How to run the program for `--differential`
+pub struct Differential {
+    pub inputs: Vec<String>,
+    pub fuel: u64,
+    pub cells: NonZeroUsize,
+    pub wrap: bool,
+}
+
+pub fn run(
+    path: &str,
+    lints: bool,
+    differential: Option<Differential>,
+    preprocessor: &Preprocessor,
+) -> Result<()> {
+    let (src, expanded) = super::read_source(path, preprocessor)?;
+
+    let program = Program::parse(&expanded.src).map_err(|e| {
+        let (e, path, src) = super::locate(e, path, &src, &expanded);
+        Failure::in_source(e, path, src)
+    })?;
+
+    let mut findings = analyze(&program);
+    if lints {
+        let analyzed: Vec<_> = findings.iter().map(|f| f.position).collect();
+        // `[]` is found by both
+        findings.extend(
+            lint(&expanded.src)
+                .into_iter()
+                .filter(|lint| !analyzed.contains(&lint.position)),
+        );
+        findings.sort_by_key(|f| f.position.offset);
+    }
+    for mut finding in findings {
+        let (path, src) = expanded.file(finding.position).unwrap_or((path, &src));
+        finding.position = expanded.origin(finding.position);
+        eprint!("{}", super::render(&Diagnostic::from(&finding), path, src));
+    }
+
+    match differential {
+        Some(differential) => check_engines(&expanded.src, differential),
+        None => Ok(()),
+    }
+}
+
+/// Fails with the first difference between the engines on any of the inputs
+fn check_engines(src: &[u8], differential: Differential) -> Result<()> {
+    let limit = (differential.cells, differential.wrap);
+    let mut template = State::new(CellsLimit::new(Some(limit)));
+    template.set_eof(Eof::Unchanged);
+
+    let inputs = match differential.inputs.is_empty() {
+        true => vec![("no input".to_owned(), Vec::new())],
+        false => differential
+            .inputs
+            .into_iter()
+            .map(|path| fs::read(&path).map(|input| (path, input)))
+            .collect::<std::io::Result<_>>()?,
+    };
+    for (name, input) in &inputs {
+        match differential::check(src, &template, input, differential.fuel) {
+            Ok(Checked::Agreed) => (),
+            Ok(Checked::OutOfFuel) => eprintln!(
+                "warning: the engines weren't compared on {name}, \
+                 since it ran for more than {} instructions",
+                differential.fuel
+            ),
+            Err(divergence) => {
+                return Err(Failure::Source(format!(
+                    "error: the engines disagree on {name}: {divergence}\n"
+                )))
+            }
+        }
+    }
+    Ok(())
+}
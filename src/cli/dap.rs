@@ -0,0 +1,467 @@
+//! A debug adapter speaking the Debug Adapter Protocol over stdin and stdout
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::stdin;
+use std::sync::mpsc::{channel, TryRecvError};
+use std::thread;
+
+use brainfuck::{diagnostic::Diagnostic, Eof, FnIo, Instruction, Layout, Program, State};
+
+use super::json::Json;
+use super::message::{read_message, send};
+use super::Result;
+
+/// How many instructions run between checks for new requests while running
+const BATCH: usize = 10_000;
+
+/// The program being debugged
+struct Session {
+    path: String,
+    program: Program,
+    state: State,
+    input: Vec<u8>,
+    pc: usize,
+    /// Instructions to stop before
+    breakpoints: BTreeSet<usize>,
+    stop_on_entry: bool,
+}
+
+/// What the program is doing between requests
+enum Mode {
+    Stopped,
+    /// Running until a breakpoint, or until `pc` reaches the given instruction
+    Running(Option<usize>),
+}
+
+struct Adapter {
+    seq: u64,
+    session: Option<Session>,
+    mode: Mode,
+    /// Breakpoint lines by path, set before the program is launched
+    lines: Vec<(String, Vec<u64>)>,
+}
+
+pub fn run() -> Result<()> {
+    let (sender, requests) = channel();
+    thread::spawn(move || {
+        let mut input = stdin().lock();
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if let Some(message) = Json::parse(&message) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut adapter = Adapter {
+        seq: 0,
+        session: None,
+        mode: Mode::Stopped,
+        lines: Vec::new(),
+    };
+    loop {
+        let request = match adapter.mode {
+            Mode::Stopped => match requests.recv() {
+                Ok(request) => Some(request),
+                Err(_) => break,
+            },
+            Mode::Running(_) => match requests.try_recv() {
+                Ok(request) => Some(request),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => break,
+            },
+        };
+        match request {
+            Some(request) => {
+                if !adapter.handle(&request)? {
+                    break;
+                }
+            }
+            None => adapter.run_batch()?,
+        }
+    }
+
+    Ok(())
+}
+
+impl Adapter {
+    fn send(&mut self, mut message: Vec<(&str, Json)>) -> Result<()> {
+        self.seq += 1;
+        message.insert(0, ("seq", self.seq.into()));
+        send(&Json::Object(
+            message
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v))
+                .collect(),
+        ))
+    }
+    fn event(&mut self, event: &str, body: Json) -> Result<()> {
+        self.send(vec![
+            ("type", "event".into()),
+            ("event", event.into()),
+            ("body", body),
+        ])
+    }
+    fn respond(&mut self, request: &Json, result: std::result::Result<Json, String>) -> Result<()> {
+        let mut message = vec![
+            ("type", "response".into()),
+            ("request_seq", request.get("seq").clone()),
+            ("command", request.get("command").clone()),
+            ("success", result.is_ok().into()),
+        ];
+        match result {
+            Ok(body) => message.push(("body", body)),
+            Err(e) => message.push(("message", e.into())),
+        }
+        self.send(message)
+    }
+    fn stopped(&mut self, reason: &str) -> Result<()> {
+        self.mode = Mode::Stopped;
+        self.event(
+            "stopped",
+            Json::object([
+                ("reason", reason.into()),
+                ("threadId", 1.into()),
+                ("allThreadsStopped", true.into()),
+            ]),
+        )
+    }
+    fn terminate(&mut self) -> Result<()> {
+        self.mode = Mode::Stopped;
+        self.session = None;
+        self.event("exited", Json::object([("exitCode", 0.into())]))?;
+        self.event("terminated", Json::object([]))
+    }
+
+    /// Handles a request, returning whether to keep going
+    fn handle(&mut self, request: &Json) -> Result<bool> {
+        let args = request.get("arguments");
+        let result = match request.get("command").as_str().unwrap_or("") {
+            "initialize" => {
+                self.respond(
+                    request,
+                    Ok(Json::object([
+                        ("supportsConfigurationDoneRequest", true.into()),
+                        ("supportsTerminateRequest", true.into()),
+                    ])),
+                )?;
+                return self.event("initialized", Json::object([])).map(|()| true);
+            }
+            "launch" => self.launch(args),
+            "setBreakpoints" => Ok(self.set_breakpoints(args)),
+            "configurationDone" => {
+                self.respond(request, Ok(Json::Null))?;
+                match &self.session {
+                    Some(session) if session.stop_on_entry => self.stopped("entry")?,
+                    Some(_) => self.mode = Mode::Running(None),
+                    None => (),
+                }
+                return Ok(true);
+            }
+            "threads" => Ok(Json::object([(
+                "threads",
+                Json::Array(vec![Json::object([
+                    ("id", 1.into()),
+                    ("name", "main".into()),
+                ])]),
+            )])),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => Ok(Json::object([(
+                "scopes",
+                Json::Array(vec![
+                    Json::object([
+                        ("name", "Tape".into()),
+                        ("variablesReference", 1.into()),
+                        ("expensive", false.into()),
+                    ]),
+                    Json::object([
+                        ("name", "State".into()),
+                        ("variablesReference", 2.into()),
+                        ("expensive", false.into()),
+                    ]),
+                ]),
+            )])),
+            "variables" => Ok(self.variables(args.get("variablesReference").as_u64())),
+            "continue" => {
+                self.respond(
+                    request,
+                    Ok(Json::object([("allThreadsContinued", true.into())])),
+                )?;
+                self.mode = Mode::Running(None);
+                // Move off a breakpoint the program is stopped on
+                return self.step(None).map(|()| true);
+            }
+            "next" | "stepIn" => {
+                self.respond(request, Ok(Json::Null))?;
+                return self.step(Some("step")).map(|()| true);
+            }
+            "stepOut" => {
+                self.respond(request, Ok(Json::Null))?;
+                let end = self
+                    .session
+                    .as_ref()
+                    .and_then(|s| loop_end(&s.program, s.pc));
+                match end {
+                    Some(end) => {
+                        self.mode = Mode::Running(Some(end + 1));
+                        return self.step(None).map(|()| true);
+                    }
+                    None => return self.step(Some("step")).map(|()| true),
+                }
+            }
+            "pause" => {
+                self.respond(request, Ok(Json::Null))?;
+                if let Mode::Running(_) = self.mode {
+                    self.stopped("pause")?;
+                }
+                return Ok(true);
+            }
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(Json::Null))?;
+                return Ok(false);
+            }
+            command => Err(format!("unsupported request {command}")),
+        };
+        self.respond(request, result)?;
+        Ok(true)
+    }
+
+    fn launch(&mut self, args: &Json) -> std::result::Result<Json, String> {
+        let path = args
+            .get("program")
+            .as_str()
+            .ok_or("no program to debug was given")?;
+        let mut src = fs::read(path).map_err(|e| format!("could not read {path}: {e}"))?;
+        brainfuck::blank_shebang(&mut src);
+        let program = Program::parse(&src).map_err(|e| {
+            let diagnostic = Diagnostic::from(&e);
+            diagnostic.render(path, &src)
+        })?;
+        let mut state = State::default();
+        state.set_eof(Eof::Zero);
+
+        let mut session = Session {
+            path: path.to_owned(),
+            program,
+            state,
+            input: args
+                .get("input")
+                .as_str()
+                .unwrap_or("")
+                .bytes()
+                .rev()
+                .collect(),
+            pc: 0,
+            breakpoints: BTreeSet::new(),
+            stop_on_entry: args.get("stopOnEntry").as_bool().unwrap_or(false),
+        };
+        if let Some((_, lines)) = self.lines.iter().find(|(p, _)| p == path) {
+            session.breakpoints = breakpoints(&session.program, lines)
+                .into_iter()
+                .flatten()
+                .collect();
+        }
+        self.session = Some(session);
+        Ok(Json::Null)
+    }
+
+    fn set_breakpoints(&mut self, args: &Json) -> Json {
+        let path = args.get("source").get("path").as_str().unwrap_or("");
+        let lines: Vec<u64> = args
+            .get("breakpoints")
+            .as_array()
+            .iter()
+            .filter_map(|bp| bp.get("line").as_u64())
+            .collect();
+
+        let verified: Vec<bool> = match &mut self.session {
+            Some(session) if session.path == path => {
+                let found = breakpoints(&session.program, &lines);
+                session.breakpoints = found.iter().flatten().copied().collect();
+                found.iter().map(Option::is_some).collect()
+            }
+            _ => vec![true; lines.len()],
+        };
+        self.lines.retain(|(p, _)| p != path);
+        self.lines.push((path.to_owned(), lines.clone()));
+
+        Json::object([(
+            "breakpoints",
+            Json::Array(
+                lines
+                    .iter()
+                    .zip(verified)
+                    .map(|(&line, verified)| {
+                        Json::object([("verified", verified.into()), ("line", line.into())])
+                    })
+                    .collect(),
+            ),
+        )])
+    }
+
+    fn stack_trace(&self) -> Json {
+        let frames = match &self.session {
+            Some(session) => {
+                let pos = session
+                    .program
+                    .position(session.pc)
+                    .or_else(|| session.program.position(session.pc.wrapping_sub(1)))
+                    .unwrap_or_default();
+                vec![Json::object([
+                    ("id", 1.into()),
+                    ("name", "main".into()),
+                    (
+                        "source",
+                        Json::object([("path", session.path.as_str().into())]),
+                    ),
+                    ("line", pos.line.into()),
+                    ("column", pos.column.into()),
+                ])]
+            }
+            None => Vec::new(),
+        };
+        Json::object([
+            ("totalFrames", frames.len().into()),
+            ("stackFrames", Json::Array(frames)),
+        ])
+    }
+
+    fn variables(&self, reference: Option<u64>) -> Json {
+        let variable = |name: String, value: String| {
+            Json::object([
+                ("name", name.into()),
+                ("value", value.into()),
+                ("variablesReference", 0.into()),
+            ])
+        };
+        let variables = match (&self.session, reference) {
+            (Some(session), Some(1)) => {
+                let state = &session.state;
+                let mut cells = state.cells();
+                cells.trim_end();
+                let len = cells.len().max(state.cell_pointer + 1);
+                state
+                    .cells()
+                    .chain(std::iter::repeat(0))
+                    .take(len)
+                    .enumerate()
+                    .map(|(i, cell)| {
+                        let name = if i == state.cell_pointer {
+                            format!("[{i}] <")
+                        } else {
+                            format!("[{i}]")
+                        };
+                        variable(name, cell.to_string())
+                    })
+                    .collect()
+            }
+            (Some(session), Some(2)) => vec![
+                variable("pointer".to_owned(), session.state.cell_pointer.to_string()),
+                variable(
+                    "instructions".to_owned(),
+                    session.state.instructions().to_string(),
+                ),
+                variable("pc".to_owned(), session.pc.to_string()),
+                variable(
+                    "tape".to_owned(),
+                    session.state.dump(Layout::RunLength).to_string(),
+                ),
+            ],
+            _ => Vec::new(),
+        };
+        Json::object([("variables", Json::Array(variables))])
+    }
+
+    /// Executes one instruction, reporting a stop with `reason` if given
+    fn step(&mut self, reason: Option<&str>) -> Result<()> {
+        let Some(session) = &mut self.session else {
+            return Ok(());
+        };
+        if session.pc >= session.program.len() {
+            return self.terminate();
+        }
+
+        let mut output = Vec::new();
+        let input = &mut session.input;
+        let mut io = FnIo::new(|| input.pop(), |byte| output.push(byte));
+        let result = session
+            .program
+            .step(&mut session.state, session.pc, &mut io);
+        let ended = match result {
+            Ok(pc) => {
+                session.pc = pc;
+                pc >= session.program.len()
+            }
+            Err(e) => {
+                output.extend(format!("\n{e}\n").bytes());
+                true
+            }
+        };
+        self.output(&output)?;
+
+        if ended {
+            self.terminate()
+        } else if let Some(reason) = reason {
+            self.stopped(reason)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn run_batch(&mut self) -> Result<()> {
+        for _ in 0..BATCH {
+            let Mode::Running(until) = self.mode else {
+                return Ok(());
+            };
+            if let Some(session) = &self.session {
+                if session.breakpoints.contains(&session.pc) {
+                    return self.stopped("breakpoint");
+                }
+                if until == Some(session.pc) {
+                    return self.stopped("step");
+                }
+            }
+            self.step(None)?;
+        }
+        Ok(())
+    }
+
+    fn output(&mut self, output: &[u8]) -> Result<()> {
+        if output.is_empty() {
+            return Ok(());
+        }
+        self.event(
+            "output",
+            Json::object([
+                ("category", "stdout".into()),
+                (
+                    "output",
+                    String::from_utf8_lossy(output).into_owned().into(),
+                ),
+            ]),
+        )
+    }
+}
+
+/// The first instruction on each of `lines`
+fn breakpoints(program: &Program, lines: &[u64]) -> Vec<Option<usize>> {
+    lines
+        .iter()
+        .map(|&line| {
+            (0..program.len()).find(|&pc| program.position(pc).map(|p| p.line as u64) == Some(line))
+        })
+        .collect()
+}
+
+/// The `]` of the innermost loop containing `pc`
+fn loop_end(program: &Program, pc: usize) -> Option<usize> {
+    let instructions = program.instructions();
+    (0..pc.min(instructions.len()))
+        .rev()
+        .find_map(|i| match instructions[i] {
+            Instruction::LoopBegin(end) if end >= pc => Some(end),
+            _ => None,
+        })
+}
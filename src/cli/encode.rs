@@ -0,0 +1,190 @@
+//! Encodings of the program's input and output, for programs that read or print binary data
+
+use std::io::{self, BufReader, Bytes, ErrorKind, Read, Write};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Writes every byte as two hex digits, separated by spaces if `spaced`,
+/// and ends with a newline once something has been written
+pub struct Hex<W: Write> {
+    inner: W,
+    spaced: bool,
+    written: bool,
+}
+
+impl<W: Write> Hex<W> {
+    pub fn new(inner: W, spaced: bool) -> Self {
+        Hex {
+            inner,
+            spaced,
+            written: false,
+        }
+    }
+}
+
+impl<W: Write> Write for Hex<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut hex = Vec::with_capacity(3 * buf.len());
+        for &byte in buf {
+            if self.spaced && (self.written || !hex.is_empty()) {
+                hex.push(b' ');
+            }
+            write!(hex, "{byte:02x}")?;
+        }
+        self.inner.write_all(&hex)?;
+        self.written |= !buf.is_empty();
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for Hex<W> {
+    fn drop(&mut self) {
+        // Dropping can't fail, like `BufWriter`
+        if self.written {
+            let _ = self
+                .inner
+                .write_all(b"\n")
+                .and_then(|()| self.inner.flush());
+        }
+    }
+}
+
+/// Writes bytes as base64 as they come in groups of three,
+/// ending with the rest of them, padding and a newline
+pub struct Base64Encoder<W: Write> {
+    inner: W,
+    group: Vec<u8>,
+    written: bool,
+}
+
+impl<W: Write> Base64Encoder<W> {
+    pub fn new(inner: W) -> Self {
+        Base64Encoder {
+            inner,
+            group: Vec::with_capacity(3),
+            written: false,
+        }
+    }
+}
+
+/// The four base64 characters for up to three bytes, padded if there are less
+fn encode_group(group: &[u8]) -> [u8; 4] {
+    let mut bytes = [0; 3];
+    bytes[..group.len()].copy_from_slice(group);
+    let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+    let mut chars = [b'='; 4];
+    for (i, c) in chars.iter_mut().enumerate().take(group.len() + 1) {
+        *c = BASE64[(n >> (18 - 6 * i)) as usize & 0x3f];
+    }
+    chars
+}
+
+impl<W: Write> Write for Base64Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut encoded = Vec::with_capacity(4 * buf.len() / 3 + 4);
+        for &byte in buf {
+            self.group.push(byte);
+            if self.group.len() == 3 {
+                encoded.extend(encode_group(&self.group));
+                self.group.clear();
+            }
+        }
+        self.inner.write_all(&encoded)?;
+        self.written |= !buf.is_empty();
+        Ok(buf.len())
+    }
+    /// Flushes the groups that are complete, since the rest can't be encoded until they are
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for Base64Encoder<W> {
+    fn drop(&mut self) {
+        if self.written {
+            let mut rest = Vec::new();
+            if !self.group.is_empty() {
+                rest.extend(encode_group(&self.group));
+            }
+            rest.push(b'\n');
+            let _ = self
+                .inner
+                .write_all(&rest)
+                .and_then(|()| self.inner.flush());
+        }
+    }
+}
+
+/// Reads bytes decoded from base64, ignoring whitespace.
+/// Padding is optional and ends the input.
+pub struct Base64Decoder<R: Read> {
+    inner: Bytes<BufReader<R>>,
+    decoded: [u8; 3],
+    start: usize,
+    end: usize,
+    ended: bool,
+}
+
+impl<R: Read> Base64Decoder<R> {
+    pub fn new(inner: R) -> Self {
+        Base64Decoder {
+            inner: BufReader::new(inner).bytes(),
+            decoded: [0; 3],
+            start: 0,
+            end: 0,
+            ended: false,
+        }
+    }
+    /// Decodes the next group of up to four characters
+    fn decode_group(&mut self) -> io::Result<()> {
+        let mut n = 0;
+        let mut len: usize = 0;
+        while len < 4 && !self.ended {
+            let c = match self.inner.next().transpose()? {
+                Some(c) => c,
+                None => break,
+            };
+            let value = match c {
+                b'=' => {
+                    self.ended = true;
+                    break;
+                }
+                c if c.is_ascii_whitespace() => continue,
+                c => BASE64.iter().position(|&b| b == c).ok_or_else(|| {
+                    io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("`{}` is not base64", c.escape_ascii()),
+                    )
+                })?,
+            };
+            n |= (value as u32) << (18 - 6 * len);
+            len += 1;
+        }
+        if len == 1 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "the base64 input ends in the middle of a byte",
+            ));
+        }
+        let [_, bytes @ ..] = n.to_be_bytes();
+        self.decoded = bytes;
+        self.start = 0;
+        self.end = len.saturating_sub(1);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Base64Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.start == self.end {
+            self.decode_group()?;
+        }
+        let n = buf.len().min(self.end - self.start);
+        buf[..n].copy_from_slice(&self.decoded[self.start..self.start + n]);
+        self.start += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,49 @@
+use std::fs;
+use std::process::ExitCode;
+
+use brainfuck::{
+    equivalence::{compare, random_inputs},
+    Eof, Program, State,
+};
+
+use super::{Failure, Result};
+
+pub struct Options {
+    pub inputs: Vec<String>,
+    pub random: usize,
+    pub seed: u64,
+    pub fuel: u64,
+}
+
+/// Succeeds if no difference is found
+pub fn run(left_path: &str, right_path: &str, options: Options) -> Result<ExitCode> {
+    let left_src = super::read_program(left_path)?;
+    let right_src = super::read_program(right_path)?;
+    let left =
+        Program::parse(&left_src).map_err(|e| Failure::in_source(e, left_path, &left_src))?;
+    let right =
+        Program::parse(&right_src).map_err(|e| Failure::in_source(e, right_path, &right_src))?;
+
+    let mut inputs = Vec::new();
+    for path in &options.inputs {
+        inputs.push(fs::read(path)?);
+    }
+    let count = inputs.len() + options.random;
+
+    let mut template = State::default();
+    template.set_eof(Eof::Zero);
+
+    let inputs = inputs
+        .into_iter()
+        .chain(random_inputs(options.seed, options.random, 64));
+    match compare(&left, &right, &template, inputs, options.fuel) {
+        Some(divergence) => {
+            println!("Programs differ {divergence}");
+            Ok(ExitCode::FAILURE)
+        }
+        None => {
+            println!("No difference found on {count} inputs");
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
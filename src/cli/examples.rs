@@ -0,0 +1,54 @@
+//! Classic programs built into the binary, to try it out without any sources at hand
+
+use std::io::{stdout, Write};
+
+use super::{Failure, Result};
+
+pub struct Example {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub source: &'static [u8],
+}
+
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        name: "hello",
+        description: "prints Hello World!",
+        source: include_bytes!("../../examples/helloworld.brainfuck"),
+    },
+    Example {
+        name: "cat",
+        description: "copies its input to its output",
+        source: include_bytes!("../../examples/cat.brainfuck"),
+    },
+    Example {
+        name: "rot13",
+        description: "rotates the letters of its input by 13 places",
+        source: include_bytes!("../../examples/rot13.brainfuck"),
+    },
+    Example {
+        name: "mandelbrot",
+        description: "draws the Mandelbrot set as text",
+        source: include_bytes!("../../examples/mandelbrot.brainfuck"),
+    },
+];
+
+pub fn find(name: &str) -> Result<&'static Example> {
+    EXAMPLES
+        .iter()
+        .find(|example| example.name == name)
+        .ok_or_else(|| {
+            Failure::Source(format!(
+                "error: no example called `{name}`, `brainfuck examples` lists them\n"
+            ))
+        })
+}
+
+/// Prints the name and description of every example
+pub fn list() -> Result<()> {
+    let mut out = stdout().lock();
+    for example in EXAMPLES {
+        writeln!(out, "{:<12}{}", example.name, example.description)?;
+    }
+    Ok(())
+}
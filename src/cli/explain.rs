@@ -0,0 +1,312 @@
+//! Narrates what a program does as it runs, in plain English
+
+use std::cell::RefCell;
+use std::io::{self, stderr, Read, StderrLock, Write};
+
+use brainfuck::{BfIo, Cell, InOuter, Instruction, Program, Result, State};
+
+/// How many changed cells are named before the rest are only counted
+const MAX_CELLS: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detail {
+    /// Every loop that isn't inside another loop, and the commands between them
+    Outer,
+    /// Every loop each time it ends, and the commands between the outermost loops
+    Loops,
+    /// Every instruction
+    Steps,
+}
+
+/// Runs `program`, writing what it does at the given level of `detail` to stderr
+pub fn run<W: Write, R: Read, C: Cell>(
+    program: &Program,
+    state: &mut State<C>,
+    io: &mut InOuter<W, R>,
+    detail: Detail,
+) -> Result<()> {
+    let printed = RefCell::new(Vec::new());
+    let mut io = Printed {
+        inner: io,
+        printed: &printed,
+    };
+    let mut narrator = Narrator {
+        program,
+        detail,
+        printed: &printed,
+        out: stderr().lock(),
+        error: None,
+        loops: Vec::new(),
+        segment: None,
+        step: None,
+    };
+
+    let result = program.run_traced(state, &mut io, |pc, instruction, state| {
+        narrator.before(pc, instruction, state);
+    });
+    io.inner.flush()?;
+    narrator.finish(state)?;
+    result
+}
+
+/// What the tape looked like when a part of the program started
+struct Snapshot<V> {
+    cells: Vec<V>,
+    pointer: usize,
+    printed: usize,
+}
+
+/// A loop that has been entered and hasn't ended yet
+struct Active<V> {
+    begin: usize,
+    end: usize,
+    iterations: u64,
+    /// Only taken for loops that are narrated
+    before: Option<Snapshot<V>>,
+}
+
+/// Straight-line code outside of any loop
+struct Segment<V> {
+    first: usize,
+    last: usize,
+    before: Snapshot<V>,
+}
+
+struct Narrator<'a, C: Cell> {
+    program: &'a Program,
+    detail: Detail,
+    printed: &'a RefCell<Vec<u8>>,
+    out: StderrLock<'static>,
+    error: Option<io::Error>,
+    loops: Vec<Active<C::Value>>,
+    segment: Option<Segment<C::Value>>,
+    /// The last instruction and the pointer before it, for `Detail::Steps`
+    step: Option<(usize, usize)>,
+}
+
+impl<C: Cell> Narrator<'_, C> {
+    /// Called before the instruction at `pc` is executed, which is when the previous one is done
+    fn before(&mut self, pc: usize, instruction: Instruction, state: &State<C>) {
+        if self.detail == Detail::Steps {
+            if let Some((last, pointer)) = self.step.replace((pc, state.cell_pointer)) {
+                self.narrate_step(last, pointer, pc, state);
+            }
+            return;
+        }
+
+        self.end_loops(pc, state);
+        if let Some(top) = self.loops.last_mut() {
+            if pc == top.begin + 1 {
+                top.iterations += 1;
+            }
+        }
+
+        match instruction {
+            Instruction::LoopBegin(end) => {
+                let narrated = self.loops.is_empty() || self.detail == Detail::Loops;
+                if self.loops.is_empty() {
+                    self.end_segment(state);
+                }
+                self.loops.push(Active {
+                    begin: pc,
+                    end,
+                    iterations: 0,
+                    before: narrated.then(|| self.snapshot(state)),
+                });
+            }
+            _ if self.loops.is_empty() => match &mut self.segment {
+                Some(segment) => segment.last = pc,
+                None => {
+                    self.segment = Some(Segment {
+                        first: pc,
+                        last: pc,
+                        before: self.snapshot(state),
+                    })
+                }
+            },
+            _ => (),
+        }
+    }
+
+    fn finish(mut self, state: &State<C>) -> io::Result<()> {
+        if let Some((last, pointer)) = self.step.take() {
+            self.narrate_step(last, pointer, self.program.len(), state);
+        }
+        self.end_loops(self.program.len(), state);
+        self.end_segment(state);
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Ends the loops that `pc` is outside of
+    fn end_loops(&mut self, pc: usize, state: &State<C>) {
+        while let Some(top) = self.loops.last() {
+            if (top.begin + 1..=top.end).contains(&pc) {
+                break;
+            }
+            let Active {
+                begin,
+                end,
+                iterations,
+                before,
+            } = self.loops.pop().unwrap();
+            let Some(before) = before else { continue };
+
+            let kind = kind(&self.program.instructions()[begin + 1..end]);
+            let position = self.program.position(begin).unwrap_or_default();
+            let times = match iterations {
+                0 => "never ran".to_owned(),
+                1 => "ran once".to_owned(),
+                n => format!("ran {n} times"),
+            };
+            let changes = self.changes(&before, state);
+            self.say(format_args!("{kind} at {position} {times}{changes}"));
+        }
+    }
+
+    fn end_segment(&mut self, state: &State<C>) {
+        let Some(segment) = self.segment.take() else {
+            return;
+        };
+        let first = self.program.position(segment.first).unwrap_or_default();
+        let last = self.program.position(segment.last).unwrap_or_default();
+        let changes = self.changes(&segment.before, state);
+        if segment.first == segment.last {
+            self.say(format_args!("Command at {first}{changes}"));
+        } else {
+            self.say(format_args!("Commands from {first} to {last}{changes}"));
+        }
+    }
+
+    fn narrate_step(&mut self, pc: usize, pointer: usize, next: usize, state: &State<C>) {
+        let instruction = self.program.instructions()[pc];
+        let position = self.program.position(pc).unwrap_or_default();
+        let command = instruction.command();
+        let cell = state.get(pointer).value();
+        let what = match instruction {
+            Instruction::PtrIncr | Instruction::PtrDecr => {
+                format!("moved the pointer to {}", state.cell_pointer)
+            }
+            Instruction::Incr | Instruction::Decr => format!("made cell {pointer} = {cell}"),
+            Instruction::Out => {
+                let byte = self.printed.borrow().last().copied().unwrap_or_default();
+                format!("printed \"{}\"", byte.escape_ascii())
+            }
+            Instruction::In => format!("read input into cell {pointer}, which is now {cell}"),
+            Instruction::LoopBegin(end) if next == end + 1 => {
+                format!("skipped its loop since cell {pointer} is 0")
+            }
+            Instruction::LoopBegin(_) => format!("entered its loop since cell {pointer} = {cell}"),
+            Instruction::LoopEnd(begin) if next == begin + 1 => {
+                format!("jumped back since cell {pointer} = {cell}")
+            }
+            Instruction::LoopEnd(_) => format!("left its loop since cell {pointer} is 0"),
+        };
+        self.say(format_args!("`{command:?}` at {position} {what}"));
+    }
+
+    fn snapshot(&self, state: &State<C>) -> Snapshot<C::Value> {
+        let mut cells = state.cells();
+        cells.trim_end();
+        Snapshot {
+            cells: cells.collect(),
+            pointer: state.cell_pointer,
+            printed: self.printed.borrow().len(),
+        }
+    }
+
+    /// Describes how `state` differs from `before`, starting with a comma
+    fn changes(&self, before: &Snapshot<C::Value>, state: &State<C>) -> String {
+        let zero = C::default().value();
+        let now = self.snapshot(state).cells;
+        let changed: Vec<_> = (0..now.len().max(before.cells.len()))
+            .filter_map(|i| {
+                let value = now.get(i).copied().unwrap_or(zero);
+                let old = before.cells.get(i).copied().unwrap_or(zero);
+                (value != old).then(|| format!("cell {i} = {value}"))
+            })
+            .collect();
+
+        let mut left: Vec<_> = changed.iter().take(MAX_CELLS).cloned().collect();
+        if changed.len() > MAX_CELLS {
+            left.push(format!("{} more cells changed", changed.len() - MAX_CELLS));
+        }
+        if state.cell_pointer != before.pointer {
+            left.push(format!("the pointer at {}", state.cell_pointer));
+        }
+
+        let mut changes = String::new();
+        if let Some((last, rest)) = left.split_last() {
+            changes += ", leaving ";
+            if !rest.is_empty() {
+                changes += &rest.join(", ");
+                changes += " and ";
+            }
+            changes += last;
+        }
+        let printed = &self.printed.borrow()[before.printed..];
+        if !printed.is_empty() {
+            changes += &format!(", printing \"{}\"", printed.escape_ascii());
+        }
+        if changes.is_empty() {
+            changes += ", changing nothing";
+        }
+        changes
+    }
+
+    fn say(&mut self, line: std::fmt::Arguments) {
+        if self.error.is_none() {
+            self.error = writeln!(self.out, "{line}").err();
+        }
+    }
+}
+
+/// Names the kind of loop with `body`, for the loops that are common enough to have a name
+fn kind(body: &[Instruction]) -> &'static str {
+    use Instruction::*;
+
+    match body {
+        [Incr] | [Decr] => "Clearing loop",
+        [PtrIncr] | [PtrDecr] => "Scanning loop",
+        _ if body.iter().any(|i| matches!(i, LoopBegin(_))) => "Loop",
+        _ => {
+            // A loop that moves back to its cell and takes one from it each time
+            // adds multiples of it to other cells
+            let mut offset = 0isize;
+            let mut change = 0i64;
+            for instruction in body {
+                match instruction {
+                    PtrIncr => offset += 1,
+                    PtrDecr => offset -= 1,
+                    Incr if offset == 0 => change += 1,
+                    Decr if offset == 0 => change -= 1,
+                    In | Out => return "Loop",
+                    _ => (),
+                }
+            }
+            if offset == 0 && change.abs() == 1 {
+                "Multiplying loop"
+            } else {
+                "Loop"
+            }
+        }
+    }
+}
+
+/// Keeps everything the program writes where the narration can see it
+struct Printed<'a, I> {
+    inner: &'a mut I,
+    printed: &'a RefCell<Vec<u8>>,
+}
+
+impl<I: BfIo> BfIo for Printed<'_, I> {
+    fn read(&mut self) -> Result<Option<u8>> {
+        self.inner.read()
+    }
+    fn write(&mut self, byte: u8) -> Result<()> {
+        self.printed.borrow_mut().push(byte);
+        self.inner.write(byte)
+    }
+}
@@ -0,0 +1,22 @@
+use std::ffi::OsStr;
+use std::io::{stdout, Write};
+
+use brainfuck::generate;
+
+use super::Result;
+
+pub fn text(text: &OsStr) -> Result<()> {
+    let mut out = stdout().lock();
+    out.write_all(&generate::text(text.as_encoded_bytes()))?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+pub fn constant(n: u8) -> Result<()> {
+    let mut out = stdout().lock();
+    out.write_all(&generate::constant(n))?;
+    writeln!(out)?;
+
+    Ok(())
+}
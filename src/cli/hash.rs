@@ -0,0 +1,151 @@
+//! The `#` extension, stopping at every `#` in the source to show the tape,
+//! or to open a prompt on the terminal for looking at and changing it
+
+use std::fs::File;
+use std::io::{stderr, BufRead, BufReader, Read, Write};
+
+use brainfuck::{Cell, Error, InOuter, Layout, Program, Result, State};
+
+/// What to do at a `#`
+#[derive(Clone, Copy)]
+pub enum Action {
+    /// Print the tape to stderr and go on
+    Dump,
+    /// Open a prompt, like a breakpoint
+    Prompt,
+}
+
+const HELP: &[(&str, &str)] = &[
+    ("c, continue", "Go on running until the next #"),
+    ("r, run", "Go on running without stopping at # again"),
+    ("a, abort", "Stop the program"),
+    (
+        "t, tape [LAYOUT]",
+        "Show the tape as inline, rows, decimal, run-length or ascii",
+    ),
+    ("p, pointer [N]", "Show the pointer or move it to cell N"),
+    ("g, get N", "Show cell N"),
+    ("s, set N VALUE", "Set cell N to VALUE"),
+    ("h, help", "Show this help"),
+];
+
+/// Steps through `program`, which was parsed from `src`, doing `action` at every `#`
+pub fn run<W: Write, R: Read, C: Cell>(
+    program: &Program,
+    src: &[u8],
+    state: &mut State<C>,
+    io: &mut InOuter<W, R>,
+    action: Action,
+) -> Result<()> {
+    let hashes = hashes(program, src);
+    let mut terminal = None;
+    let mut stopping = true;
+    let mut pc = 0;
+    loop {
+        if stopping && hashes[pc] {
+            io.flush()?;
+            let at = match program.position(pc) {
+                Some(pos) => format!("before {pos}"),
+                None => "at the end".to_owned(),
+            };
+            match action {
+                Action::Dump => eprintln!("# {at}: {}", state),
+                Action::Prompt => {
+                    eprintln!("Stopped at # {at}, type help for commands");
+                    let terminal = match &mut terminal {
+                        Some(terminal) => terminal,
+                        None => terminal.insert(open_terminal()?),
+                    };
+                    stopping = prompt(state, terminal)?;
+                }
+            }
+        }
+        if pc == program.len() {
+            break;
+        }
+        pc = program.step(state, pc, io)?;
+    }
+    io.flush()
+}
+
+/// Which instructions come right after a `#`, with one more for the end of the program
+fn hashes(program: &Program, src: &[u8]) -> Vec<bool> {
+    let offsets: Vec<usize> = (0..program.len())
+        .map(|pc| program.position(pc).unwrap().offset)
+        .collect();
+    let mut hashes = vec![false; program.len() + 1];
+    for (i, _) in src.iter().enumerate().filter(|(_, &b)| b == b'#') {
+        hashes[offsets.partition_point(|&offset| offset < i)] = true;
+    }
+    hashes
+}
+
+/// The terminal, since stdin is the program's input
+fn open_terminal() -> Result<BufReader<File>> {
+    let path = if cfg!(windows) { "CONIN$" } else { "/dev/tty" };
+    Ok(BufReader::new(File::open(path)?))
+}
+
+/// Reads commands until the program is to go on, returning whether to stop at `#` again
+fn prompt<C: Cell>(state: &mut State<C>, terminal: &mut impl BufRead) -> Result<bool> {
+    let mut err = stderr().lock();
+    loop {
+        write!(err, "# ")?;
+        err.flush()?;
+        let mut line = String::new();
+        if terminal.read_line(&mut line)? == 0 {
+            writeln!(err)?;
+            return Ok(false);
+        }
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        match (command, &*args) {
+            ("" | "c" | "continue", []) => return Ok(true),
+            ("r" | "run", []) => return Ok(false),
+            ("a" | "abort", []) => return Err(Error::Stopped),
+            ("t" | "tape", [] | [_]) => {
+                let layout = match args.first().copied().unwrap_or("inline") {
+                    "inline" => Layout::Inline,
+                    "rows" => Layout::Rows,
+                    "decimal" => Layout::Decimal,
+                    "run-length" => Layout::RunLength,
+                    "ascii" => Layout::Ascii,
+                    _ => {
+                        writeln!(err, "Expected inline, rows, decimal, run-length or ascii")?;
+                        continue;
+                    }
+                };
+                writeln!(err, "{}", state.dump(layout))?;
+            }
+            ("p" | "pointer", []) => {
+                writeln!(err, "The pointer is at cell {}", state.cell_pointer)?
+            }
+            ("p" | "pointer", [n]) => match n.parse::<usize>() {
+                Ok(n) if state.cells_limit().limit().is_some_and(|limit| n >= limit) => {
+                    writeln!(err, "Cell {n} is past the end of the tape")?
+                }
+                Ok(n) => state.cell_pointer = n,
+                Err(_) => writeln!(err, "Expected a cell number")?,
+            },
+            ("g" | "get", [n]) => match n.parse() {
+                Ok(n) => writeln!(err, "{}", state.get(n).value())?,
+                Err(_) => writeln!(err, "Expected a cell number")?,
+            },
+            ("s" | "set", [n, value]) => match (n.parse(), value.parse()) {
+                (Ok(n), Ok(value)) => {
+                    if state.set(n, C::from_value(value)).is_err() {
+                        writeln!(err, "Cell {n} is past the end of the tape")?;
+                    }
+                }
+                _ => writeln!(err, "Expected a cell number and a value the cell can hold")?,
+            },
+            ("h" | "help", []) => {
+                for (usage, help) in HELP {
+                    writeln!(err, "  {usage:18}  {help}")?;
+                }
+            }
+            _ => writeln!(err, "Unknown command, type help for commands")?,
+        }
+    }
+}
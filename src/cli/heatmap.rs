@@ -0,0 +1,84 @@
+//! Shows how often each cell was accessed as a bar of colors in the terminal
+
+use std::io::{stderr, Write};
+
+use brainfuck::{BfIo, Cell, Instruction, Program, Result, State};
+
+/// Background colors of the 256 color palette going from blue through green and yellow to red
+const COLORS: [u8; 12] = [21, 27, 33, 39, 45, 49, 48, 118, 190, 220, 208, 196];
+/// The most blocks shown, beyond which cells are grouped together
+const MAX_BLOCKS: usize = 512;
+const ROW: usize = 64;
+
+/// How many times each cell was accessed, by `+`, `-`, `.`, `,`, `[` or `]`
+#[derive(Debug, Default)]
+pub struct Accesses {
+    counts: Vec<u64>,
+}
+
+impl Accesses {
+    /// Counts the access made by `instruction`, which is about to be executed on `state`
+    pub fn record<C: Cell>(&mut self, instruction: Instruction, state: &State<C>) {
+        if matches!(instruction, Instruction::PtrIncr | Instruction::PtrDecr) {
+            return;
+        }
+        let cell = state.cell_pointer;
+        if self.counts.len() <= cell {
+            self.counts.resize(cell + 1, 0);
+        }
+        self.counts[cell] += 1;
+    }
+}
+
+/// Runs `program` and draws the heat map of its cell accesses to stderr, even if it fails
+pub fn run<C: Cell, I: BfIo>(program: &Program, state: &mut State<C>, io: &mut I) -> Result<()> {
+    let mut accesses = Accesses::default();
+    let result = program.run_traced(state, io, |_, instruction, state| {
+        accesses.record(instruction, state);
+    });
+    draw(&mut stderr().lock(), &accesses)?;
+    result
+}
+
+fn draw(out: &mut impl Write, accesses: &Accesses) -> std::io::Result<()> {
+    let counts = &accesses.counts;
+    let Some((hottest, _)) = counts.iter().enumerate().max_by_key(|&(_, count)| count) else {
+        return writeln!(out, "no cells were accessed");
+    };
+
+    let group = counts.len().div_ceil(MAX_BLOCKS);
+    let blocks: Vec<u64> = counts.chunks(group).map(|c| c.iter().sum()).collect();
+    let max_block = blocks.iter().copied().max().unwrap_or(1);
+
+    writeln!(out)?;
+    for (row, blocks) in blocks.chunks(ROW).enumerate() {
+        write!(out, "{:>8} ", row * ROW * group)?;
+        for &count in blocks {
+            match color(count, max_block) {
+                Some(color) => write!(out, "\x1b[48;5;{color}m \x1b[0m")?,
+                None => write!(out, "\x1b[2m·\x1b[0m")?,
+            }
+        }
+        writeln!(out)?;
+    }
+
+    write!(out, "{:>8} ", "")?;
+    for color in COLORS {
+        write!(out, "\x1b[48;5;{color}m \x1b[0m")?;
+    }
+    if group > 1 {
+        write!(out, " 1 to {max_block} accesses per {group} cells")?;
+    } else {
+        write!(out, " 1 to {max_block} accesses")?;
+    }
+    writeln!(out, ", the most at cell {hottest}")
+}
+
+/// The color for `count` on a logarithmic scale up to `max`, which is `None` for no accesses
+fn color(count: u64, max: u64) -> Option<u8> {
+    if count == 0 {
+        return None;
+    }
+    let scale = ((count as f64).ln() / (max as f64).ln().max(f64::MIN_POSITIVE)).min(1.);
+    Some(COLORS[(scale * (COLORS.len() - 1) as f64).round() as usize])
+}
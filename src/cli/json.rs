@@ -0,0 +1,312 @@
+//! Just enough JSON for the editor protocols and machine-readable output
+
+use std::fmt::{self, Display, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object<const N: usize>(fields: [(&str, Json); N]) -> Self {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+    }
+    /// The field `key` of an object, or `Null`
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map_or(&Json::Null, |(_, v)| v),
+            _ => &Json::Null,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Json::Number(n) if n >= 0. && n.fract() == 0. => Some(n as u64),
+            _ => None,
+        }
+    }
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Json::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+    pub fn as_array(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+    pub fn is_null(&self) -> bool {
+        *self == Json::Null
+    }
+
+    pub fn parse(src: &str) -> Option<Self> {
+        let mut parser = Parser {
+            src: src.as_bytes(),
+            i: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.whitespace();
+        (parser.i == parser.src.len()).then_some(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+macro_rules! from_number {
+    ($($t:ty),*) => {$(
+        impl From<$t> for Json {
+            fn from(n: $t) -> Self {
+                Json::Number(n as f64)
+            }
+        }
+    )*};
+}
+
+from_number!(u8, u32, u64, usize, i32, i64, f64);
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(items: Vec<T>) -> Self {
+        Json::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            Json::Number(n) if n.is_finite() => write!(f, "{n}"),
+            Json::Number(_) => f.write_str("null"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// How deeply arrays and objects may be nested, so that parsing can't overflow the stack
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    src: &'a [u8],
+    i: usize,
+    /// How many arrays and objects the parser is inside
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.src.get(self.i) {
+            self.i += 1;
+        }
+    }
+    fn eat(&mut self, byte: u8) -> bool {
+        self.whitespace();
+        if self.src.get(self.i) == Some(&byte) {
+            self.i += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn keyword(&mut self, word: &str, value: Json) -> Option<Json> {
+        if self.src[self.i..].starts_with(word.as_bytes()) {
+            self.i += word.len();
+            Some(value)
+        } else {
+            None
+        }
+    }
+    fn value(&mut self) -> Option<Json> {
+        self.whitespace();
+        match *self.src.get(self.i)? {
+            b'n' => self.keyword("null", Json::Null),
+            b't' => self.keyword("true", Json::Bool(true)),
+            b'f' => self.keyword("false", Json::Bool(false)),
+            b'"' => self.string().map(Json::String),
+            b'[' => {
+                self.i += 1;
+                self.enter()?;
+                let mut items = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value()?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return None;
+                        }
+                    }
+                }
+                self.depth -= 1;
+                Some(Json::Array(items))
+            }
+            b'{' => {
+                self.i += 1;
+                self.enter()?;
+                let mut fields = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        self.whitespace();
+                        let key = self.string()?;
+                        if !self.eat(b':') {
+                            return None;
+                        }
+                        fields.push((key, self.value()?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return None;
+                        }
+                    }
+                }
+                self.depth -= 1;
+                Some(Json::Object(fields))
+            }
+            _ => self.number(),
+        }
+    }
+    /// Goes into an array or object, failing if they are nested too deeply
+    fn enter(&mut self) -> Option<()> {
+        self.depth += 1;
+        (self.depth <= MAX_DEPTH).then_some(())
+    }
+    fn number(&mut self) -> Option<Json> {
+        let start = self.i;
+        while let Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') = self.src.get(self.i) {
+            self.i += 1;
+        }
+        let s = std::str::from_utf8(&self.src[start..self.i]).ok()?;
+        s.parse().ok().map(Json::Number)
+    }
+    fn string(&mut self) -> Option<String> {
+        if self.src.get(self.i) != Some(&b'"') {
+            return None;
+        }
+        self.i += 1;
+        let mut s = String::new();
+        loop {
+            let start = self.i;
+            while !matches!(self.src.get(self.i), Some(b'"' | b'\\') | None) {
+                self.i += 1;
+            }
+            s.push_str(std::str::from_utf8(&self.src[start..self.i]).ok()?);
+            match *self.src.get(self.i)? {
+                b'"' => {
+                    self.i += 1;
+                    return Some(s);
+                }
+                _ => {
+                    self.i += 1;
+                    let c = match *self.src.get(self.i)? {
+                        b'n' => '\n',
+                        b't' => '\t',
+                        b'r' => '\r',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xD800..0xDC00).contains(&code) {
+                                // A surrogate pair
+                                if !self.src[self.i + 1..].starts_with(b"\\u") {
+                                    return None;
+                                }
+                                self.i += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000
+                                    + ((code - 0xD800) << 10)
+                                    + (low.checked_sub(0xDC00)?);
+                            }
+                            char::from_u32(code)?
+                        }
+                        c => c as char,
+                    };
+                    s.push(c);
+                    self.i += 1;
+                }
+            }
+        }
+    }
+    /// Reads the four hex digits after the current byte, leaving `i` on the last one
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = self.src.get(self.i + 1..self.i + 5)?;
+        let code = u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+        self.i += 4;
+        Some(code)
+    }
+}
@@ -0,0 +1,323 @@
+//! A language server speaking the Language Server Protocol over stdin and stdout
+
+use std::collections::HashMap;
+use std::io::stdin;
+
+use brainfuck::{
+    diagnostic::{Diagnostic, Severity},
+    lint::lint,
+    Position, Program,
+};
+
+use super::json::Json;
+use super::message::{read_message, send};
+use super::Result;
+
+/// Open documents by their URI
+type Documents = HashMap<String, String>;
+
+pub fn run() -> Result<()> {
+    let mut input = stdin().lock();
+    let mut documents = Documents::new();
+
+    while let Some(message) = read_message(&mut input)? {
+        let Some(message) = Json::parse(&message) else {
+            continue;
+        };
+        let method = message.get("method").as_str().unwrap_or("");
+        let params = message.get("params");
+        let id = message.get("id");
+
+        let result = match method {
+            "initialize" => Some(Json::object([
+                (
+                    "capabilities",
+                    Json::object([
+                        ("textDocumentSync", 1.into()),
+                        ("hoverProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                        ("documentFormattingProvider", true.into()),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    Json::object([
+                        ("name", "brainfuck".into()),
+                        ("version", env!("CARGO_PKG_VERSION").into()),
+                    ]),
+                ),
+            ])),
+            "shutdown" => Some(Json::Null),
+            "exit" => break,
+            "textDocument/didOpen" => {
+                let doc = params.get("textDocument");
+                if let (Some(uri), Some(text)) = (doc.get("uri").as_str(), doc.get("text").as_str())
+                {
+                    documents.insert(uri.to_owned(), text.to_owned());
+                    publish_diagnostics(uri, text)?;
+                }
+                None
+            }
+            "textDocument/didChange" => {
+                let uri = params.get("textDocument").get("uri").as_str();
+                let changes = params.get("contentChanges").as_array();
+                if let (Some(uri), Some(text)) =
+                    (uri, changes.last().and_then(|c| c.get("text").as_str()))
+                {
+                    documents.insert(uri.to_owned(), text.to_owned());
+                    publish_diagnostics(uri, text)?;
+                }
+                None
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = params.get("textDocument").get("uri").as_str() {
+                    documents.remove(uri);
+                    publish_diagnostics(uri, "")?;
+                }
+                None
+            }
+            "textDocument/hover" => Some(with_document(&documents, params, hover)),
+            "textDocument/definition" => Some(with_document(&documents, params, definition)),
+            "textDocument/formatting" => {
+                let uri = params.get("textDocument").get("uri").as_str();
+                let options = params.get("options");
+                let indent = match options.get("insertSpaces").as_bool() {
+                    Some(false) => "\t".to_owned(),
+                    _ => " ".repeat(options.get("tabSize").as_u64().unwrap_or(4) as usize),
+                };
+                Some(match uri.and_then(|uri| documents.get(uri)) {
+                    Some(text) => Json::Array(vec![Json::object([
+                        ("range", whole_range(text)),
+                        ("newText", format(text, &indent).into()),
+                    ])]),
+                    None => Json::Null,
+                })
+            }
+            _ => {
+                if id.is_null() {
+                    None
+                } else {
+                    // Unknown requests still need an answer
+                    Some(Json::Null)
+                }
+            }
+        };
+
+        if let (Some(result), false) = (result, id.is_null()) {
+            send(&Json::object([
+                ("jsonrpc", "2.0".into()),
+                ("id", id.clone()),
+                ("result", result),
+            ]))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn publish_diagnostics(uri: &str, text: &str) -> Result<()> {
+    let src = text.as_bytes();
+    let mut diagnostics = Vec::new();
+    if let Err(e) = Program::parse(src) {
+        diagnostics.push(Diagnostic::from(&e));
+    }
+    diagnostics.extend(lint(src).iter().map(Diagnostic::from));
+
+    let diagnostics = diagnostics
+        .iter()
+        .flat_map(|diagnostic| {
+            let severity = match diagnostic.severity {
+                Severity::Error => 1,
+                Severity::Warning => 2,
+            };
+            diagnostic
+                .labels
+                .iter()
+                .filter(|label| label.primary)
+                .map(move |label| {
+                    let mut message = diagnostic.message.clone();
+                    if !label.message.is_empty() {
+                        message = format!("{message}: {}", label.message);
+                    }
+                    let start = to_lsp(text, label.position);
+                    let end = Json::object([
+                        ("line", start.get("line").clone()),
+                        (
+                            "character",
+                            (start.get("character").as_u64().unwrap_or(0) + 1).into(),
+                        ),
+                    ]);
+                    Json::object([
+                        ("range", Json::object([("start", start), ("end", end)])),
+                        ("severity", severity.into()),
+                        ("source", "brainfuck".into()),
+                        ("message", message.into()),
+                    ])
+                })
+        })
+        .collect();
+
+    send(&Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/publishDiagnostics".into()),
+        (
+            "params",
+            Json::object([
+                ("uri", uri.into()),
+                ("diagnostics", Json::Array(diagnostics)),
+            ]),
+        ),
+    ]))
+}
+
+/// Calls `f` with the URI, text and byte offset of a text document position request
+fn with_document(documents: &Documents, params: &Json, f: fn(&str, &str, usize) -> Json) -> Json {
+    let uri = params.get("textDocument").get("uri").as_str();
+    let position = params.get("position");
+    match uri.and_then(|uri| Some((uri, documents.get(uri)?))) {
+        Some((uri, text)) => {
+            let line = position.get("line").as_u64().unwrap_or(0) as usize;
+            let character = position.get("character").as_u64().unwrap_or(0) as usize;
+            f(uri, text, from_lsp(text, line, character))
+        }
+        None => Json::Null,
+    }
+}
+
+fn hover(_uri: &str, text: &str, offset: usize) -> Json {
+    let depth = text.as_bytes()[..offset]
+        .iter()
+        .fold(0isize, |depth, &byte| match byte {
+            b'[' => depth + 1,
+            b']' => depth - 1,
+            _ => depth,
+        });
+    if depth <= 0 {
+        return Json::Null;
+    }
+    Json::object([(
+        "contents",
+        Json::object([
+            ("kind", "plaintext".into()),
+            ("value", format!("loop depth {depth}").into()),
+        ]),
+    )])
+}
+
+/// Jumps from a bracket to the one matching it
+fn definition(uri: &str, text: &str, offset: usize) -> Json {
+    let src = text.as_bytes();
+    let target = match src.get(offset) {
+        Some(b'[') => {
+            let mut depth = 0;
+            (offset..src.len()).find(|&i| {
+                match src[i] {
+                    b'[' => depth += 1,
+                    b']' => depth -= 1,
+                    _ => (),
+                }
+                depth == 0
+            })
+        }
+        Some(b']') => {
+            let mut depth = 0;
+            (0..=offset).rev().find(|&i| {
+                match src[i] {
+                    b']' => depth += 1,
+                    b'[' => depth -= 1,
+                    _ => (),
+                }
+                depth == 0
+            })
+        }
+        _ => None,
+    };
+    match target {
+        Some(i) => {
+            let start = to_lsp(text, position_at(src, i));
+            Json::object([
+                ("uri", uri.into()),
+                (
+                    "range",
+                    Json::object([("start", start.clone()), ("end", start)]),
+                ),
+            ])
+        }
+        None => Json::Null,
+    }
+}
+
+/// Indents every line by the depth of the loops it is in
+fn format(text: &str, indent: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut depth = 0usize;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim();
+        if content.is_empty() {
+            out.push_str(if line.ends_with('\n') { "\n" } else { "" });
+            continue;
+        }
+        let closing = content.bytes().take_while(|&b| b == b']').count();
+        for _ in 0..depth.saturating_sub(closing) {
+            out.push_str(indent);
+        }
+        out.push_str(content);
+        if line.ends_with('\n') {
+            out.push('\n');
+        }
+        for byte in content.bytes() {
+            match byte {
+                b'[' => depth += 1,
+                b']' => depth = depth.saturating_sub(1),
+                _ => (),
+            }
+        }
+    }
+    out
+}
+
+fn whole_range(text: &str) -> Json {
+    let end = to_lsp(text, position_at(text.as_bytes(), text.len()));
+    Json::object([
+        (
+            "start",
+            Json::object([("line", 0.into()), ("character", 0.into())]),
+        ),
+        ("end", end),
+    ])
+}
+
+fn position_at(src: &[u8], offset: usize) -> Position {
+    let mut pos = Position::START;
+    for &byte in &src[..offset] {
+        pos.advance(byte);
+    }
+    pos
+}
+
+/// LSP positions have 0-based lines and count characters in UTF-16 code units
+fn to_lsp(text: &str, pos: Position) -> Json {
+    let line_start = pos.offset + 1 - pos.column;
+    let before = text.get(line_start..pos.offset).unwrap_or("");
+    let character: usize = before.chars().map(char::len_utf16).sum();
+    Json::object([
+        ("line", (pos.line - 1).into()),
+        ("character", character.into()),
+    ])
+}
+
+fn from_lsp(text: &str, line: usize, character: usize) -> usize {
+    let line_start = text
+        .match_indices('\n')
+        .nth(line.wrapping_sub(1))
+        .map_or(0, |(i, _)| i + 1);
+    let line_start = if line == 0 { 0 } else { line_start };
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
@@ -0,0 +1,50 @@
+//! The framing shared by the language server and debug adapter protocols,
+//! a `Content-Length` header followed by a JSON body
+
+use std::io::{stdout, BufRead, Error as IoError, ErrorKind, Write};
+
+use super::json::Json;
+use super::Result;
+
+/// The largest message body accepted, in bytes, so that a wrong `Content-Length`
+/// can't make it allocate more than any real message needs
+const MAX_MESSAGE: usize = 64 << 20;
+
+/// Reads the body of the next message, `None` once the input has ended
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+    let length = length.unwrap_or(0);
+    if length > MAX_MESSAGE {
+        return Err(IoError::new(
+            ErrorKind::InvalidData,
+            format!("a message of {length} bytes is larger than the {MAX_MESSAGE} allowed"),
+        )
+        .into());
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+}
+
+pub fn send(message: &Json) -> Result<()> {
+    let body = message.to_string();
+    let mut out = stdout().lock();
+    write!(out, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    out.flush()?;
+    Ok(())
+}
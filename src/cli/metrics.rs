@@ -0,0 +1,14 @@
+use std::io::{stdout, Write};
+
+use brainfuck::Program;
+
+use super::{Failure, Result};
+
+pub fn run(path: &str) -> Result<()> {
+    let src = super::read_program(path)?;
+    let program = Program::parse(&src).map_err(|e| Failure::in_source(e, path, &src))?;
+
+    write!(stdout().lock(), "{}", program.metrics())?;
+
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use std::io::{stdout, Write};
+
+use brainfuck::minify::minify;
+
+use super::Result;
+
+pub fn run(path: &str, shorten: bool) -> Result<()> {
+    let src = super::read_program(path)?;
+
+    let mut out = stdout().lock();
+    out.write_all(&minify(&src, shorten))?;
+    writeln!(out)?;
+
+    Ok(())
+}
@@ -0,0 +1,363 @@
+pub mod animate;
+pub mod asm;
+pub mod batch;
+pub mod cache;
+pub mod check;
+// WASI has no threads to read requests while a program runs
+#[cfg(not(target_os = "wasi"))]
+pub mod dap;
+pub mod disasm;
+pub mod encode;
+pub mod equiv;
+pub mod examples;
+pub mod explain;
+pub mod generate;
+pub mod hash;
+pub mod heatmap;
+pub mod json;
+pub mod lsp;
+pub mod message;
+pub mod metrics;
+pub mod minify;
+pub mod obfuscate;
+pub mod persist;
+pub mod pipe;
+#[cfg(unix)]
+pub mod raw;
+#[cfg(not(target_os = "wasi"))]
+pub mod remote;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod repl;
+pub mod script;
+pub mod selftest;
+#[cfg(unix)]
+pub mod signal;
+// WASI has no threads to run programs on
+#[cfg(not(target_os = "wasi"))]
+pub mod serve;
+pub mod summary;
+pub mod test;
+pub mod throttle;
+pub mod trace;
+pub mod translate;
+
+use std::fs;
+use std::io::{Error as IoError, ErrorKind};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use json::Json;
+
+use brainfuck::{
+    diagnostic::{Diagnostic, Severity},
+    preprocess::{Expanded, PreprocessError, Preprocessor},
+    Cell, Error, Layout, Position, State, Warning, Warnings,
+};
+
+pub type Result<T> = std::result::Result<T, Failure>;
+
+pub enum Failure {
+    /// An error together with the source it happened in, if that is known
+    Error {
+        error: Error,
+        source: Option<(String, Vec<u8>)>,
+        halt: Option<Box<Halt>>,
+    },
+    /// A problem found in the source before running it
+    Diagnostic {
+        diagnostic: Box<Diagnostic>,
+        path: String,
+        src: Vec<u8>,
+    },
+    /// Any other problem, already rendered
+    Source(String),
+}
+
+/// Where the program was when it failed
+#[derive(Debug, Clone)]
+pub struct Halt {
+    /// The index of the instruction, for the engines that run parsed programs
+    pub pc: Option<usize>,
+    pub cell_pointer: usize,
+    /// The cells around the pointer, like the interactive shell shows them
+    pub tape: String,
+}
+
+impl Halt {
+    /// Where the program running on `state` is
+    pub fn of<C: Cell>(state: &State<C>) -> Self {
+        Halt {
+            pc: state.pc(),
+            cell_pointer: state.cell_pointer,
+            tape: state.dump(Layout::Inline).around(TAPE_EXCERPT).to_string(),
+        }
+    }
+}
+
+/// How many cells on each side of the pointer are shown where only part of the tape is,
+/// like where a program failed or in animations
+pub const TAPE_EXCERPT: usize = 8;
+
+/// Whether diagnostics are colored, see `set_color`
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Colors the diagnostics written from now on with ANSI escape codes, or stops coloring them
+pub fn set_color(color: bool) {
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+/// Renders `diagnostic`, colored if `set_color` says so
+pub fn render(diagnostic: &Diagnostic, path: &str, src: &[u8]) -> String {
+    if COLOR.load(Ordering::Relaxed) {
+        diagnostic.render_colored(path, src)
+    } else {
+        diagnostic.render(path, src)
+    }
+}
+
+/// How failures are written to stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Human,
+    /// A JSON object on a single line
+    Json,
+}
+
+impl Failure {
+    pub fn in_source(error: Error, path: &str, src: &[u8]) -> Self {
+        Failure::Error {
+            error,
+            source: Some((path.to_owned(), src.to_owned())),
+            halt: None,
+        }
+    }
+    pub fn preprocess(error: &PreprocessError, path: &str, src: &[u8]) -> Self {
+        let diagnostic = Diagnostic::from(error);
+        match &error.file {
+            Some(file) => match fs::read(file) {
+                Ok(src) => Failure::Diagnostic {
+                    diagnostic: Box::new(diagnostic),
+                    path: file.clone(),
+                    src,
+                },
+                Err(_) => Failure::Source(format!("error: {error}\n")),
+            },
+            None => Failure::Diagnostic {
+                diagnostic: Box::new(diagnostic),
+                path: path.to_owned(),
+                src: src.to_owned(),
+            },
+        }
+    }
+    /// Notes where the program was when it failed
+    #[must_use]
+    pub fn halted_at(mut self, at: Halt) -> Self {
+        if let Failure::Error { halt, .. } = &mut self {
+            *halt = Some(Box::new(at));
+        }
+        self
+    }
+    pub fn report_as(&self, format: ErrorFormat) {
+        match format {
+            ErrorFormat::Human => self.report(),
+            ErrorFormat::Json => eprintln!("{}", self.to_json()),
+        }
+    }
+    pub fn report(&self) {
+        let (error, source, halt) = match self {
+            Failure::Error {
+                error,
+                source,
+                halt,
+            } => (error, source, halt),
+            Failure::Diagnostic {
+                diagnostic,
+                path,
+                src,
+            } => return eprint!("{}", render(diagnostic, path, src)),
+            Failure::Source(rendered) => return eprint!("{rendered}"),
+        };
+        match (error, source) {
+            (e, Some((path, src))) if e.position().is_some() => {
+                let mut diagnostic = Diagnostic::from(e);
+                if let Some(halt) = halt {
+                    diagnostic = diagnostic
+                        .with_note(format!("the pointer was at cell {}", halt.cell_pointer))
+                        .with_note(format!("the tape around it was {}", halt.tape));
+                }
+                eprint!("{}", render(&diagnostic, path, src));
+            }
+            // Input that couldn't be decoded, like with --base64
+            (Error::IoError(e), _) if e.kind() == ErrorKind::InvalidData => {
+                eprintln!("error: {e}")
+            }
+            (Error::IoError(e), _) => eprintln!("Unexpected error:\n{e:?}"),
+            (Error::Stopped, _) => eprintln!("Stopped"),
+            (e, _) => eprintln!("Error, {e}"),
+        }
+    }
+}
+
+impl Failure {
+    /// The failure as an object with its `kind`, `message`, `file`, `span` and other `labels`,
+    /// `hint`, and the `pc` and `pointer` the program failed at
+    pub fn to_json(&self) -> Json {
+        let (kind, diagnostic, path, halt) = match self {
+            Failure::Error {
+                error,
+                source,
+                halt,
+            } => (
+                kind(error),
+                Diagnostic::from(error),
+                source.as_ref().map(|(path, _)| path.as_str()),
+                halt.as_deref(),
+            ),
+            Failure::Diagnostic {
+                diagnostic, path, ..
+            } => (
+                "preprocess",
+                (**diagnostic).clone(),
+                Some(path.as_str()),
+                None,
+            ),
+            Failure::Source(rendered) => {
+                let message = rendered.trim_end();
+                let message = message.strip_prefix("error: ").unwrap_or(message);
+                return Json::object([("kind", "other".into()), ("message", message.into())]);
+            }
+        };
+        let span = |position: Position| {
+            Json::object([
+                ("line", position.line.into()),
+                ("column", position.column.into()),
+                ("offset", position.offset.into()),
+            ])
+        };
+        let primary = diagnostic.labels.iter().find(|label| label.primary);
+        let labels: Vec<Json> = diagnostic
+            .labels
+            .iter()
+            .filter(|label| !label.primary)
+            .map(|label| {
+                Json::object([
+                    ("span", span(label.position)),
+                    ("message", label.message.as_str().into()),
+                ])
+            })
+            .collect();
+        Json::object([
+            ("kind", kind.into()),
+            ("message", diagnostic.message.into()),
+            ("file", path.into()),
+            ("span", primary.map(|label| span(label.position)).into()),
+            ("labels", labels.into()),
+            ("hint", diagnostic.hint.into()),
+            ("pc", halt.and_then(|halt| halt.pc).into()),
+            ("pointer", halt.map(|halt| halt.cell_pointer).into()),
+        ])
+    }
+}
+
+/// A name for the kind of `error` that tools can match on
+fn kind(error: &Error) -> &'static str {
+    match error {
+        Error::Stopped => "stopped",
+        Error::OutOfBounds => "out_of_bounds",
+        Error::NoLoopStarted(_) => "no_loop_started",
+        Error::UnendedLoop(_) => "unended_loop",
+        Error::CellPointerOverflow(_) => "cell_pointer_overflow",
+        Error::EndOfInput(_) => "end_of_input",
+        Error::InputLimitReached(_) => "input_limit_reached",
+        Error::LoopLimitReached(_) => "loop_limit_reached",
+        Error::UninitializedRead { .. } => "uninitialized_read",
+        Error::Io(_) | Error::IoError(_) => "io",
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(error: Error) -> Self {
+        Failure::Error {
+            error,
+            source: None,
+            halt: None,
+        }
+    }
+}
+
+impl From<IoError> for Failure {
+    fn from(e: IoError) -> Self {
+        Error::from(e).into()
+    }
+}
+
+/// Reads the program at `path`, ignoring a `#!` line at its start
+pub fn read_program(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+    let mut src = fs::read(path)?;
+    brainfuck::blank_shebang(&mut src);
+    Ok(src)
+}
+
+/// Reads the source at `path` and expands it with `preprocessor`,
+/// returning the original source and the expansion
+pub fn read_source(path: &str, preprocessor: &Preprocessor) -> Result<(Vec<u8>, Expanded)> {
+    expand_source(read_program(path)?, path, preprocessor)
+}
+
+/// Expands `src`, which is called `path`, with `preprocessor`,
+/// returning it and the expansion
+pub fn expand_source(
+    src: Vec<u8>,
+    path: &str,
+    preprocessor: &Preprocessor,
+) -> Result<(Vec<u8>, Expanded)> {
+    let expanded = preprocessor
+        .expand_at(&src, path)
+        .map_err(|e| Failure::preprocess(&e, path, &src))?;
+    Ok((src, expanded))
+}
+
+/// Maps `error` in the expansion of the source at `path` back to
+/// the source or included file it came from, returning it with that file
+pub fn locate<'a>(
+    error: Error,
+    path: &'a str,
+    src: &'a [u8],
+    expanded: &'a Expanded,
+) -> (Error, &'a str, &'a [u8]) {
+    let (path, src) = error
+        .position()
+        .and_then(|pos| expanded.file(pos))
+        .unwrap_or((path, src));
+    (error.map_positions(|pos| expanded.origin(pos)), path, src)
+}
+
+/// The path and contents of a source, if known
+pub type Source<'a> = Option<(&'a str, &'a [u8])>;
+
+/// Prints `warnings`, each pointing into the source `locate` finds for it, if any
+pub fn print_warnings<'a>(
+    warnings: Warnings,
+    mut locate: impl FnMut(Error) -> (Error, Source<'a>),
+) {
+    for Warning { error, count } in warnings.kept {
+        let (warning, source) = locate(error);
+        let times = match count {
+            1 => String::new(),
+            n => format!(" ({n} times)"),
+        };
+        match source {
+            Some((path, src)) if warning.position().is_some() => {
+                let mut diagnostic = Diagnostic::from(&warning);
+                diagnostic.severity = Severity::Warning;
+                diagnostic.message += &times;
+                eprint!("{}", render(&diagnostic, path, src));
+            }
+            _ => eprintln!("Warning, {warning}{times}"),
+        }
+    }
+    if warnings.omitted > 0 {
+        eprintln!("Warning, {} more warnings weren't kept", warnings.omitted);
+    }
+}
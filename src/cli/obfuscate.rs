@@ -0,0 +1,30 @@
+use std::io::{stdout, Write};
+use std::process::ExitCode;
+
+use brainfuck::{
+    equivalence::{compare, random_inputs},
+    obfuscate::obfuscate,
+    Eof, Program, State,
+};
+
+use super::{Failure, Result};
+
+/// Prints the obfuscated program if it behaves the same as the original on `random` inputs
+pub fn run(path: &str, seed: u64, random: usize, fuel: u64) -> Result<ExitCode> {
+    let src = super::read_program(path)?;
+    let original = Program::parse(&src).map_err(|e| Failure::in_source(e, path, &src))?;
+
+    let obfuscated = obfuscate(&src, seed);
+    let program = Program::parse(&obfuscated)?;
+
+    let mut template = State::default();
+    template.set_eof(Eof::Zero);
+    let inputs = random_inputs(seed, random, 64);
+    if let Some(divergence) = compare(&original, &program, &template, inputs, fuel) {
+        eprintln!("Obfuscated program differs {divergence}");
+        return Ok(ExitCode::FAILURE);
+    }
+
+    stdout().lock().write_all(&obfuscated)?;
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,104 @@
+//! The tape, pointer and settings of the shell, saved on exit with `--persist`
+//! and restored the next time it's started
+//!
+//! ```text
+//! pointer 2
+//! timing on
+//! cells 0 72 101
+//! ```
+//!
+//! Cells are saved in decimal up to the last one that isn't zero.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use brainfuck::{Cell, State};
+
+use super::{Failure, Result};
+
+/// What is kept between sessions
+pub struct Saved<C: Cell> {
+    pub cells: Vec<C>,
+    pub pointer: usize,
+    pub timing: bool,
+}
+
+/// Reads what was saved to `path`, `None` if nothing has been saved there yet
+pub fn load<C: Cell>(path: &Path) -> Result<Option<Saved<C>>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let invalid = |number: usize, problem: &str| {
+        Failure::Source(format!("error: {}:{number}: {problem}\n", path.display()))
+    };
+
+    let mut saved = Saved {
+        cells: Vec::new(),
+        pointer: 0,
+        timing: false,
+    };
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let (key, value) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        match key {
+            "" => (),
+            "pointer" => {
+                saved.pointer = value
+                    .parse()
+                    .map_err(|_| invalid(number, "the pointer has to be a cell number"))?;
+            }
+            "timing" => {
+                saved.timing = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(invalid(number, "timing has to be on or off")),
+                };
+            }
+            "cells" => {
+                saved.cells = value
+                    .split_whitespace()
+                    .map(|value| value.parse().map(C::from_value))
+                    .collect::<std::result::Result<_, _>>()
+                    .map_err(|_| invalid(number, "cells have to be numbers the cells can hold"))?;
+            }
+            _ => return Err(invalid(number, &format!("unknown setting `{key}`"))),
+        }
+    }
+    Ok(Some(saved))
+}
+
+/// Puts the saved tape and pointer on `state`, failing if they don't fit within its cell limit
+pub fn restore<C: Cell>(saved: Saved<C>, state: &mut State<C>, path: &Path) -> Result<()> {
+    let last = saved.cells.len().max(saved.pointer + 1);
+    if let Some(limit) = state.cells_limit().limit().filter(|&limit| last > limit) {
+        return Err(Failure::Source(format!(
+            "error: the tape saved in {} needs {last} cells but only {limit} are allowed\n",
+            path.display()
+        )));
+    }
+    for (i, cell) in saved.cells.into_iter().enumerate() {
+        state.set(i, cell)?;
+    }
+    state.cell_pointer = saved.pointer;
+    Ok(())
+}
+
+pub fn save<C: Cell>(path: &Path, state: &State<C>, timing: bool) -> Result<()> {
+    let mut cells = state.cells();
+    cells.trim_end();
+    let cells: Vec<String> = cells.map(|value| value.to_string()).collect();
+    let text = format!(
+        "pointer {}\ntiming {}\ncells {}\n",
+        state.cell_pointer,
+        if timing { "on" } else { "off" },
+        cells.join(" ")
+    );
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
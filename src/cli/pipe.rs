@@ -0,0 +1,73 @@
+//! Connecting a program to a shell command with `--input-cmd`, so that what the command
+//! prints is the program's input, and what the program prints can be the command's input
+
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+
+/// A started command, with the program's input from it and maybe the program's output to it
+pub type Connection = (Pipe, Box<dyn Read>, Option<Box<dyn Write>>);
+
+/// A running command connected to a program
+pub struct Pipe {
+    command: String,
+    child: Child,
+}
+
+impl Pipe {
+    /// Starts `command` in the shell, returning it with the program's input from it,
+    /// and the program's output to it if `output` is set
+    pub fn spawn(command: &str, output: bool) -> io::Result<Connection> {
+        let mut shell = if cfg!(windows) {
+            let mut shell = Command::new("cmd");
+            shell.arg("/C");
+            shell
+        } else {
+            let mut shell = Command::new("sh");
+            shell.arg("-c");
+            shell
+        };
+        let mut child = shell
+            .arg(command)
+            .stdin(if output {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            })
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let input: Box<dyn Read> = Box::new(child.stdout.take().unwrap());
+        let output = child
+            .stdin
+            .take()
+            .map(|stdin| Box::new(stdin) as Box<dyn Write>);
+        let pipe = Pipe {
+            command: command.to_owned(),
+            child,
+        };
+        Ok((pipe, input, output))
+    }
+    /// Waits for the command to end once the program has, warning if it failed.
+    /// The program's ends of the pipes have to be dropped first, so the command isn't waiting on them.
+    pub fn wait(mut self) -> io::Result<()> {
+        let status = self.child.wait()?;
+        if !status.success() && !closed_pipe(status) {
+            eprintln!("warning: `{}` exited with {status}", self.command);
+        }
+        Ok(())
+    }
+}
+
+/// Whether the command was ended by writing to the program after the program had ended,
+/// which is how commands that print forever like `yes` are ended
+#[cfg(unix)]
+fn closed_pipe(status: ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    // Shells exit with 128 plus the signal their command was ended by
+    status.signal() == Some(libc::SIGPIPE) || status.code() == Some(128 + libc::SIGPIPE)
+}
+
+#[cfg(not(unix))]
+fn closed_pipe(_: ExitStatus) -> bool {
+    false
+}
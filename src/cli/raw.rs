@@ -0,0 +1,69 @@
+//! Reading input a key at a time with `--raw-input`, without waiting for Enter
+//! and without echoing the keys, so that interactive games can be played
+
+use std::io::{self, stdin, IsTerminal};
+use std::sync::OnceLock;
+
+/// How the terminal was set before input was made raw, to be put back when the program ends
+static ORIGINAL: OnceLock<libc::termios> = OnceLock::new();
+
+/// The terminal on stdin in raw mode, which is restored when this is dropped
+pub struct RawInput(());
+
+impl RawInput {
+    /// Makes stdin raw if it's a terminal, doing nothing if input is coming from elsewhere
+    pub fn enable() -> io::Result<Option<Self>> {
+        if !stdin().is_terminal() {
+            return Ok(None);
+        }
+        // Safety: `termios` is plain data that `tcgetattr` fills in
+        let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+        // Safety: `termios` is valid to write to
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let original = *ORIGINAL.get_or_init(|| termios);
+
+        // Bytes are read as soon as a key is pressed, and Ctrl-C still interrupts
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        set(&termios)?;
+        let handler = interrupted as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // Safety: the handler only restores the terminal and raises the signal again
+        if unsafe { libc::signal(libc::SIGINT, handler) } == libc::SIG_ERR {
+            let e = io::Error::last_os_error();
+            set(&original)?;
+            return Err(e);
+        }
+        Ok(Some(RawInput(())))
+    }
+}
+
+impl Drop for RawInput {
+    fn drop(&mut self) {
+        if let Some(original) = ORIGINAL.get() {
+            let _ = set(original);
+        }
+    }
+}
+
+fn set(termios: &libc::termios) -> io::Result<()> {
+    // Safety: `termios` is a valid terminal setting from `tcgetattr`
+    if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Leaves the terminal as it was before being interrupted, instead of without echo
+extern "C" fn interrupted(signal: libc::c_int) {
+    if let Some(original) = ORIGINAL.get() {
+        // Safety: `tcsetattr` and `signal` are async-signal-safe
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original);
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+}
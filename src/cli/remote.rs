@@ -0,0 +1,112 @@
+//! Lets the interactive shell attach to a program running elsewhere over a local socket
+//!
+//! Each line sent is a command, answered with a single line:
+//! `tape` shows the tape, `status` the progress, and `pause`, `resume` and `stop` control the run.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use brainfuck::ControlHandle;
+
+/// How long to wait for the running program to show its tape
+const VIEW_TIMEOUT: Duration = Duration::from_millis(500);
+
+const HELP: &str = "commands: tape, status, pause, resume, stop, help";
+
+/// Starts answering commands for the program controlled by `handle` on `127.0.0.1:port`
+/// in the background
+pub fn listen(port: u16, handle: ControlHandle) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("Listening for the shell on {}", listener.local_addr()?);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let handle = handle.clone();
+            thread::spawn(move || {
+                // A shell that goes away only ends its own connection
+                let _ = answer(stream, &handle);
+            });
+        }
+    });
+    Ok(())
+}
+
+fn answer(stream: TcpStream, handle: &ControlHandle) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let reply = match line?.trim() {
+            "tape" => handle.tape_view(VIEW_TIMEOUT).unwrap_or_else(|| {
+                "the program didn't show its tape, it may be waiting for input".to_owned()
+            }),
+            "status" => status(handle),
+            "pause" => {
+                handle.pause();
+                status(handle)
+            }
+            "resume" => {
+                handle.resume();
+                status(handle)
+            }
+            "stop" => {
+                handle.stop();
+                status(handle)
+            }
+            "help" | "" => HELP.to_owned(),
+            command => format!("unknown command `{command}`, {HELP}"),
+        };
+        writeln!(writer, "{reply}")?;
+    }
+    Ok(())
+}
+
+fn status(handle: &ControlHandle) -> String {
+    let state = match (handle.is_running(), handle.is_paused()) {
+        (false, _) => "stopped",
+        (true, true) => "paused",
+        (true, false) => "running",
+    };
+    format!(
+        "{state} after {} instructions, pointer at {}",
+        handle.instructions(),
+        handle.cell_pointer()
+    )
+}
+
+/// A connection from the shell to a program started with `--listen`
+pub struct Remote {
+    address: String,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Remote {
+    /// Connects to `address`, where just a port means one on this machine
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let address = match address.parse::<u16>() {
+            Ok(port) => format!("127.0.0.1:{port}"),
+            Err(_) => address.to_owned(),
+        };
+        let stream = TcpStream::connect(address.as_str())?;
+        Ok(Remote {
+            address,
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+    /// Sends a command and waits for its reply, which is `None` once the program is gone
+    pub fn send(&mut self, command: &str) -> io::Result<Option<String>> {
+        writeln!(self.writer, "{command}")?;
+        let mut reply = String::new();
+        if self.reader.read_line(&mut reply)? == 0 {
+            return Ok(None);
+        }
+        reply.truncate(reply.trim_end().len());
+        Ok(Some(reply))
+    }
+}
@@ -0,0 +1,40 @@
+use std::fs;
+use std::path::Path;
+
+use brainfuck::{render::TapeHistory, BfIo, Cell, Program, Result, State};
+
+/// Runs `program`, recording the tape every `every` instructions, and writes the history
+/// to `image` even if the program fails
+pub fn run<C: Cell, I: BfIo>(
+    program: &Program,
+    state: &mut State<C>,
+    io: &mut I,
+    every: u64,
+    image: &Path,
+) -> Result<()> {
+    let mut history = TapeHistory::new();
+    history.record(state);
+
+    let mut result = Ok(());
+    let mut steps = 0u64;
+    let mut pc = 0;
+    while pc < program.len() {
+        match program.step(state, pc, io) {
+            Ok(next) => pc = next,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+        steps += 1;
+        if steps.is_multiple_of(every) {
+            history.record(state);
+        }
+    }
+    if !steps.is_multiple_of(every) {
+        history.record(state);
+    }
+
+    fs::write(image, history.to_png())?;
+    result
+}
@@ -0,0 +1,714 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{stdin, stdout, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
+
+use brainfuck::{BfIo, Cell, EndReason, Feeder, InOuter, Layout, Snapshot, State};
+
+use super::persist;
+#[cfg(not(target_os = "wasi"))]
+use super::remote::Remote;
+use super::script::{self, Recorder};
+use super::{Failure, Halt, Result};
+
+struct Session<'a, C: Cell> {
+    state: &'a mut State<C>,
+    history: History,
+    /// The tape before each of the last `UNDO_LIMIT` lines, newest last
+    undo: VecDeque<Snapshot<C>>,
+    timing: bool,
+    /// How the tape is shown after each line
+    display: Layout,
+    prompt: Prompt,
+    /// Runs the lines entered, which continue the same source while loops are unended
+    feeder: Feeder,
+    /// The lines entered since the oldest unended loop, or just the last line
+    source: String,
+    /// Where the lines entered are being recorded to, if anywhere
+    recorder: Option<Recorder>,
+    /// The program that lines are sent to instead of being run, if attached to one
+    #[cfg(not(target_os = "wasi"))]
+    remote: Option<Remote>,
+}
+
+/// How many lines can be undone
+const UNDO_LIMIT: usize = 100;
+/// How many of the latest lines entered are kept in the history
+const HISTORY_LIMIT: usize = 1000;
+
+/// What the shell should do after a meta-command has run
+enum Flow {
+    ShowTape,
+    Prompt,
+    Exit,
+}
+
+struct MetaCommand<C: Cell> {
+    name: &'static str,
+    help: &'static str,
+    action: fn(&mut Session<C>, &str) -> Flow,
+}
+
+impl<C: Cell> Session<'_, C> {
+    const META_COMMANDS: &'static [MetaCommand<C>] = &[
+        MetaCommand {
+            name: "$exit",
+            help: "Exit the shell",
+            action: |_, _| Flow::Exit,
+        },
+        MetaCommand {
+            name: "$undo",
+            help: "Restore the tape to before the last executed line",
+            action: |session, _| match session.undo.pop_back() {
+                Some(snapshot) => {
+                    session.state.restore(snapshot);
+                    Flow::ShowTape
+                }
+                None => {
+                    println!("Nothing to undo");
+                    Flow::Prompt
+                }
+            },
+        },
+        MetaCommand {
+            name: "$reset",
+            help: "Clear the tape and any unfinished loops",
+            action: |session, _| {
+                session.save_undo();
+                session.state.reset();
+                Flow::ShowTape
+            },
+        },
+        MetaCommand {
+            name: "$history",
+            help: "List previously entered lines",
+            action: |session, _| {
+                for (i, line) in session.history.lines.iter().enumerate() {
+                    println!("{:5}  {line}", i + 1);
+                }
+                Flow::Prompt
+            },
+        },
+        MetaCommand {
+            name: "$dump",
+            help: "Show the tape as inline, rows, decimal, run-length or ascii, rows by default",
+            action: |session, args| {
+                let layout = match args {
+                    "inline" => Layout::Inline,
+                    "rows" | "" => Layout::Rows,
+                    "decimal" => Layout::Decimal,
+                    "run-length" => Layout::RunLength,
+                    "ascii" => Layout::Ascii,
+                    _ => {
+                        println!("Expected inline, rows, decimal, run-length or ascii");
+                        return Flow::Prompt;
+                    }
+                };
+                println!("{}", session.state.dump(layout));
+                Flow::Prompt
+            },
+        },
+        MetaCommand {
+            name: "$pending",
+            help: "Show the commands buffered in unfinished loops",
+            action: |session, _| {
+                let state = &*session.state;
+                if state.loop_nesting == 0 {
+                    println!("No unfinished loops");
+                } else {
+                    print!("[");
+                    for (cmd, _) in &state.ongoing_loops {
+                        print!("{cmd:?}");
+                    }
+                    println!();
+                    println!("Nesting depth {}", state.loop_nesting);
+                    for pos in state.loop_starts() {
+                        println!("  opened at {pos}");
+                    }
+                }
+                Flow::Prompt
+            },
+        },
+        MetaCommand {
+            name: "$timing",
+            help: "Show execution time and instruction count after each line (on/off)",
+            action: |session, args| {
+                match args {
+                    "on" => session.timing = true,
+                    "off" => session.timing = false,
+                    "" => session.timing = !session.timing,
+                    _ => println!("Expected on or off"),
+                }
+                let timing = if session.timing { "on" } else { "off" };
+                println!("Timing is {timing}");
+                Flow::Prompt
+            },
+        },
+        MetaCommand {
+            name: "$hex",
+            help: "Show the tape in hex after each line",
+            action: |session, _| {
+                session.display = Layout::Inline;
+                Flow::ShowTape
+            },
+        },
+        MetaCommand {
+            name: "$dec",
+            help: "Show the tape in decimal after each line",
+            action: |session, _| {
+                session.display = Layout::Decimal;
+                Flow::ShowTape
+            },
+        },
+        MetaCommand {
+            name: "$ascii",
+            help: "Show the tape as the characters it would print after each line",
+            action: |session, _| {
+                session.display = Layout::Ascii;
+                Flow::ShowTape
+            },
+        },
+        MetaCommand {
+            name: "$prompt",
+            help: "Set the prompt, with {pointer} and {cell} for the pointer and its cell",
+            action: |session, args| {
+                if args.is_empty() {
+                    println!("The prompt is \"{}\"", session.prompt.template);
+                    return Flow::Prompt;
+                }
+                // Quotes keep the spaces at the end, which are trimmed from the line otherwise
+                let template = args
+                    .strip_prefix('"')
+                    .and_then(|args| args.strip_suffix('"'))
+                    .unwrap_or(args);
+                match template.parse() {
+                    Ok(prompt) => session.prompt = prompt,
+                    Err(e) => println!("{e}"),
+                }
+                Flow::Prompt
+            },
+        },
+        #[cfg(not(target_os = "wasi"))]
+        MetaCommand {
+            name: "$attach",
+            help: "Send lines to a program started with --listen at this port or address",
+            action: |session, args| {
+                if args.is_empty() {
+                    println!("Expected the port or address to attach to");
+                    return Flow::Prompt;
+                }
+                match Remote::connect(args) {
+                    Ok(remote) => {
+                        println!(
+                            "Attached to {}, type help for its commands",
+                            remote.address()
+                        );
+                        session.remote = Some(remote);
+                    }
+                    Err(e) => println!("Couldn't attach to {args}: {e}"),
+                }
+                Flow::Prompt
+            },
+        },
+        #[cfg(not(target_os = "wasi"))]
+        MetaCommand {
+            name: "$detach",
+            help: "Go back to running lines on the local tape",
+            action: |session, _| {
+                match session.remote.take() {
+                    Some(remote) => println!("Detached from {}", remote.address()),
+                    None => println!("Not attached"),
+                }
+                Flow::Prompt
+            },
+        },
+        MetaCommand {
+            name: "$record",
+            help: "Record the following lines and what they do to a script file, or stop recording",
+            action: |session, args| {
+                if let Some(recorder) = session.recorder.take() {
+                    println!("Stopped recording to {}", recorder.path());
+                }
+                if !args.is_empty() {
+                    match Recorder::create(args) {
+                        Ok(recorder) => {
+                            println!("Recording to {args}, replay it with --script {args}");
+                            session.recorder = Some(recorder);
+                        }
+                        Err(e) => println!("Couldn't record to {args}: {e}"),
+                    }
+                }
+                Flow::Prompt
+            },
+        },
+        MetaCommand {
+            name: "$help",
+            help: "Show this help",
+            action: |session, _| {
+                print_help(session);
+                Flow::Prompt
+            },
+        },
+    ];
+}
+
+const KEY_BINDINGS: &[(&str, &str)] = &[
+    ("Ctrl-D", "Exit the shell"),
+    ("Ctrl-C", "Abort the shell and the running program"),
+];
+
+/// Runs the shell on `state` with `prompt`,
+/// restoring it from and saving it to `persist` if given
+pub fn run<W: Write, R: Read, C: Cell>(
+    state: &mut State<C>,
+    io: &mut InOuter<W, R>,
+    prompt: Prompt,
+    persist: Option<&Path>,
+) -> Result<()> {
+    println!("Brainfuck Interactive Shell");
+    println!("Type $help for help or $exit to exit");
+
+    let saved = persist.map(persist::load).transpose()?.flatten();
+    let mut session = Session::new(state, History::load());
+    session.prompt = prompt;
+    if let (Some(saved), Some(path)) = (saved, persist) {
+        session.timing = saved.timing;
+        persist::restore(saved, session.state, path)?;
+        println!("Restored from {}", path.display());
+        println!("{}", session.state.dump(session.display));
+    }
+    loop {
+        print!("{}", session.prompt());
+        stdout().flush().unwrap();
+
+        let mut s = String::new();
+        if stdin().read_line(&mut s).unwrap() == 0 {
+            println!();
+            break;
+        }
+        let line = s.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        session.history.push(line);
+
+        #[cfg(not(target_os = "wasi"))]
+        if !line.starts_with('$') {
+            if let Some(remote) = &mut session.remote {
+                match remote.send(line) {
+                    Ok(Some(reply)) => println!("{reply}"),
+                    Ok(None) => {
+                        println!("{} has gone away, detaching", remote.address());
+                        session.remote = None;
+                    }
+                    Err(e) => {
+                        println!("Lost {}: {e}, detaching", remote.address());
+                        session.remote = None;
+                    }
+                }
+                continue;
+            }
+        }
+
+        let (flow, printed) = session.enter(line, io)?;
+        let tape = matches!(flow, Flow::ShowTape).then(|| session.state.to_string());
+        if let Some(recorder) = &mut session.recorder {
+            if !line.starts_with("$record") {
+                if let Err(e) = recorder.record(line, &printed, tape.as_deref()) {
+                    println!("Couldn't record to {}: {e}, stopping", recorder.path());
+                    session.recorder = None;
+                }
+            }
+        }
+
+        match flow {
+            Flow::ShowTape => println!("{}", session.state.dump(session.display)),
+            Flow::Prompt => (),
+            Flow::Exit => {
+                println!();
+                break;
+            }
+        }
+    }
+
+    if let Some(path) = persist {
+        persist::save(path, session.state, session.timing)?;
+    }
+    Ok(())
+}
+
+/// Where `--persist` saves to if it isn't given a file
+pub fn default_persist_path() -> Option<PathBuf> {
+    Some(data_dir()?.join("brainfuck").join("session"))
+}
+
+/// Enters the lines of the script at `path` into the shell, reporting those that don't
+/// print or show what the script says to stderr
+pub fn replay<W: Write, R: Read, C: Cell>(
+    state: &mut State<C>,
+    io: &mut InOuter<W, R>,
+    path: &str,
+) -> Result<()> {
+    let text = fs::read_to_string(path)?;
+    let entries = script::parse(&text, path)?;
+
+    let mut session = Session::new(
+        state,
+        History {
+            lines: Vec::new(),
+            file: None,
+        },
+    );
+    let mut mismatches = 0;
+    for entry in &entries {
+        println!("$> {}", entry.input);
+        session.history.lines.push(entry.input.clone());
+        let (flow, printed) = session.enter(&entry.input, io)?;
+
+        let printed = printed.escape_ascii().to_string();
+        if printed != entry.printed {
+            mismatches += 1;
+            eprintln!(
+                "{path}:{}: expected output \"{}\" but got \"{printed}\"",
+                entry.number, entry.printed
+            );
+        }
+        let tape = session.state.to_string();
+        if let Flow::ShowTape = flow {
+            println!("{}", session.state.dump(session.display));
+        }
+        match &entry.tape {
+            Some(expected) if *expected != tape => {
+                mismatches += 1;
+                eprintln!(
+                    "{path}:{}: expected the tape {expected} but got {tape}",
+                    entry.number
+                );
+            }
+            _ => (),
+        }
+        if let Flow::Exit = flow {
+            break;
+        }
+    }
+
+    if mismatches > 0 {
+        let s = if mismatches == 1 { "" } else { "es" };
+        return Err(Failure::Source(format!(
+            "error: {mismatches} mismatch{s} replaying {path}\n"
+        )));
+    }
+    Ok(())
+}
+
+impl<'a, C: Cell> Session<'a, C> {
+    fn new(state: &'a mut State<C>, history: History) -> Self {
+        Session {
+            state,
+            history,
+            undo: VecDeque::new(),
+            timing: false,
+            display: Layout::Inline,
+            prompt: Prompt::default(),
+            feeder: Feeder::new(),
+            source: String::new(),
+            recorder: None,
+            #[cfg(not(target_os = "wasi"))]
+            remote: None,
+        }
+    }
+
+    /// Remembers the tape so the next change to it can be undone
+    fn save_undo(&mut self) {
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(self.state.snapshot());
+    }
+    /// Runs a meta-command or a line of code, returning what to do next and what it printed
+    fn enter<W: Write, R: Read>(
+        &mut self,
+        line: &str,
+        io: &mut InOuter<W, R>,
+    ) -> Result<(Flow, Vec<u8>)> {
+        if line.starts_with('$') {
+            let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+            let flow = match Self::META_COMMANDS.iter().find(|cmd| cmd.name == name) {
+                Some(cmd) => (cmd.action)(self, args.trim()),
+                None => {
+                    println!("Unknown command {name}, type $help for a list of commands");
+                    Flow::Prompt
+                }
+            };
+            return Ok((flow, Vec::new()));
+        }
+
+        // Lines entered while loops are unended continue the same source
+        if self.state.loop_nesting == 0 {
+            self.feeder = Feeder::new();
+            self.source.clear();
+        }
+        let src = format!("{line}\n");
+        self.source.push_str(&src);
+
+        self.save_undo();
+        let mut io = Printed {
+            inner: io,
+            printed: Vec::new(),
+        };
+        let start = Instant::now();
+        let result = self.feeder.feed(src.as_bytes(), self.state, &mut io);
+        let elapsed = start.elapsed();
+        io.inner.flush()?;
+        let source = Some(("<stdin>", self.source.as_bytes()));
+        super::print_warnings(self.state.take_warnings(), |warning| (warning, source));
+        let report = match result {
+            Ok(report) => report,
+            // A mistake in a line doesn't end the session, it can be undone instead
+            Err(e) => {
+                Failure::in_source(e, "<stdin>", self.source.as_bytes())
+                    .halted_at(Halt::of(self.state))
+                    .report();
+                self.state.abandon_loops();
+                self.feeder = Feeder::new();
+                return Ok((Flow::Prompt, io.printed));
+            }
+        };
+        if self.timing {
+            println!("{elapsed:.2?}, {} instructions", report.instructions);
+        }
+        let flow = match report.end {
+            EndReason::EndOfSource => Flow::ShowTape,
+            EndReason::AwaitingLoopEnd => Flow::Prompt,
+        };
+        Ok((flow, io.printed))
+    }
+}
+
+/// Keeps what a line prints, so it can be recorded
+struct Printed<'a, I> {
+    inner: &'a mut I,
+    printed: Vec<u8>,
+}
+
+impl<I: BfIo> BfIo for Printed<'_, I> {
+    fn read(&mut self) -> brainfuck::Result<Option<u8>> {
+        self.inner.read()
+    }
+    fn write(&mut self, byte: u8) -> brainfuck::Result<()> {
+        self.printed.push(byte);
+        self.inner.write(byte)
+    }
+}
+
+impl<C: Cell> Session<'_, C> {
+    fn prompt(&self) -> String {
+        #[cfg(not(target_os = "wasi"))]
+        if let Some(remote) = &self.remote {
+            return format!("{}> ", remote.address());
+        }
+        if self.state.loop_nesting > 0 {
+            // Waiting for the rest of a loop
+            return ".. ".to_owned();
+        }
+        self.prompt.render(self.state)
+    }
+}
+
+/// The prompt of the shell, shown as written except for `{pointer}` and `{cell}`,
+/// which show where the pointer is and the value of the cell it's at.
+/// `{{` and `}}` are literal braces.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    template: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(String),
+    Pointer,
+    Cell,
+}
+
+impl Prompt {
+    fn render<C: Cell>(&self, state: &State<C>) -> String {
+        let mut prompt = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => prompt.push_str(text),
+                Part::Pointer => prompt.push_str(&state.cell_pointer.to_string()),
+                Part::Cell => prompt.push_str(&state.get(state.cell_pointer).value().to_string()),
+            }
+        }
+        prompt
+    }
+}
+
+impl Default for Prompt {
+    fn default() -> Self {
+        "$> ".parse().unwrap()
+    }
+}
+
+impl FromStr for Prompt {
+    type Err = String;
+    fn from_str(template: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(c) = rest.chars().next() {
+            rest = &rest[c.len_utf8()..];
+            match c {
+                '{' if rest.starts_with('{') => {
+                    text.push('{');
+                    rest = &rest[1..];
+                }
+                '}' if rest.starts_with('}') => {
+                    text.push('}');
+                    rest = &rest[1..];
+                }
+                '{' => {
+                    let (name, after) = rest
+                        .split_once('}')
+                        .ok_or("the prompt has a { without a }, write {{ for a brace")?;
+                    let part = match name {
+                        "pointer" => Part::Pointer,
+                        "cell" => Part::Cell,
+                        _ => {
+                            return Err(format!(
+                                "the prompt can't show {{{name}}}, only {{pointer}} and {{cell}}"
+                            ))
+                        }
+                    };
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(part);
+                    rest = after;
+                }
+                '}' => {
+                    return Err("the prompt has a } without a {, write }} for a brace".to_owned())
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Prompt {
+            template: template.to_owned(),
+            parts,
+        })
+    }
+}
+
+fn print_help<C: Cell>(session: &Session<C>) {
+    let state = &*session.state;
+    println!("Commands:");
+    for cmd in Session::<C>::META_COMMANDS {
+        println!("  {:10}  {}", cmd.name, cmd.help);
+    }
+    println!();
+    println!("Configuration:");
+    match state.cells_limit().limit() {
+        Some(limit) => println!("  cell limit  {limit}"),
+        None => println!("  cell limit  none"),
+    }
+    println!("  wrap        {}", state.cells_limit().wraps());
+    println!("  cell        {}", cell_name::<C>());
+    println!("  eof         {:?}", state.eof());
+    println!("  prompt      \"{}\"", session.prompt.template);
+    let display = match session.display {
+        Layout::Decimal => "dec",
+        Layout::Ascii => "ascii",
+        _ => "hex",
+    };
+    println!("  display     {display}");
+    println!(
+        "  timing      {}",
+        if session.timing { "on" } else { "off" }
+    );
+    println!();
+    println!("Key bindings:");
+    for (key, help) in KEY_BINDINGS {
+        println!("  {key:10}  {help}");
+    }
+}
+
+fn cell_name<C: Cell>() -> &'static str {
+    let name = std::any::type_name::<C::Value>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Lines entered into the shell, kept in a file in the user's data directory
+struct History {
+    lines: Vec<String>,
+    file: Option<File>,
+}
+
+impl History {
+    fn load() -> Self {
+        let Some(path) = history_path() else {
+            return History {
+                lines: Vec::new(),
+                file: None,
+            };
+        };
+
+        let mut lines: Vec<String> = File::open(&path)
+            .map(|f| BufReader::new(f).lines().map_while(|l| l.ok()).collect())
+            .unwrap_or_default();
+        if lines.len() > HISTORY_LIMIT {
+            lines.drain(..lines.len() - HISTORY_LIMIT);
+            // Lines were only appended during earlier sessions, so the oldest are dropped here
+            let _ = fs::write(
+                &path,
+                lines
+                    .iter()
+                    .map(|line| line.clone() + "\n")
+                    .collect::<String>(),
+            );
+        }
+        let file = path
+            .parent()
+            .and_then(|dir| fs::create_dir_all(dir).ok())
+            .and_then(|()| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .ok()
+            });
+
+        History { lines, file }
+    }
+    fn push(&mut self, line: &str) {
+        if let Some(file) = &mut self.file {
+            // Failing to save history shouldn't interrupt the session
+            let _ = writeln!(file, "{line}");
+        }
+        if self.lines.len() == HISTORY_LIMIT {
+            self.lines.remove(0);
+        }
+        self.lines.push(line.to_owned());
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    Some(data_dir()?.join("brainfuck").join("history"))
+}
+
+fn data_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    }
+}
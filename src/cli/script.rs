@@ -0,0 +1,93 @@
+//! Shell sessions written down as the lines entered and what they did, to record and replay
+//!
+//! ```text
+//! # Comments and blank lines are ignored
+//! > ++++++++[>++++++++<-]>+.
+//! | A
+//! = 00[41]
+//! > $reset
+//! = [00]
+//! ```
+//!
+//! `>` is a line entered into the shell, `|` what it printed, escaped like a byte string,
+//! and `=` the tape shown after it.
+//! A line without `|` has to print nothing, while one without `=` doesn't have its tape checked.
+
+use std::fs::File;
+use std::io::{self, Write};
+
+use super::{Failure, Result};
+
+/// A line entered into the shell and what it has to do
+pub struct Entry {
+    /// The line in the script it was entered on
+    pub number: usize,
+    pub input: String,
+    pub printed: String,
+    pub tape: Option<String>,
+}
+
+/// Reads the entries of the script `text`, which is called `path`
+pub fn parse(text: &str, path: &str) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (kind, rest) = line.split_at(line.chars().next().map_or(0, char::len_utf8));
+        let rest = rest.strip_prefix(' ').unwrap_or(rest);
+        let entry = entries.last_mut();
+        match (kind, entry) {
+            (">", _) => entries.push(Entry {
+                number,
+                input: rest.to_owned(),
+                printed: String::new(),
+                tape: None,
+            }),
+            ("|", Some(entry)) => entry.printed = rest.to_owned(),
+            ("=", Some(entry)) => entry.tape = Some(rest.to_owned()),
+            ("|" | "=", None) => {
+                return Err(Failure::Source(format!(
+                    "error: {path}:{number}: `{kind}` has to come after a `>` line\n"
+                )))
+            }
+            _ => {
+                return Err(Failure::Source(format!(
+                    "error: {path}:{number}: lines have to start with `>`, `|`, `=` or `#`\n"
+                )))
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Writes the lines entered into the shell to a script as they are run
+pub struct Recorder {
+    path: String,
+    file: File,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Ok(Recorder {
+            path: path.to_owned(),
+            file: File::create(path)?,
+        })
+    }
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// Records that `input` was entered, printing `printed` and showing `tape` if it did
+    pub fn record(&mut self, input: &str, printed: &[u8], tape: Option<&str>) -> io::Result<()> {
+        writeln!(self.file, "> {input}")?;
+        if !printed.is_empty() {
+            writeln!(self.file, "| {}", printed.escape_ascii())?;
+        }
+        if let Some(tape) = tape {
+            writeln!(self.file, "= {tape}")?;
+        }
+        self.file.flush()
+    }
+}
@@ -0,0 +1,65 @@
+//! Checks that every engine runs the conformance cases as they should
+
+use std::io::{stdout, Write};
+use std::process::ExitCode;
+
+use brainfuck::{
+    bytecode::Bytecode,
+    conformance::{CaseIo, CASES},
+    run_program, run_slice,
+    tiered::run_tiered,
+    Program, Result as BfResult, State,
+};
+
+use super::Result;
+
+type Engine = fn(&[u8], &mut State, &mut CaseIo) -> BfResult<()>;
+
+const ENGINES: [(&str, Engine); 4] = [
+    ("stream", |src, state, io| {
+        run_slice(src, state, io).map(drop)
+    }),
+    ("step", |src, state, io| {
+        run_program(&Program::parse(src)?, state, io).map(drop)
+    }),
+    ("bytecode", |src, state, io| {
+        Bytecode::compile(&Program::parse(src)?)
+            .run(state, io)
+            .map(drop)
+    }),
+    // Compiles every loop the first time it runs
+    ("tiered", |src, state, io| {
+        run_tiered(&Program::parse(src)?, state, io, 1).map(drop)
+    }),
+];
+
+/// Succeeds if every case passes on every engine
+pub fn run() -> Result<ExitCode> {
+    let mut out = stdout().lock();
+    let mut failures = Vec::new();
+    for case in CASES {
+        write!(out, "{:<28}", case.name)?;
+        for (engine, run) in ENGINES {
+            match case.check(run) {
+                Ok(()) => write!(out, " {engine} ok  ")?,
+                Err(mismatch) => {
+                    write!(out, " {engine} FAIL")?;
+                    failures.push((case.name, engine, mismatch));
+                }
+            }
+        }
+        writeln!(out)?;
+    }
+
+    let checks = CASES.len() * ENGINES.len();
+    writeln!(out)?;
+    for (case, engine, mismatch) in &failures {
+        writeln!(out, "{case} on {engine}: {mismatch}")?;
+    }
+    writeln!(out, "{} of {checks} checks passed", checks - failures.len())?;
+    Ok(if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -0,0 +1,261 @@
+//! A small HTTP service running submitted programs in sandboxes
+//!
+//! `POST /run` takes a JSON object like
+//! `{"program": "+[,.]", "input": "hi", "eof": "zero", "fuel": 1000, "cells": 300, "output": 64}`,
+//! where everything but the program is optional and the limits can only lower the server's quotas.
+//! It answers with the output, how the run ended, the error if it failed and statistics.
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use brainfuck::{
+    pool::ExecutorPool,
+    sandbox::{End, Limits, Outcome, Sandbox},
+    Eof,
+};
+
+use super::json::Json;
+use super::Result;
+
+/// The largest request body accepted, in bytes
+const MAX_BODY: usize = 1 << 20;
+/// The longest request line or header accepted, in bytes
+const MAX_LINE: usize = 8 << 10;
+/// The most headers a request may have
+const MAX_HEADERS: usize = 100;
+/// How long a client may take to send each part of its request or receive the response
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+/// The most connections handled at once, beyond which clients are told to come back later
+const MAX_CONNECTIONS: usize = 256;
+
+pub struct Options {
+    pub host: String,
+    pub port: u16,
+    pub workers: NonZeroUsize,
+    /// The most any request may use
+    pub quotas: Limits,
+    /// The most wall time a program may run for
+    pub timeout: Duration,
+}
+
+struct Server {
+    pool: ExecutorPool,
+    quotas: Limits,
+    timeout: Duration,
+    /// How many connections are being handled
+    connections: AtomicUsize,
+}
+
+pub fn run(options: Options) -> Result<()> {
+    let listener = TcpListener::bind((options.host.as_str(), options.port))?;
+    eprintln!("Listening on http://{}", listener.local_addr()?);
+
+    let server = Arc::new(Server {
+        pool: ExecutorPool::new(options.workers),
+        quotas: options.quotas,
+        timeout: options.timeout,
+        connections: AtomicUsize::new(0),
+    });
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue };
+        // A client that goes away or stalls only ends its own connection
+        if stream.set_read_timeout(Some(IO_TIMEOUT)).is_err()
+            || stream.set_write_timeout(Some(IO_TIMEOUT)).is_err()
+        {
+            continue;
+        }
+        if server.connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            server.connections.fetch_sub(1, Ordering::SeqCst);
+            let _ = respond(&mut stream, 503, error("too many connections"));
+            continue;
+        }
+        let server = server.clone();
+        thread::spawn(move || {
+            let _ = server.handle(stream);
+            server.connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    Ok(())
+}
+
+impl Server {
+    fn handle(&self, mut stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (status, body) = match read_head(&mut reader) {
+            Ok(Head { path, .. }) if path != "/run" => (404, error("not found")),
+            Ok(Head { method, .. }) if method != "POST" => (405, error("only POST is allowed")),
+            Ok(Head { length, .. }) if length > MAX_BODY => {
+                (413, error("request body is too large"))
+            }
+            Ok(Head { length, .. }) => {
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body)?;
+                match Json::parse(&String::from_utf8_lossy(&body)) {
+                    Some(request) => self.run(&request),
+                    None => (400, error("request body isn't JSON")),
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                (431, error("request line or headers are too large"))
+            }
+            Err(e) => return Err(e),
+        };
+        respond(&mut stream, status, body)
+    }
+
+    fn run(&self, request: &Json) -> (u16, Json) {
+        let Some(program) = request.get("program").as_str() else {
+            return (400, error("`program` has to be a string"));
+        };
+        let input = request.get("input").as_str().unwrap_or("");
+        let eof = match request.get("eof").as_str() {
+            None | Some("error") => Eof::Error,
+            Some("zero") => Eof::Zero,
+            Some("minus-one") => Eof::MinusOne,
+            Some("unchanged") => Eof::Unchanged,
+            Some("terminate") => Eof::Terminate,
+            Some(_) => {
+                return (
+                    400,
+                    error("`eof` has to be error, zero, minus-one, unchanged or terminate"),
+                )
+            }
+        };
+        let limit = |key, quota: u64| request.get(key).as_u64().map_or(quota, |n| n.min(quota));
+        let quotas = self.quotas;
+        let limits = Limits {
+            fuel: limit("fuel", quotas.fuel),
+            cells: NonZeroUsize::new(limit("cells", quotas.cells.get() as u64) as usize)
+                .unwrap_or(quotas.cells),
+            output: limit("output", quotas.output as u64) as usize,
+        };
+
+        let start = Instant::now();
+        let job = self.pool.submit(
+            Sandbox::new(limits).eof(eof),
+            program.as_bytes().to_vec(),
+            input.as_bytes().to_vec(),
+        );
+        let (outcome, timed_out) = match job.wait_timeout(self.timeout) {
+            Some(outcome) => (outcome, false),
+            None => {
+                job.cancel();
+                (job.wait(), true)
+            }
+        };
+        (200, response(outcome, timed_out, start.elapsed()))
+    }
+}
+
+/// The parts of a request that matter before its body
+struct Head {
+    method: String,
+    path: String,
+    length: usize,
+}
+
+/// Reads the request line and headers, failing with `InvalidData` if there are too many of them
+/// or any is too long
+fn read_head(reader: &mut impl BufRead) -> io::Result<Head> {
+    let request_line = read_line(reader)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_owned();
+    let path = parts.next().unwrap_or("").to_owned();
+
+    let mut length = 0;
+    for _ in 0..=MAX_HEADERS {
+        let line = read_line(reader)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            return Ok(Head {
+                method,
+                path,
+                length,
+            });
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    Err(ErrorKind::InvalidData.into())
+}
+
+/// Reads a line of at most `MAX_LINE` bytes, which is empty at the end of the stream
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    let read = reader.take(MAX_LINE as u64).read_line(&mut line)?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err(ErrorKind::InvalidData.into());
+    }
+    Ok(line)
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: Json) -> io::Result<()> {
+    let body = body.to_string();
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+fn response(outcome: Outcome, timed_out: bool, time: Duration) -> Json {
+    let Outcome {
+        output,
+        end,
+        instructions,
+    } = outcome;
+    let (end, e) = match end {
+        End::Finished => ("finished", None),
+        End::Failed(e) => ("failed", Some(e)),
+        End::OutOfFuel => ("out of fuel", None),
+        End::OutputLimitReached => ("output limit reached", None),
+        End::Cancelled if timed_out => ("timed out", None),
+        End::Cancelled => ("cancelled", None),
+    };
+    let position = e.as_ref().and_then(|e| e.position());
+    Json::object([
+        (
+            "output",
+            String::from_utf8_lossy(&output).into_owned().into(),
+        ),
+        ("end", end.into()),
+        ("error", e.map(|e| e.to_string()).into()),
+        (
+            "position",
+            position
+                .map(|pos| Json::object([("line", pos.line.into()), ("column", pos.column.into())]))
+                .into(),
+        ),
+        (
+            "stats",
+            Json::object([
+                ("instructions", instructions.into()),
+                ("bytes_written", output.len().into()),
+                ("milliseconds", (time.as_millis() as u64).into()),
+            ]),
+        ),
+    ])
+}
+
+fn error(message: &str) -> Json {
+    Json::object([("error", message.into())])
+}
@@ -0,0 +1,72 @@
+//! Checking on a long-running program with `--dump-on-signal`: sending it SIGUSR1
+//! prints where it is and the tape around the pointer to stderr, without stopping it
+
+use std::fs::File;
+use std::io::{self, ErrorKind, Read};
+use std::os::unix::io::FromRawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use brainfuck::ControlHandle;
+
+use super::TAPE_EXCERPT;
+
+/// How long to wait for the running program to show its tape
+const VIEW_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The end of the pipe the signal handler writes to, to wake up the thread printing the dump,
+/// since hardly anything can be done in the handler itself
+static SIGNALLED: AtomicI32 = AtomicI32::new(-1);
+
+/// Prints the progress of the program controlled by `handle` every time SIGUSR1 is received
+pub fn watch(handle: ControlHandle) -> io::Result<()> {
+    let mut fds = [0; 2];
+    // Safety: `fds` has room for both ends of the pipe
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    SIGNALLED.store(fds[1], Ordering::SeqCst);
+    // Safety: the read end was just opened and nothing else owns it
+    let mut signals = unsafe { File::from_raw_fd(fds[0]) };
+
+    // Safety: `sigaction` is plain data that is filled in before it's used
+    let mut action = unsafe { std::mem::zeroed::<libc::sigaction>() };
+    action.sa_sigaction = signalled as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // Reading input isn't interrupted by the signal
+    action.sa_flags = libc::SA_RESTART;
+    // Safety: the handler only writes to the pipe, which is async-signal-safe
+    if unsafe { libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    thread::spawn(move || loop {
+        match signals.read(&mut [0]) {
+            Ok(0) => break,
+            Ok(_) => eprintln!("{}", dump(&handle)),
+            Err(e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(_) => break,
+        }
+    });
+    Ok(())
+}
+
+extern "C" fn signalled(_: libc::c_int) {
+    let fd = SIGNALLED.load(Ordering::SeqCst);
+    // Safety: `write` is async-signal-safe and the byte is valid to read
+    unsafe {
+        libc::write(fd, [0u8].as_ptr().cast(), 1);
+    }
+}
+
+fn dump(handle: &ControlHandle) -> String {
+    let tape = match handle.tape_excerpt(VIEW_TIMEOUT, TAPE_EXCERPT) {
+        Some(view) => view,
+        None => "not shown, the program may be waiting for input".to_owned(),
+    };
+    format!(
+        "pointer at cell {} after {} instructions\ntape {tape}",
+        handle.cell_pointer(),
+        handle.instructions()
+    )
+}
@@ -0,0 +1,120 @@
+//! Explains what a program did once it's done, for people running their first programs
+
+use std::io::{stderr, Read, Write};
+
+use brainfuck::{BfIo, Cell, InOuter, Instruction, Program, Result, State};
+
+/// Runs `program` and prints a summary of the run to stderr, even if it fails
+pub fn run<W: Write, R: Read, C: Cell>(
+    program: &Program,
+    state: &mut State<C>,
+    io: &mut InOuter<W, R>,
+) -> Result<()> {
+    let depths = depths(program);
+    let mut io = Printed {
+        inner: io,
+        printed: Vec::new(),
+    };
+
+    let mut result = Ok(());
+    let mut steps = 0u64;
+    let mut cells = state.cell_pointer + 1;
+    let mut deepest = 0;
+    let mut pc = 0;
+    while pc < program.len() {
+        deepest = deepest.max(depths[pc]);
+        match program.step(state, pc, &mut io) {
+            Ok(next) => pc = next,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+        steps += 1;
+        cells = cells.max(state.cell_pointer + 1);
+    }
+    io.inner.flush()?;
+
+    let heading = match result {
+        Ok(()) => "Summary:",
+        Err(_) => "Summary until the error:",
+    };
+    let mut err = stderr().lock();
+    writeln!(err)?;
+    writeln!(err, "{heading}")?;
+    writeln!(
+        err,
+        "  The program took {steps} {}.",
+        plural(steps, "step", "steps")
+    )?;
+    writeln!(
+        err,
+        "  The pointer visited {cells} {}.",
+        plural(cells as u64, "cell", "cells")
+    )?;
+    match deepest {
+        0 => writeln!(err, "  It never went into a loop.")?,
+        1 => writeln!(
+            err,
+            "  It went into loops, but never into a loop inside another loop."
+        )?,
+        n => writeln!(
+            err,
+            "  At its deepest, it was in {n} loops inside each other."
+        )?,
+    }
+    match io.printed.len() {
+        0 => writeln!(err, "  It didn't print anything.")?,
+        n => writeln!(
+            err,
+            "  It printed {n} {}: \"{}\"",
+            plural(n as u64, "character", "characters"),
+            io.printed.escape_ascii()
+        )?,
+    }
+    result
+}
+
+/// How many loops each instruction is inside of
+fn depths(program: &Program) -> Vec<usize> {
+    let mut depth = 0;
+    program
+        .instructions()
+        .iter()
+        .map(|instruction| match instruction {
+            Instruction::LoopBegin(_) => {
+                depth += 1;
+                depth - 1
+            }
+            Instruction::LoopEnd(_) => {
+                depth -= 1;
+                depth + 1
+            }
+            _ => depth,
+        })
+        .collect()
+}
+
+fn plural(n: u64, one: &'static str, many: &'static str) -> &'static str {
+    if n == 1 {
+        one
+    } else {
+        many
+    }
+}
+
+/// Keeps everything the program writes
+struct Printed<'a, I> {
+    inner: &'a mut I,
+    printed: Vec<u8>,
+}
+
+impl<I: BfIo> BfIo for Printed<'_, I> {
+    fn read(&mut self) -> Result<Option<u8>> {
+        self.inner.read()
+    }
+    fn write(&mut self, byte: u8) -> Result<()> {
+        self.printed.push(byte);
+        self.inner.write(byte)
+    }
+}
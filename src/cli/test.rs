@@ -0,0 +1,129 @@
+//! Runs the test suite of a brainfuck project: every `name.bf` in a directory,
+//! with `name.in` as its input and `name.out` as what it has to print, if they're there
+
+use std::fs;
+use std::io::{stdout, ErrorKind, Write};
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+use brainfuck::{
+    sandbox::{End, Limits, Sandbox},
+    Eof,
+};
+
+use super::Result;
+
+/// The extensions of the programs that are tests
+const EXTENSIONS: [&str; 3] = ["bf", "b", "brainfuck"];
+/// The most differing lines shown for a test
+const MAX_DIFF_LINES: usize = 10;
+
+/// Succeeds if every test in `dir` passes
+pub fn run(
+    dir: &Path,
+    limits: Limits,
+    eof: Eof,
+    loop_limit: Option<NonZeroU64>,
+) -> Result<ExitCode> {
+    let mut tests = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    tests.retain(|path| {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| EXTENSIONS.contains(&ext))
+    });
+    tests.sort();
+
+    let sandbox = Sandbox::new(limits).eof(eof).loop_limit(loop_limit);
+    let mut out = stdout().lock();
+    let mut failed = 0;
+    let start = Instant::now();
+    for path in &tests {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let src = super::read_program(path)?;
+        let input = read_companion(path, "in")?.unwrap_or_default();
+        let expected = read_companion(path, "out")?;
+
+        let test_start = Instant::now();
+        let outcome = sandbox.run(&src, &input);
+        let elapsed = test_start.elapsed();
+
+        let problem = match outcome.end {
+            End::Finished => None,
+            End::Failed(e) => Some(format!("error: {e}")),
+            End::OutOfFuel => Some(format!(
+                "ran out of fuel after {} instructions",
+                limits.fuel
+            )),
+            End::OutputLimitReached => Some(format!("printed more than {} bytes", limits.output)),
+            End::Cancelled => unreachable!("tests aren't cancelled"),
+        };
+        let mismatch = expected.filter(|expected| *expected != outcome.output);
+        if problem.is_none() && mismatch.is_none() {
+            writeln!(out, "{name:<28} ok    {elapsed:.2?}")?;
+            continue;
+        }
+
+        failed += 1;
+        writeln!(out, "{name:<28} FAIL  {elapsed:.2?}")?;
+        if let Some(problem) = problem {
+            writeln!(out, "    {problem}")?;
+        }
+        if let Some(expected) = mismatch {
+            write_diff(&mut out, &expected, &outcome.output)?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{} of {} tests passed in {:.2?}",
+        tests.len() - failed,
+        tests.len(),
+        start.elapsed()
+    )?;
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Reads the file next to the test at `path` with the extension `ext`, if there is one
+fn read_companion(path: &Path, ext: &str) -> Result<Option<Vec<u8>>> {
+    match fs::read(path.with_extension(ext)) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes the lines that differ between what a test had to print and what it did,
+/// `-` for expected and `+` for printed
+fn write_diff(out: &mut impl Write, expected: &[u8], got: &[u8]) -> Result<()> {
+    let expected: Vec<&[u8]> = expected.split_inclusive(|&b| b == b'\n').collect();
+    let got: Vec<&[u8]> = got.split_inclusive(|&b| b == b'\n').collect();
+    let mut shown = 0;
+    for i in 0..expected.len().max(got.len()) {
+        let (e, g) = (expected.get(i), got.get(i));
+        if e == g {
+            continue;
+        }
+        if shown == MAX_DIFF_LINES {
+            writeln!(out, "    ...")?;
+            break;
+        }
+        shown += 1;
+        writeln!(out, "    line {}:", i + 1)?;
+        if let Some(e) = e {
+            writeln!(out, "    - {}", e.escape_ascii())?;
+        }
+        if let Some(g) = g {
+            writeln!(out, "    + {}", g.escape_ascii())?;
+        }
+    }
+    Ok(())
+}
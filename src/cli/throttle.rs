@@ -0,0 +1,43 @@
+//! Slowing down what a program prints with `--delay` or `--bytes-per-second`,
+//! so that animations drawn in the terminal can be watched
+
+use std::io::{self, Write};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Writes and flushes one byte at a time, at most one every `interval`
+pub struct Throttle<W: Write> {
+    inner: W,
+    interval: Duration,
+    next: Instant,
+}
+
+impl<W: Write> Throttle<W> {
+    pub fn new(inner: W, interval: Duration) -> Self {
+        Throttle {
+            inner,
+            interval,
+            next: Instant::now(),
+        }
+    }
+}
+
+impl<W: Write> Write for Throttle<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            let now = Instant::now();
+            if let Some(wait) = self.next.checked_duration_since(now) {
+                sleep(wait);
+            }
+            self.inner.write_all(&[byte])?;
+            self.inner.flush()?;
+            // Time spent running the program since the last byte counts towards the wait,
+            // but bytes aren't let through faster to catch up after a long computation
+            self.next = self.next.max(now) + self.interval;
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,38 @@
+//! Writes a JSON object for every executed instruction, one per line
+
+use std::io::Write;
+
+use brainfuck::{BfIo, Cell, Program, Result, State};
+
+/// Runs `program`, writing a line like
+/// `{"step":1,"pc":0,"command":"+","line":1,"column":1,"pointer":0,"old":0,"new":1}`
+/// to `out` for each instruction, where `old` and `new` are the values of the cell at `pointer`
+/// before and after the instruction
+pub fn run<C: Cell, I: BfIo>(
+    program: &Program,
+    state: &mut State<C>,
+    io: &mut I,
+    out: &mut impl Write,
+) -> Result<()> {
+    let mut step = 0u64;
+    let mut pc = 0;
+    while pc < program.len() {
+        let pointer = state.cell_pointer;
+        let old = state.get_cur().value();
+        let executed = pc;
+        let result = program.step(state, pc, io);
+        let new = state.get(pointer).value();
+        step += 1;
+
+        let command = program.instructions()[executed].command();
+        let position = program.position(executed).unwrap_or_default();
+        writeln!(
+            out,
+            r#"{{"step":{step},"pc":{executed},"command":"{command:?}","line":{},"column":{},"pointer":{pointer},"old":{old},"new":{new}}}"#,
+            position.line, position.column
+        )?;
+        pc = result?;
+    }
+    out.flush()?;
+    Ok(())
+}
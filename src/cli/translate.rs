@@ -0,0 +1,18 @@
+use std::io::{stdout, Write};
+
+use brainfuck::{
+    diagnostic::Diagnostic,
+    dialect::{translate, Dialect},
+};
+
+use super::{Failure, Result};
+
+pub fn run(path: &str, from: Dialect, to: Dialect) -> Result<()> {
+    let src = super::read_program(path)?;
+    let translated = translate(&src, from, to)
+        .map_err(|e| Failure::Source(super::render(&Diagnostic::from(&e), path, &src)))?;
+
+    stdout().lock().write_all(&translated)?;
+
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Display},
+    mem::discriminant,
+    num::NonZeroUsize,
+};
+
+use crate::{BfIo, CellsLimit, Eof, Error, Position, Result, State};
+
+/// A program with the input and configuration it runs with, and what it has to do
+#[derive(Debug, Clone, Copy)]
+pub struct Case {
+    pub name: &'static str,
+    pub source: &'static [u8],
+    pub input: &'static [u8],
+    pub eof: Eof,
+    /// The amount of cells and whether the pointer wraps around them
+    pub cells: Option<(NonZeroUsize, bool)>,
+    pub expected: Expected,
+}
+
+/// How a case has to end
+#[derive(Debug, Clone, Copy)]
+pub enum Expected {
+    /// Finishing after writing exactly this
+    Output(&'static [u8]),
+    /// Failing with this kind of error, wherever it is
+    Error(&'static Error),
+}
+
+/// A case that didn't end as expected
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub expected: Expected,
+    /// What was written, and the error if it failed
+    pub got: (Vec<u8>, Option<Error>),
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.expected {
+            Expected::Output(output) => write!(f, "expected output \"{}\"", output.escape_ascii())?,
+            Expected::Error(e) => write!(f, "expected error \"{e}\"")?,
+        }
+        match &self.got {
+            (_, Some(e)) => write!(f, " but failed with \"{e}\""),
+            (output, None) => write!(f, " but got output \"{}\"", output.escape_ascii()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Mismatch {}
+
+/// The input and output of a case
+pub struct CaseIo {
+    input: core::slice::Iter<'static, u8>,
+    output: Vec<u8>,
+}
+
+impl BfIo for CaseIo {
+    #[inline]
+    fn read(&mut self) -> Result<Option<u8>> {
+        Ok(self.input.next().copied())
+    }
+    #[inline]
+    fn write(&mut self, byte: u8) -> Result<()> {
+        self.output.push(byte);
+        Ok(())
+    }
+}
+
+impl Case {
+    /// Runs the case with `run`, which runs the source on the state and I/O it is given
+    /// using the engine being checked, and then evaluates the state
+    pub fn check<F>(&self, run: F) -> core::result::Result<(), Mismatch>
+    where
+        F: FnOnce(&[u8], &mut State, &mut CaseIo) -> Result<()>,
+    {
+        let mut state = State::new(CellsLimit::new(self.cells));
+        state.set_eof(self.eof);
+        let mut io = CaseIo {
+            input: self.input.iter(),
+            output: Vec::new(),
+        };
+        // Like the interpreter, loops left open are only reported once the state is evaluated
+        let result =
+            run(self.source, &mut state, &mut io).and_then(|()| state.evaluate().map(drop));
+
+        let passed = match (&self.expected, &result) {
+            (Expected::Output(output), Ok(())) => *output == io.output,
+            (Expected::Error(expected), Err(e)) => discriminant(*expected) == discriminant(e),
+            _ => false,
+        };
+        if passed {
+            Ok(())
+        } else {
+            Err(Mismatch {
+                expected: self.expected,
+                got: (io.output, result.err()),
+            })
+        }
+    }
+}
+
+impl Case {
+    const fn input(self, input: &'static [u8]) -> Self {
+        let Case {
+            name,
+            source,
+            eof,
+            cells,
+            expected,
+            ..
+        } = self;
+        Case {
+            name,
+            source,
+            input,
+            eof,
+            cells,
+            expected,
+        }
+    }
+    const fn eof(self, eof: Eof) -> Self {
+        let Case {
+            name,
+            source,
+            input,
+            cells,
+            expected,
+            ..
+        } = self;
+        Case {
+            name,
+            source,
+            input,
+            eof,
+            cells,
+            expected,
+        }
+    }
+    const fn cells(self, n: usize, wrap: bool) -> Self {
+        let Case {
+            name,
+            source,
+            input,
+            eof,
+            expected,
+            ..
+        } = self;
+        let cells = match NonZeroUsize::new(n) {
+            Some(n) => Some((n, wrap)),
+            None => None,
+        };
+        Case {
+            name,
+            source,
+            input,
+            eof,
+            cells,
+            expected,
+        }
+    }
+}
+
+const fn case(name: &'static str, source: &'static [u8], expected: Expected) -> Case {
+    Case {
+        name,
+        source,
+        input: b"",
+        eof: Eof::Error,
+        cells: None,
+        expected,
+    }
+}
+
+const fn output(name: &'static str, source: &'static [u8], expected: &'static [u8]) -> Case {
+    case(name, source, Expected::Output(expected))
+}
+
+const fn error(name: &'static str, source: &'static [u8], error: &'static Error) -> Case {
+    case(name, source, Expected::Error(error))
+}
+
+static UNENDED_LOOP: Error = Error::UnendedLoop(Vec::new());
+
+/// Programs that pin down the semantics every engine has to agree on, for 8-bit cells
+pub static CASES: &[Case] = &[
+    output("hello world", b"++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.", b"Hello World!\n"),
+    output("decrement wraps", b"-.", b"\xff"),
+    output("increment wraps", b"-+.+[+].", b"\x00\x00"),
+    output("skipped loop", b"[.]+.", b"\x01"),
+    output("clear loop", b"+++[-].", b"\x00"),
+    output("multiplication", b"++++++[>+++++++<-]>.", b"*"),
+    output("nested multiplication", b"++[>+++[>++<-]<-]>>.", b"\x0c"),
+    output("scan right", b"+>++>+++>>+<<<<[>]<.", b"\x03"),
+    output("scan left", b">+>+>+[<]>.", b"\x01"),
+    output(
+        "deep nesting",
+        b"+[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[-]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]+.",
+        b"\x01",
+    ),
+    output("comments", b"a+b.c", b"\x01"),
+    output("input", b",.,.", b"ab").input(b"ab"),
+    error("end of input fails", b",", &Error::EndOfInput(Position::START)),
+    output("end of input as zero", b"+,.", b"\x00").eof(Eof::Zero),
+    output("end of input as minus one", b",.", b"\xff").eof(Eof::MinusOne),
+    output("end of input unchanged", b"+++,.", b"\x03").eof(Eof::Unchanged),
+    output("end of input terminates", b"+.,.[]", b"\x01").eof(Eof::Terminate),
+    output("end of input terminates in a loop", b"+[.,.]", b"\x01").eof(Eof::Terminate),
+    error("unstarted loop", b"]", &Error::NoLoopStarted(Position::START)),
+    error("unended loop", b"+[", &UNENDED_LOOP),
+    error("pointer below zero", b"<", &Error::CellPointerOverflow(Position::START)),
+    error("pointer past limit", b">>>", &Error::CellPointerOverflow(Position::START)).cells(3, false),
+    output("pointer wraps", b"<+++>>>.", b"\x03").cells(3, true),
+];
@@ -0,0 +1,203 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::{
+    string::{String, ToString},
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+#[cfg(feature = "std")]
+use crate::Layout;
+use crate::{Cell, Error, Result, State};
+
+/// The part of a `State` that can be reached from other threads
+#[derive(Debug, Default)]
+pub(crate) struct Shared {
+    running: AtomicBool,
+    paused: AtomicBool,
+    cell_pointer: AtomicUsize,
+    instructions: AtomicUsize,
+    /// Set by `ControlHandle::tape_view` until the running program has published a view
+    #[cfg(feature = "std")]
+    view_requested: AtomicBool,
+    /// How many cells around the pointer the requested view shows, or `usize::MAX` for all of them
+    #[cfg(feature = "std")]
+    view_around: AtomicUsize,
+    #[cfg(feature = "std")]
+    view: Mutex<Option<String>>,
+    /// Notified when the program is resumed or stopped, a view is requested or one is published,
+    /// always while holding the lock on `view` so that waiting on it can't miss anything
+    #[cfg(feature = "std")]
+    changed: Condvar,
+}
+
+impl Shared {
+    #[inline]
+    pub(crate) fn start(&self) {
+        self.running.store(true, Ordering::SeqCst);
+    }
+    #[inline]
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+    /// Publishes the progress of `state`, and a view of its tape if one has been requested,
+    /// and waits while paused.
+    /// Fails if the run has been stopped.
+    #[inline]
+    pub(crate) fn checkpoint<C: Cell>(&self, state: &State<C>) -> Result<()> {
+        self.cell_pointer
+            .store(state.cell_pointer, Ordering::Relaxed);
+        self.instructions
+            .store(state.instructions as usize, Ordering::Relaxed);
+        self.publish_view(state);
+        if self.paused.load(Ordering::SeqCst) {
+            self.wait_while_paused(state);
+        }
+        if self.running.load(Ordering::SeqCst) {
+            Ok(())
+        } else {
+            Err(Error::Stopped)
+        }
+    }
+}
+
+impl Shared {
+    #[cfg(feature = "std")]
+    #[inline]
+    fn publish_view<C: Cell>(&self, state: &State<C>) {
+        if self.view_requested.load(Ordering::Relaxed) {
+            if let Ok(mut published) = self.view.lock() {
+                self.publish_locked(&mut published, state);
+            }
+        }
+    }
+    #[cfg(feature = "std")]
+    fn publish_locked<C: Cell>(&self, published: &mut Option<String>, state: &State<C>) {
+        let view = state.dump(Layout::Inline);
+        *published = Some(match self.view_around.load(Ordering::Relaxed) {
+            usize::MAX => view.to_string(),
+            cells => view.around(cells).to_string(),
+        });
+        self.view_requested.store(false, Ordering::Relaxed);
+        self.changed.notify_all();
+    }
+    /// Blocks until resumed or stopped, still publishing views of the tape when asked to
+    #[cfg(feature = "std")]
+    #[cold]
+    fn wait_while_paused<C: Cell>(&self, state: &State<C>) {
+        let Ok(mut published) = self.view.lock() else {
+            return;
+        };
+        while self.paused.load(Ordering::SeqCst) && self.running.load(Ordering::SeqCst) {
+            if self.view_requested.load(Ordering::Relaxed) {
+                self.publish_locked(&mut published, state);
+            }
+            published = match self.changed.wait(published) {
+                Ok(published) => published,
+                Err(_) => return,
+            };
+        }
+    }
+    /// Wakes up a program waiting while paused to see what has changed
+    #[cfg(feature = "std")]
+    fn notify(&self) {
+        // Taking the lock makes sure the program is either waiting or hasn't checked yet
+        let _lock = self.view.lock();
+        self.changed.notify_all();
+    }
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    fn publish_view<C: Cell>(&self, _state: &State<C>) {}
+    /// Spins until resumed or stopped, since there's nothing to block on without `std`
+    #[cfg(not(feature = "std"))]
+    #[cold]
+    fn wait_while_paused<C: Cell>(&self, _state: &State<C>) {
+        while self.paused.load(Ordering::SeqCst) && self.running.load(Ordering::SeqCst) {
+            core::hint::spin_loop();
+        }
+    }
+    #[cfg(not(feature = "std"))]
+    fn notify(&self) {}
+    fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        self.notify();
+    }
+}
+
+/// Stops a running program, created by `State::get_stop_sender`
+pub struct Stopper {
+    pub(crate) inner: Arc<Shared>,
+}
+
+impl Stopper {
+    pub fn stop(self) {
+        self.inner.stop();
+    }
+}
+
+/// A handle for controlling and inspecting a `State` from other threads,
+/// created by `State::control_handle`
+#[derive(Debug, Clone)]
+pub struct ControlHandle {
+    pub(crate) inner: Arc<Shared>,
+}
+
+impl ControlHandle {
+    /// Stops the running program, which then fails with `Error::Stopped`
+    pub fn stop(&self) {
+        self.inner.stop();
+    }
+    /// Makes the running program wait until `resume` is called
+    pub fn pause(&self) {
+        self.inner.paused.store(true, Ordering::SeqCst);
+    }
+    pub fn resume(&self) {
+        self.inner.paused.store(false, Ordering::SeqCst);
+        self.inner.notify();
+    }
+    pub fn is_paused(&self) -> bool {
+        self.inner.is_paused()
+    }
+    pub fn is_running(&self) -> bool {
+        self.inner.running.load(Ordering::SeqCst)
+    }
+    /// The cell pointer as of the last checkpoint, which happens before every
+    /// top-level command and every loop iteration
+    pub fn cell_pointer(&self) -> usize {
+        self.inner.cell_pointer.load(Ordering::Relaxed)
+    }
+    /// The number of executed commands as of the last checkpoint
+    pub fn instructions(&self) -> usize {
+        self.inner.instructions.load(Ordering::Relaxed)
+    }
+    /// Asks the running program for a view of its tape, like the interactive shell shows,
+    /// which it gives at its next checkpoint.
+    /// `None` if it doesn't get to one within `timeout`, like when it's waiting for input.
+    #[cfg(feature = "std")]
+    pub fn tape_view(&self, timeout: Duration) -> Option<String> {
+        self.request_view(timeout, usize::MAX)
+    }
+    /// Like `tape_view`, but only showing the cells at most `cells` away from the pointer
+    /// like `TapeDump::around`
+    #[cfg(feature = "std")]
+    pub fn tape_excerpt(&self, timeout: Duration, cells: usize) -> Option<String> {
+        self.request_view(timeout, cells)
+    }
+    #[cfg(feature = "std")]
+    fn request_view(&self, timeout: Duration, around: usize) -> Option<String> {
+        let shared = &self.inner;
+        let mut view = shared.view.lock().ok()?;
+        *view = None;
+        shared.view_around.store(around, Ordering::Relaxed);
+        shared.view_requested.store(true, Ordering::Relaxed);
+        // A paused program publishes it right away
+        shared.changed.notify_all();
+        let (mut view, _) = shared
+            .changed
+            .wait_timeout_while(view, timeout, |view| view.is_none())
+            .ok()?;
+        shared.view_requested.store(false, Ordering::Relaxed);
+        view.take()
+    }
+}
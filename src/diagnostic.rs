@@ -0,0 +1,355 @@
+use alloc::{
+    borrow::ToOwned,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Display, Write};
+
+use crate::{
+    asm::{AsmError, AsmErrorKind},
+    dialect::UnpairedWord,
+    lint::{Lint, LintKind},
+    preprocess::{PreprocessError, PreprocessErrorKind, MAX_COUNT, MAX_REPEATED},
+    Error, Position,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A message about a place in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub position: Position,
+    pub message: String,
+    /// Primary labels are marked with `^`, secondary ones with `-`
+    pub primary: bool,
+}
+
+/// An error or warning that can be rendered together with the source it refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub hint: Option<String>,
+    /// Extra context shown after the hint
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            message: message.into(),
+            labels: Vec::new(),
+            hint: None,
+            notes: Vec::new(),
+        }
+    }
+    #[must_use]
+    pub fn with_label(mut self, position: Position, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            position,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+    #[must_use]
+    pub fn with_secondary_label(mut self, position: Position, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            position,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+    #[must_use]
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+    #[must_use]
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+    /// Renders the diagnostic with the source lines its labels point at.
+    ///
+    /// A label on a bracket also underlines its matching bracket and a label
+    /// inside a loop underlines the start of the innermost loop.
+    #[inline]
+    pub fn render(&self, path: &str, src: &[u8]) -> String {
+        self.render_styled(path, src, false)
+    }
+    /// Renders the diagnostic like `render`, colored with ANSI escape codes for terminals
+    #[inline]
+    pub fn render_colored(&self, path: &str, src: &[u8]) -> String {
+        self.render_styled(path, src, true)
+    }
+    fn render_styled(&self, path: &str, src: &[u8], colored: bool) -> String {
+        let paint = |style: &str, text: &dyn Display| {
+            if colored {
+                format!("\x1b[{style}m{text}\x1b[0m")
+            } else {
+                text.to_string()
+            }
+        };
+        let accent = match self.severity {
+            Severity::Error => BOLD_RED,
+            Severity::Warning => BOLD_YELLOW,
+        };
+
+        let mut labels = self.labels.clone();
+        let loops = loops(src);
+        for label in self.labels.iter().filter(|l| l.primary) {
+            let offset = label.position.offset;
+            let related = match src.get(offset) {
+                Some(b'[') => loops
+                    .iter()
+                    .find(|(start, _)| start.offset == offset)
+                    .map(|&(_, end)| (end, "loop ends here")),
+                Some(b']') => loops
+                    .iter()
+                    .find(|(_, end)| end.offset == offset)
+                    .map(|&(start, _)| (start, "loop starts here")),
+                _ => loops
+                    .iter()
+                    .filter(|(start, end)| start.offset < offset && offset < end.offset)
+                    .max_by_key(|(start, _)| start.offset)
+                    .map(|&(start, _)| (start, "in this loop")),
+            };
+            if let Some((position, message)) = related {
+                if !labels.iter().any(|l| l.position == position) {
+                    labels.push(Label {
+                        position,
+                        message: message.to_owned(),
+                        primary: false,
+                    });
+                }
+            }
+        }
+        labels.sort_by_key(|l| (l.position.offset, !l.primary));
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}{}",
+            paint(accent, &self.severity),
+            paint(BOLD, &format_args!(": {}", self.message))
+        );
+
+        let gutter = labels
+            .iter()
+            .map(|l| l.position.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let arrow = paint(BOLD_BLUE, &format_args!("{:gutter$}-->", ""));
+        match labels.iter().find(|l| l.primary).or(labels.first()) {
+            Some(first) => {
+                let _ = writeln!(out, "{arrow} {path}:{}", first.position);
+            }
+            None => {
+                let _ = writeln!(out, "{arrow} {path}");
+            }
+        }
+        let bar = paint(BOLD_BLUE, &format_args!("{:gutter$} |", ""));
+
+        let lines: Vec<&[u8]> = src.split(|&b| b == b'\n').collect();
+        let mut last_line = None;
+        for label in &labels {
+            let line_no = label.position.line;
+            let line = lines.get(line_no - 1).copied().unwrap_or_default();
+            if last_line != Some(line_no) {
+                let _ = writeln!(out, "{bar}");
+                let text = String::from_utf8_lossy(line);
+                let number = paint(BOLD_BLUE, &format_args!("{line_no:>gutter$} |"));
+                let _ = writeln!(out, "{number} {}", text.trim_end());
+                last_line = Some(line_no);
+            }
+            let prefix = &line[..(label.position.column - 1).min(line.len())];
+            let indent: String = String::from_utf8_lossy(prefix)
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let (marker, style) = if label.primary {
+                ('^', accent)
+            } else {
+                ('-', BOLD_BLUE)
+            };
+            let mark = format!("{marker} {}", label.message);
+            let _ = writeln!(out, "{bar} {indent}{}", paint(style, &mark.trim_end()));
+        }
+        let equals = paint(BOLD_BLUE, &format_args!("{:gutter$} =", ""));
+        if self.hint.is_some() || !self.notes.is_empty() {
+            let _ = writeln!(out, "{bar}");
+        }
+        if let Some(hint) = &self.hint {
+            let _ = writeln!(out, "{equals} {}: {hint}", paint(BOLD, &"hint"));
+        }
+        for note in &self.notes {
+            let _ = writeln!(out, "{equals} {}: {note}", paint(BOLD, &"note"));
+        }
+
+        out
+    }
+}
+
+const BOLD: &str = "1";
+const BOLD_RED: &str = "1;31";
+const BOLD_YELLOW: &str = "1;33";
+const BOLD_BLUE: &str = "1;34";
+
+/// Start and end of every matched loop in `src`
+fn loops(src: &[u8]) -> Vec<(Position, Position)> {
+    let mut loops = Vec::new();
+    let mut starts = Vec::new();
+    let mut pos = Position::START;
+    for &byte in src {
+        match byte {
+            b'[' => starts.push(pos),
+            b']' => {
+                if let Some(start) = starts.pop() {
+                    loops.push((start, pos));
+                }
+            }
+            _ => (),
+        }
+        pos.advance(byte);
+    }
+    loops
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(e: &Error) -> Self {
+        let diagnostic = |message: &str| Diagnostic::new(Severity::Error, message);
+        match *e {
+            Error::NoLoopStarted(pos) => diagnostic("cannot end a loop when none has been started")
+                .with_label(pos, "this `]` has no matching `[`")
+                .with_hint("remove it or start the loop with a `[` before it"),
+            Error::UnendedLoop(ref starts) => {
+                let mut diagnostic = diagnostic("ended with unended loops")
+                    .with_hint("end each loop with a matching `]`");
+                for &pos in starts {
+                    diagnostic = diagnostic.with_label(pos, "this loop is never ended");
+                }
+                diagnostic
+            }
+            Error::CellPointerOverflow(pos) => diagnostic("cell pointer overflowed limit")
+                .with_label(pos, "the pointer moves outside the tape here")
+                .with_hint("the cell limit can be raised or set to wrap around"),
+            Error::EndOfInput(pos) => diagnostic("tried to read past the end of input")
+                .with_label(pos, "this `,` has no input left to read")
+                .with_hint("check for a terminating byte before reading more"),
+            Error::InputLimitReached(pos) => diagnostic("tried to read more input than allowed")
+                .with_label(pos, "this `,` is past the input limit")
+                .with_hint("the input limit can be raised or made to end the input instead"),
+            Error::LoopLimitReached(pos) => diagnostic("a loop ran more iterations than allowed")
+                .with_label(pos, "this loop didn't end within the loop limit")
+                .with_hint("check that the loop's cell reaches zero, or raise the loop limit"),
+            Error::UninitializedRead { cell, position } => diagnostic("read uninitialized cell")
+                .with_label(position, format!("cell {cell} is read here"))
+                .with_hint(
+                    "cells start out as zero, check that the pointer is where you expect it",
+                ),
+            _ => Diagnostic::new(Severity::Error, e.to_string()),
+        }
+    }
+}
+
+impl From<&Lint> for Diagnostic {
+    fn from(lint: &Lint) -> Self {
+        let diagnostic =
+            Diagnostic::new(Severity::Warning, lint.kind.to_string()).with_label(lint.position, "");
+        match lint.kind {
+            LintKind::OverwrittenByInput => {
+                diagnostic.with_hint("remove the `+` and `-` or move them after the `,`")
+            }
+            LintKind::EmptyLoop => diagnostic.with_hint("use `[-]` to clear a cell"),
+            LintKind::DeadLoop => diagnostic.with_hint("a loop ends when its cell is zero"),
+            LintKind::PointerDrift(_) => diagnostic
+                .with_hint("balance the `<` and `>` in the loop body if this is unintended"),
+            LintKind::InfiniteLoop => {
+                diagnostic.with_hint("change the current cell in the loop so it can reach zero")
+            }
+            LintKind::Unreachable => diagnostic.with_hint("remove it or make the loop end"),
+        }
+    }
+}
+
+impl From<&PreprocessError> for Diagnostic {
+    fn from(e: &PreprocessError) -> Self {
+        let diagnostic = Diagnostic::new(Severity::Error, e.kind.to_string());
+        match e.kind {
+            PreprocessErrorKind::ExpectedName => diagnostic
+                .with_label(e.position, "")
+                .with_hint("macro names are made of letters, digits and `_`"),
+            PreprocessErrorKind::ExpectedBody => diagnostic
+                .with_label(e.position, "")
+                .with_hint("write the macro as `@define name { body }`"),
+            PreprocessErrorKind::UnterminatedBody => diagnostic
+                .with_label(e.position, "this `{` is never closed")
+                .with_hint("end the body with a matching `}`"),
+            PreprocessErrorKind::UnknownMacro(_) => diagnostic
+                .with_label(e.position, "invoked here")
+                .with_hint("macros have to be defined before they are invoked"),
+            PreprocessErrorKind::CountTooLarge => {
+                diagnostic.with_label(e.position, "").with_hint(format!(
+                "counts can be at most {MAX_COUNT} and add up to at most {MAX_REPEATED} commands, \
+                     so the program has to be written with loops instead"
+            ))
+            }
+            PreprocessErrorKind::ExpectedPath => diagnostic
+                .with_label(e.position, "")
+                .with_hint("write the include as `@include \"file.bf\"`"),
+            PreprocessErrorKind::IncludeFailed { .. } => diagnostic
+                .with_label(e.position, "included here")
+                .with_hint("paths are relative to the file that includes them"),
+            PreprocessErrorKind::CyclicInclude(_) => diagnostic
+                .with_label(e.position, "included again here")
+                .with_hint("move the code both files need into a third file"),
+            PreprocessErrorKind::NestedDefinition
+            | PreprocessErrorKind::Redefined(_)
+            | PreprocessErrorKind::Recursive(_) => diagnostic.with_label(e.position, ""),
+        }
+    }
+}
+
+impl From<&AsmError> for Diagnostic {
+    fn from(e: &AsmError) -> Self {
+        let diagnostic = Diagnostic::new(Severity::Error, e.kind.to_string());
+        match e.kind {
+            AsmErrorKind::UnknownVariable(_) => diagnostic
+                .with_label(e.position, "used here")
+                .with_hint("declare it with `var` before using it"),
+            AsmErrorKind::UnclosedBlock => diagnostic
+                .with_label(e.position, "this `{` is never closed")
+                .with_hint("end the block with a `}` on its own line"),
+            AsmErrorKind::ExpectedNewline => diagnostic
+                .with_label(e.position, "")
+                .with_hint("put each statement on its own line"),
+            _ => diagnostic.with_label(e.position, ""),
+        }
+    }
+}
+
+impl From<&UnpairedWord> for Diagnostic {
+    fn from(e: &UnpairedWord) -> Self {
+        Diagnostic::new(Severity::Error, "word isn't part of a command")
+            .with_label(e.0, "this word has no other word to pair with")
+            .with_hint("every command is two words, and `?` `?` isn't one")
+    }
+}
@@ -0,0 +1,204 @@
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+use crate::{Command, Command::*, Position};
+
+/// A language that is brainfuck with its commands spelled differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Brainfuck,
+    /// Commands are pairs of `Ook.`, `Ook?` and `Ook!`
+    Ook,
+    /// Like Ook, but with `Blub`
+    Blub,
+    /// Commands are the letters `a c e i j o p s`
+    Alphuck,
+}
+
+/// A part of a program in some dialect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Piece {
+    Command(Command),
+    /// Anything between commands
+    Comment(Vec<u8>),
+}
+
+/// A word of a word-based dialect that isn't paired with another into a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnpairedWord(pub Position);
+
+impl Display for UnpairedWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "word at {} isn't part of a command", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnpairedWord {}
+
+const COMMANDS: [Command; 8] = [PtrIncr, PtrDecr, Incr, Decr, Out, In, LoopBegin, LoopEnd];
+/// Spellings of `COMMANDS` in dialects with a byte for each command
+const BRAINFUCK: &[u8; 8] = b"><+-.,[]";
+const ALPHUCK: &[u8; 8] = b"aceijops";
+/// Punctuation after the two words of `COMMANDS` in Ook and Blub
+const PAIRS: [(u8, u8); 8] = [
+    (b'.', b'?'),
+    (b'?', b'.'),
+    (b'.', b'.'),
+    (b'!', b'!'),
+    (b'!', b'.'),
+    (b'.', b'!'),
+    (b'!', b'?'),
+    (b'?', b'!'),
+];
+
+enum Spelling {
+    Bytes(&'static [u8; 8]),
+    Words(&'static [u8]),
+}
+
+impl Dialect {
+    fn spelling(self) -> Spelling {
+        match self {
+            Dialect::Brainfuck => Spelling::Bytes(BRAINFUCK),
+            Dialect::Alphuck => Spelling::Bytes(ALPHUCK),
+            Dialect::Ook => Spelling::Words(b"Ook"),
+            Dialect::Blub => Spelling::Words(b"Blub"),
+        }
+    }
+
+    /// Splits `src` into commands and the comments between them
+    pub fn decode(self, src: &[u8]) -> Result<Vec<Piece>, UnpairedWord> {
+        let mut pieces = Vec::new();
+        let mut comment = Vec::new();
+
+        match self.spelling() {
+            Spelling::Bytes(spelling) => {
+                for &byte in src {
+                    match spelling.iter().position(|&b| b == byte) {
+                        Some(i) => push_command(&mut pieces, &mut comment, COMMANDS[i]),
+                        None => comment.push(byte),
+                    }
+                }
+            }
+            Spelling::Words(word) => {
+                let mut pos = Position::START;
+                let mut i = 0;
+                // The punctuation and position of the first word of a pair
+                let mut first: Option<(u8, Position)> = None;
+                while i < src.len() {
+                    match word_at(src, i, word) {
+                        Some(punctuation) => {
+                            match first.take() {
+                                None => first = Some((punctuation, pos)),
+                                Some((p, _)) => {
+                                    let cmd =
+                                        PAIRS.iter().position(|&pair| pair == (p, punctuation));
+                                    // Every pair of punctuation is a command except `?` `?`
+                                    let Some(cmd) = cmd else {
+                                        return Err(UnpairedWord(pos));
+                                    };
+                                    // A single space is only what separates two commands
+                                    if comment == b" "
+                                        && matches!(pieces.last(), Some(Piece::Command(_)))
+                                    {
+                                        comment.clear();
+                                    }
+                                    push_command(&mut pieces, &mut comment, COMMANDS[cmd]);
+                                }
+                            }
+                            for &byte in &src[i..=i + word.len()] {
+                                pos.advance(byte);
+                            }
+                            i += word.len() + 1;
+                        }
+                        None => {
+                            match first {
+                                None => comment.push(src[i]),
+                                // Whitespace between the words of a pair is dropped
+                                Some(_) if src[i].is_ascii_whitespace() => (),
+                                Some((_, pos)) => return Err(UnpairedWord(pos)),
+                            }
+                            pos.advance(src[i]);
+                            i += 1;
+                        }
+                    }
+                }
+                if let Some((_, pos)) = first {
+                    return Err(UnpairedWord(pos));
+                }
+            }
+        }
+
+        if !comment.is_empty() {
+            pieces.push(Piece::Comment(comment));
+        }
+        Ok(pieces)
+    }
+
+    /// Writes `pieces` in this dialect.
+    ///
+    /// Anything in comments that would be read as a command is left out.
+    /// In word-based dialects, commands without a comment between them are separated by a space.
+    pub fn encode(self, pieces: &[Piece]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for piece in pieces {
+            match (piece, self.spelling()) {
+                (&Piece::Command(cmd), Spelling::Bytes(spelling)) => {
+                    out.push(spelling[cmd as usize]);
+                }
+                (&Piece::Command(cmd), Spelling::Words(word)) => {
+                    let (first, second) = PAIRS[cmd as usize];
+                    for punctuation in [first, second] {
+                        if out.last().is_some_and(|b: &u8| !b.is_ascii_whitespace()) {
+                            out.push(b' ');
+                        }
+                        out.extend_from_slice(word);
+                        out.push(punctuation);
+                    }
+                }
+                (Piece::Comment(comment), Spelling::Bytes(spelling)) => {
+                    out.extend(comment.iter().filter(|b| !spelling.contains(b)));
+                }
+                (Piece::Comment(comment), Spelling::Words(word)) => {
+                    let mut i = 0;
+                    while i < comment.len() {
+                        if word_at(comment, i, word).is_some() {
+                            // Leave out the punctuation so it isn't a word anymore
+                            out.extend_from_slice(word);
+                            i += word.len() + 1;
+                        } else {
+                            out.push(comment[i]);
+                            i += 1;
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Translates `src` from one dialect to another
+pub fn translate(src: &[u8], from: Dialect, to: Dialect) -> Result<Vec<u8>, UnpairedWord> {
+    Ok(to.encode(&from.decode(src)?))
+}
+
+/// Pushes `cmd` after the comment before it, if there is one
+fn push_command(pieces: &mut Vec<Piece>, comment: &mut Vec<u8>, cmd: Command) {
+    if !comment.is_empty() {
+        pieces.push(Piece::Comment(core::mem::take(comment)));
+    }
+    pieces.push(Piece::Command(cmd));
+}
+
+/// The punctuation after `word` if it is at `i` in `src`
+fn word_at(src: &[u8], i: usize, word: &[u8]) -> Option<u8> {
+    if !src[i..].starts_with(word) {
+        return None;
+    }
+    match src.get(i + word.len()) {
+        Some(&p @ (b'.' | b'?' | b'!')) => Some(p),
+        _ => None,
+    }
+}
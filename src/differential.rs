@@ -0,0 +1,183 @@
+//! Differential testing of the optimizing engines against the streaming interpreter
+//!
+//! The streaming interpreter runs commands as it reads them without optimizing anything,
+//! so it's kept as the reference that the other engines have to agree with.
+//! An engine that ends a program differently has miscompiled it.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    fmt::{self, Display},
+    mem::discriminant,
+};
+
+use crate::{
+    bytecode::Bytecode, run_program, run_slice, tiered::run_tiered, BfIo, Error, Program, Result,
+    State,
+};
+
+type Engine = fn(&[u8], &mut State, &mut MemoryIo) -> Result<()>;
+
+/// The engine the others are compared with
+const REFERENCE: Engine = |src, state, io| run_slice(src, state, io).map(drop);
+
+/// The engines checked against the reference
+const ENGINES: [(&str, Engine); 3] = [
+    ("step", |src, state, io| {
+        run_program(&Program::parse(src)?, state, io).map(drop)
+    }),
+    ("bytecode", |src, state, io| {
+        Bytecode::compile(&Program::parse(src)?)
+            .run(state, io)
+            .map(drop)
+    }),
+    // Compiles every loop the first time it runs
+    ("tiered", |src, state, io| {
+        run_tiered(&Program::parse(src)?, state, io, 1).map(drop)
+    }),
+];
+
+/// How a differential check went when no engine disagreed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checked {
+    /// Every engine ended the program the same way
+    Agreed,
+    /// The program didn't end within the fuel, so it wasn't run on the engines
+    OutOfFuel,
+}
+
+/// Runs `src` with `input` on a copy of `template` on the reference and every other engine,
+/// checking that they print the same and end with the same tape and instruction count
+/// or the same kind of error
+///
+/// `src` has to parse, since the streaming interpreter only finds unmatched brackets
+/// once it gets to them.
+/// Programs that don't end within `fuel` instructions aren't run on the engines,
+/// since only stepping through a program can stop it after a number of instructions.
+pub fn check(
+    src: &[u8],
+    template: &State,
+    input: &[u8],
+    fuel: u64,
+) -> core::result::Result<Checked, Box<Divergence>> {
+    if !ends_within(src, template, input, fuel) {
+        return Ok(Checked::OutOfFuel);
+    }
+    let expected = run(REFERENCE, src, template, input);
+    for (engine, run_engine) in ENGINES {
+        let got = run(run_engine, src, template, input);
+        let same = expected.output == got.output
+            && match (&expected.error, &got.error) {
+                (None, None) => {
+                    expected.state == got.state
+                        && expected.state.instructions() == got.state.instructions()
+                }
+                (Some(e), Some(got)) => discriminant(e) == discriminant(got),
+                _ => false,
+            };
+        if !same {
+            return Err(Box::new(Divergence {
+                engine,
+                expected,
+                got,
+            }));
+        }
+    }
+    Ok(Checked::Agreed)
+}
+
+/// Whether stepping through `src` ends within `fuel` instructions
+fn ends_within(src: &[u8], template: &State, input: &[u8], fuel: u64) -> bool {
+    let Ok(program) = Program::parse(src) else {
+        return true;
+    };
+    let mut state = template.clone();
+    let mut io = MemoryIo {
+        input,
+        output: Vec::new(),
+    };
+    let mut pc = 0;
+    while pc < program.len() {
+        if state.instructions == fuel {
+            return false;
+        }
+        match program.step(&mut state, pc, &mut io) {
+            Ok(next) => pc = next,
+            Err(_) => return true,
+        }
+    }
+    true
+}
+
+fn run(engine: Engine, src: &[u8], template: &State, input: &[u8]) -> Ending {
+    let mut state = template.clone();
+    let mut io = MemoryIo {
+        input,
+        output: Vec::new(),
+    };
+    let error = engine(src, &mut state, &mut io).err();
+    Ending {
+        state,
+        output: io.output,
+        error,
+    }
+}
+
+/// How a run of a program ended
+#[derive(Debug, Clone)]
+pub struct Ending {
+    pub state: State,
+    pub output: Vec<u8>,
+    pub error: Option<Error>,
+}
+
+/// An engine that ended a program differently from the reference
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub engine: &'static str,
+    pub expected: Ending,
+    pub got: Ending,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn ending(f: &mut fmt::Formatter<'_>, ending: &Ending) -> fmt::Result {
+            write!(f, "output \"{}\" and ", ending.output.escape_ascii())?;
+            match &ending.error {
+                Some(e) => write!(f, "error \"{e}\""),
+                None => write!(
+                    f,
+                    "tape {} after {} instructions",
+                    ending.state,
+                    ending.state.instructions()
+                ),
+            }
+        }
+        write!(f, "{} ended with ", self.engine)?;
+        ending(f, &self.got)?;
+        f.write_str(" instead of ")?;
+        ending(f, &self.expected)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Divergence {}
+
+struct MemoryIo<'a> {
+    input: &'a [u8],
+    output: Vec<u8>,
+}
+
+impl BfIo for MemoryIo<'_> {
+    #[inline]
+    fn read(&mut self) -> Result<Option<u8>> {
+        Ok(self.input.split_first().map(|(&b, rest)| {
+            self.input = rest;
+            b
+        }))
+    }
+    #[inline]
+    fn write(&mut self, byte: u8) -> Result<()> {
+        self.output.push(byte);
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use core::fmt::{self, Display, Write};
+use core::num::Wrapping;
+
+use crate::{Cell, State};
+
+/// How `TapeDump` lays out the cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// Every cell in hex on one line, like `00 48[65]6c`
+    #[default]
+    Inline,
+    /// Rows of cells in hex with their index and the bytes they would print, like `hexdump -C`
+    Rows,
+    /// Every cell in decimal on one line, like `0 72 [101] 108`
+    Decimal,
+    /// Cells in decimal with repeated values counted, like `0×245 72 [101] …`
+    RunLength,
+    /// Every cell as the character it would print on one line, like `'H' ['e'] '\n'`,
+    /// or in decimal if it's too big to print
+    Ascii,
+}
+
+/// The tape of a `State` formatted in one of several layouts, created by `State::dump`
+///
+/// Cells are shown up to the last one that isn't zero, or the pointer if it's further,
+/// and the cell at the pointer is put in brackets.
+/// With `around`, only the cells near the pointer are shown.
+pub struct TapeDump<'a, C: Cell = Wrapping<u8>> {
+    state: &'a State<C>,
+    layout: Layout,
+    around: Option<usize>,
+}
+
+/// How many times a value has to repeat to be counted in `Layout::RunLength`
+const MIN_RUN: usize = 3;
+
+impl<C: Cell> State<C> {
+    #[inline]
+    pub fn dump(&self, layout: Layout) -> TapeDump<'_, C> {
+        TapeDump {
+            state: self,
+            layout,
+            around: None,
+        }
+    }
+}
+
+impl<C: Cell> TapeDump<'_, C> {
+    /// Only shows the cells at most `cells` away from the pointer,
+    /// with `…` where the tape goes on beyond them
+    #[inline]
+    pub fn around(mut self, cells: usize) -> Self {
+        self.around = Some(cells);
+        self
+    }
+    /// The number of cells on the tape up to the last one shown without `around`
+    fn len(&self) -> usize {
+        let mut cells = self.state.cells();
+        cells.trim_end();
+        cells.len().max(self.state.cell_pointer + 1)
+    }
+    /// The range of cells shown out of the first `len`
+    fn shown(&self, len: usize) -> (usize, usize) {
+        let pointer = self.state.cell_pointer;
+        match self.around {
+            Some(cells) => (
+                pointer.saturating_sub(cells),
+                pointer.saturating_add(cells).saturating_add(1).min(len),
+            ),
+            None => (0, len),
+        }
+    }
+    #[inline]
+    fn cell(&self, i: usize) -> C {
+        self.state.get(i)
+    }
+}
+
+impl<C: Cell> Display for TapeDump<'_, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = self.state.cell_pointer;
+        let len = self.len();
+        let (from, to) = self.shown(len);
+        let width = 2 * core::mem::size_of::<C::Value>();
+        // Rows show where they start instead
+        if from > 0 && self.layout != Layout::Rows {
+            f.write_str("… ")?;
+        }
+        match self.layout {
+            Layout::Inline => {
+                for i in from..to {
+                    if i == pointer {
+                        f.write_char('[')?;
+                    } else if i > from && i - 1 != pointer {
+                        f.write_char(' ')?;
+                    }
+                    write!(f, "{:0width$x}", self.cell(i).value())?;
+                    if i == pointer {
+                        f.write_char(']')?;
+                    }
+                }
+            }
+            Layout::Rows => {
+                let per_row = match width {
+                    2 => 16,
+                    4 => 8,
+                    _ => 4,
+                };
+                let first = from - from % per_row;
+                for start in (first..to).step_by(per_row) {
+                    if start > first {
+                        f.write_char('\n')?;
+                    }
+                    write!(f, "{start:08x} ")?;
+                    let end = (start + per_row).min(to);
+                    for i in start..end {
+                        let separator = if i == pointer {
+                            '['
+                        } else if i > start && i - 1 == pointer {
+                            ']'
+                        } else {
+                            ' '
+                        };
+                        write!(f, "{separator}{:0width$x}", self.cell(i).value())?;
+                    }
+                    f.write_char(if end - 1 == pointer { ']' } else { ' ' })?;
+                    // Short rows are padded so the bytes line up
+                    for _ in end..start + per_row {
+                        write!(f, "{:1$}", "", width + 1)?;
+                    }
+                    f.write_str(" |")?;
+                    for i in start..end {
+                        let byte = self.cell(i).to_byte();
+                        let c = if byte.is_ascii_graphic() || byte == b' ' {
+                            byte as char
+                        } else {
+                            '.'
+                        };
+                        f.write_char(c)?;
+                    }
+                    f.write_char('|')?;
+                }
+                return Ok(());
+            }
+            Layout::Decimal => {
+                for i in from..to {
+                    if i > from {
+                        f.write_char(' ')?;
+                    }
+                    write_decimal(f, self.cell(i).value(), 1, i == pointer)?;
+                }
+            }
+            Layout::RunLength => {
+                let mut i = from;
+                while i < to {
+                    if i > from {
+                        f.write_char(' ')?;
+                    }
+                    let value = self.cell(i).value();
+                    // Runs end at the pointer so it can be shown on its own
+                    let mut run = 1;
+                    while i + run < to && i + run != pointer && i != pointer {
+                        if self.cell(i + run).value() != value {
+                            break;
+                        }
+                        run += 1;
+                    }
+                    if run < MIN_RUN {
+                        run = 1;
+                    }
+                    write_decimal(f, value, run, i == pointer)?;
+                    i += run;
+                }
+                // The rest of the tape is zero
+                return f.write_str(" …");
+            }
+            Layout::Ascii => {
+                for i in from..to {
+                    if i > from {
+                        f.write_char(' ')?;
+                    }
+                    let cell = self.cell(i);
+                    let byte = cell.to_byte();
+                    if i == pointer {
+                        f.write_char('[')?;
+                    }
+                    if C::from_byte(byte) == cell {
+                        write!(f, "'{}'", byte.escape_ascii())?;
+                    } else {
+                        write!(f, "{}", cell.value())?;
+                    }
+                    if i == pointer {
+                        f.write_char(']')?;
+                    }
+                }
+            }
+        }
+        if to < len {
+            f.write_str(" …")?;
+        }
+        Ok(())
+    }
+}
+
+fn write_decimal<V: Display>(
+    f: &mut fmt::Formatter<'_>,
+    value: V,
+    run: usize,
+    at_pointer: bool,
+) -> fmt::Result {
+    match (run, at_pointer) {
+        (1, true) => write!(f, "[{value}]"),
+        (1, false) => write!(f, "{value}"),
+        (n, _) => write!(f, "{value}×{n}"),
+    }
+}
@@ -0,0 +1,181 @@
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Display},
+    mem::discriminant,
+};
+
+use crate::{BfIo, Cell, Error, Program, Result, State};
+
+/// How a program ended when run by `compare`
+#[derive(Debug)]
+pub enum RunEnd {
+    Finished,
+    Failed(Error),
+    /// The program ran for more instructions than allowed
+    OutOfFuel,
+}
+
+impl RunEnd {
+    /// Errors are the same if they are the same kind of error, wherever they happened
+    fn same_as(&self, other: &RunEnd) -> bool {
+        match (self, other) {
+            (RunEnd::Failed(a), RunEnd::Failed(b)) => discriminant(a) == discriminant(b),
+            _ => discriminant(self) == discriminant(other),
+        }
+    }
+}
+
+/// The result of running one of the programs on an input
+#[derive(Debug)]
+pub struct Run<C: Cell> {
+    pub output: Vec<u8>,
+    pub end: RunEnd,
+    pub state: State<C>,
+}
+
+/// An input the two programs behave differently on
+#[derive(Debug)]
+pub struct Divergence<C: Cell> {
+    pub input: Vec<u8>,
+    pub left: Run<C>,
+    pub right: Run<C>,
+}
+
+impl<C: Cell> Display for Divergence<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "on input {:?}: ", self.input)?;
+        let (left, right) = (&self.left, &self.right);
+        if left.output != right.output {
+            let at = left
+                .output
+                .iter()
+                .zip(&right.output)
+                .position(|(a, b)| a != b)
+                .unwrap_or(left.output.len().min(right.output.len()));
+            write!(
+                f,
+                "output differs at byte {at}: {:?} and {:?}",
+                left.output.get(at),
+                right.output.get(at)
+            )
+        } else if !left.end.same_as(&right.end) {
+            write!(
+                f,
+                "runs end differently: {:?} and {:?}",
+                left.end, right.end
+            )
+        } else {
+            write!(f, "final tapes differ: {} and {}", left.state, right.state)
+        }
+    }
+}
+
+/// Runs `left` and `right` on each input with a copy of `template` and returns
+/// the first input on which their output, how they end, or their final tape differs.
+///
+/// Each run may execute at most `fuel` instructions.
+/// Runs that both run out of fuel are only compared by the output produced until then.
+pub fn compare<C, I>(
+    left: &Program,
+    right: &Program,
+    template: &State<C>,
+    inputs: I,
+    fuel: u64,
+) -> Option<Divergence<C>>
+where
+    C: Cell,
+    I: IntoIterator<Item = Vec<u8>>,
+{
+    for input in inputs {
+        let l = run(left, template, &input, fuel);
+        let r = run(right, template, &input, fuel);
+        let both_out_of_fuel = matches!((&l.end, &r.end), (RunEnd::OutOfFuel, RunEnd::OutOfFuel));
+        let same = l.output == r.output
+            && l.end.same_as(&r.end)
+            && (both_out_of_fuel || l.state == r.state);
+        if !same {
+            return Some(Divergence {
+                input,
+                left: l,
+                right: r,
+            });
+        }
+    }
+    None
+}
+
+fn run<C: Cell>(program: &Program, template: &State<C>, input: &[u8], fuel: u64) -> Run<C> {
+    let mut state = template.clone();
+    let mut io = Recorder {
+        input: input.iter(),
+        output: Vec::new(),
+    };
+
+    let mut end = RunEnd::Finished;
+    let mut pc = 0;
+    while pc < program.len() {
+        if state.instructions - template.instructions == fuel {
+            end = RunEnd::OutOfFuel;
+            break;
+        }
+        match program.step(&mut state, pc, &mut io) {
+            Ok(next) => pc = next,
+            Err(e) => {
+                end = RunEnd::Failed(e);
+                break;
+            }
+        }
+    }
+
+    Run {
+        output: io.output,
+        end,
+        state,
+    }
+}
+
+struct Recorder<'a> {
+    input: core::slice::Iter<'a, u8>,
+    output: Vec<u8>,
+}
+
+impl BfIo for Recorder<'_> {
+    #[inline]
+    fn read(&mut self) -> Result<Option<u8>> {
+        Ok(self.input.next().copied())
+    }
+    #[inline]
+    fn write(&mut self, byte: u8) -> Result<()> {
+        self.output.push(byte);
+        Ok(())
+    }
+}
+
+/// `count` pseudo-random inputs of up to `max_len` bytes, the same for the same `seed`
+pub fn random_inputs(seed: u64, count: usize, max_len: usize) -> impl Iterator<Item = Vec<u8>> {
+    let mut rng = Rng::new(seed);
+    (0..count).map(move |_| {
+        let len = rng.below(max_len + 1);
+        (0..len).map(|_| rng.next() as u8).collect()
+    })
+}
+
+/// xorshift64*
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        // The state can't be zero
+        Rng(seed | 1)
+    }
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+    /// A number in `0..n`
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
@@ -1,4 +1,12 @@
-use std::{io::Error as IoError, result::Result as StdResult};
+use crate::Position;
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Display},
+    mem::discriminant,
+    result::Result as StdResult,
+};
+#[cfg(feature = "std")]
+use std::{error::Error as StdError, io::Error as IoError};
 
 pub type Result<T> = StdResult<T, Error>;
 
@@ -6,14 +14,190 @@ pub type Result<T> = StdResult<T, Error>;
 pub enum Error {
     Stopped,
     OutOfBounds,
-    NoLoopStarted,
-    UnendedLoop,
-    CellPointerOverflow,
+    NoLoopStarted(Position),
+    /// Contains the positions of the `[` of every loop that was left open
+    UnendedLoop(Vec<Position>),
+    CellPointerOverflow(Position),
+    /// `,` was executed after the input had ended
+    EndOfInput(Position),
+    /// `,` was executed after reading as much input as the `InputLimit` allows
+    InputLimitReached(Position),
+    /// A loop ran more iterations in a row than the loop limit allows,
+    /// with the position of its `[`
+    LoopLimitReached(Position),
+    UninitializedRead {
+        cell: usize,
+        position: Position,
+    },
+    /// An I/O error from a `BfIo` implementation that doesn't use `std::io`
+    Io(&'static str),
+    #[cfg(feature = "std")]
     IoError(IoError),
 }
 
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Stopped => write!(f, "execution was stopped"),
+            Error::OutOfBounds => write!(f, "out of bounds"),
+            Error::NoLoopStarted(pos) => {
+                write!(f, "cannot end a loop when none has been started at {pos}")
+            }
+            Error::UnendedLoop(starts) => {
+                write!(f, "ended with unended loops")?;
+                for (i, pos) in starts.iter().enumerate() {
+                    let sep = if i == 0 { " started at" } else { "," };
+                    write!(f, "{sep} {pos}")?;
+                }
+                Ok(())
+            }
+            Error::CellPointerOverflow(pos) => write!(f, "cell pointer overflowed limit at {pos}"),
+            Error::EndOfInput(pos) => write!(f, "tried to read past the end of input at {pos}"),
+            Error::InputLimitReached(pos) => {
+                write!(f, "tried to read more input than allowed at {pos}")
+            }
+            Error::LoopLimitReached(pos) => {
+                write!(f, "the loop at {pos} ran more iterations than allowed")
+            }
+            Error::UninitializedRead { cell, position } => {
+                write!(f, "read uninitialized cell {cell} at {position}")
+            }
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            #[cfg(feature = "std")]
+            Error::IoError(e) => write!(f, "I/O error: {e}"),
+        }
+    }
+}
+
+impl Clone for Error {
+    /// I/O errors are cloned by their kind and message
+    fn clone(&self) -> Self {
+        match self {
+            Error::Stopped => Error::Stopped,
+            Error::OutOfBounds => Error::OutOfBounds,
+            Error::NoLoopStarted(pos) => Error::NoLoopStarted(*pos),
+            Error::UnendedLoop(starts) => Error::UnendedLoop(starts.clone()),
+            Error::CellPointerOverflow(pos) => Error::CellPointerOverflow(*pos),
+            Error::EndOfInput(pos) => Error::EndOfInput(*pos),
+            Error::InputLimitReached(pos) => Error::InputLimitReached(*pos),
+            Error::LoopLimitReached(pos) => Error::LoopLimitReached(*pos),
+            Error::UninitializedRead { cell, position } => Error::UninitializedRead {
+                cell: *cell,
+                position: *position,
+            },
+            Error::Io(e) => Error::Io(e),
+            #[cfg(feature = "std")]
+            Error::IoError(e) => Error::IoError(IoError::new(e.kind(), e.to_string())),
+        }
+    }
+}
+
+impl Error {
+    /// The source position of the instruction that caused the error, if known
+    pub fn position(&self) -> Option<Position> {
+        match *self {
+            Error::NoLoopStarted(pos)
+            | Error::CellPointerOverflow(pos)
+            | Error::EndOfInput(pos)
+            | Error::InputLimitReached(pos)
+            | Error::LoopLimitReached(pos) => Some(pos),
+            Error::UnendedLoop(ref starts) => starts.first().copied(),
+            Error::UninitializedRead { position, .. } => Some(position),
+            _ => None,
+        }
+    }
+    /// Replaces every source position in the error with `f` of it,
+    /// e.g. to point into the source a program was expanded from
+    #[must_use]
+    pub fn map_positions(self, mut f: impl FnMut(Position) -> Position) -> Self {
+        match self {
+            Error::NoLoopStarted(pos) => Error::NoLoopStarted(f(pos)),
+            Error::UnendedLoop(starts) => Error::UnendedLoop(starts.into_iter().map(f).collect()),
+            Error::CellPointerOverflow(pos) => Error::CellPointerOverflow(f(pos)),
+            Error::EndOfInput(pos) => Error::EndOfInput(f(pos)),
+            Error::InputLimitReached(pos) => Error::InputLimitReached(f(pos)),
+            Error::LoopLimitReached(pos) => Error::LoopLimitReached(f(pos)),
+            Error::UninitializedRead { cell, position } => Error::UninitializedRead {
+                cell,
+                position: f(position),
+            },
+            e => e,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl From<IoError> for Error {
     fn from(e: IoError) -> Self {
         Error::IoError(e)
     }
 }
+
+/// How many distinct warnings are kept, see `Warnings`
+pub const MAX_WARNINGS: usize = 100;
+
+/// A warning recorded while running and how many times it happened
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub error: Error,
+    pub count: usize,
+}
+
+/// The warnings recorded while running, see `State::take_warnings`.
+/// A warning of the same kind at the same position as an earlier one only counts that one again.
+#[derive(Debug, Clone, Default)]
+pub struct Warnings {
+    /// The distinct warnings in the order they first happened, at most `MAX_WARNINGS` of them
+    pub kept: Vec<Warning>,
+    /// How many warnings weren't kept because there were too many distinct ones before them
+    pub omitted: usize,
+}
+
+impl Warnings {
+    pub(crate) fn record(&mut self, error: Error) {
+        let key = (discriminant(&error), error.position());
+        let earlier = self
+            .kept
+            .iter()
+            .position(|w| (discriminant(&w.error), w.error.position()) == key);
+        match earlier {
+            Some(i) => self.kept[i].count += 1,
+            None if self.kept.len() < MAX_WARNINGS => self.kept.push(Warning { error, count: 1 }),
+            None => self.omitted += 1,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.kept.is_empty() && self.omitted == 0
+    }
+}
+
+/// An error from one of several sources run one after another by `State::run_all`
+#[derive(Debug, Clone)]
+pub struct StageError {
+    /// Index of the source that failed
+    pub stage: usize,
+    pub error: Error,
+}
+
+impl Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stage {}: {}", self.stage, self.error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for StageError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
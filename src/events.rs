@@ -0,0 +1,137 @@
+use std::{
+    num::Wrapping,
+    sync::mpsc::{sync_channel, Receiver, SyncSender},
+    thread::{self, JoinHandle},
+};
+
+use crate::{BfIo, Cell, ControlHandle, Error, Instruction, Program, Result, State};
+
+/// How many events can be waiting to be received before the program waits for the receiver
+const CAPACITY: usize = 1024;
+
+/// Something that happened while running a program with `EventStream`
+#[derive(Debug, Clone)]
+pub enum Event<V = u8> {
+    InstructionExecuted {
+        pc: usize,
+        instruction: Instruction,
+        cell_pointer: usize,
+    },
+    /// The current cell was changed by `+`, `-` or `,`
+    CellChanged {
+        cell: usize,
+        value: V,
+    },
+    Output(u8),
+    /// `,` is about to read from the input
+    InputRequested,
+    /// The program has been paused through its `ControlHandle`
+    Paused,
+    /// The program ended, successfully or not. This is always the last event.
+    Finished(Result<()>),
+}
+
+/// A program running on its own thread, sending an `Event` for everything it does
+///
+/// The program only runs as fast as the events are received.
+/// Dropping the receiver stops the program.
+pub struct EventStream<C: Cell = Wrapping<u8>> {
+    events: Receiver<Event<C::Value>>,
+    handle: ControlHandle,
+    thread: JoinHandle<State<C>>,
+}
+
+impl<C: Cell> EventStream<C>
+where
+    C::Value: Send,
+{
+    pub fn spawn<I>(program: Program, mut state: State<C>, io: I) -> Self
+    where
+        I: BfIo + Send + 'static,
+    {
+        let (sender, events) = sync_channel(CAPACITY);
+        let handle = state.control_handle();
+        state.start();
+        let thread = thread::spawn(move || {
+            let mut io = EventIo {
+                inner: io,
+                sender: sender.clone(),
+            };
+            let result = run(&program, &mut state, &mut io, &sender);
+            let _ = sender.send(Event::Finished(result));
+            state
+        });
+
+        EventStream {
+            events,
+            handle,
+            thread,
+        }
+    }
+    #[inline]
+    pub fn events(&self) -> &Receiver<Event<C::Value>> {
+        &self.events
+    }
+    #[inline]
+    pub fn control_handle(&self) -> &ControlHandle {
+        &self.handle
+    }
+    /// Waits for the program to end and returns its state.
+    /// Events that haven't been received yet are discarded.
+    pub fn join(self) -> State<C> {
+        drop(self.events);
+        self.thread.join().expect("event stream thread panicked")
+    }
+}
+
+fn run<C: Cell, I: BfIo>(
+    program: &Program,
+    state: &mut State<C>,
+    io: &mut I,
+    sender: &SyncSender<Event<C::Value>>,
+) -> Result<()> {
+    let send = |event| sender.send(event).map_err(|_| Error::Stopped);
+
+    let mut pc = 0;
+    while pc < program.len() {
+        if state.shared.is_paused() {
+            send(Event::Paused)?;
+            state.checkpoint()?;
+        }
+        let instruction = program.instructions()[pc];
+        let next = program.step(state, pc, io)?;
+        send(Event::InstructionExecuted {
+            pc,
+            instruction,
+            cell_pointer: state.cell_pointer,
+        })?;
+        if let Instruction::Incr | Instruction::Decr | Instruction::In = instruction {
+            send(Event::CellChanged {
+                cell: state.cell_pointer,
+                value: state.get_cur().value(),
+            })?;
+        }
+        pc = next;
+    }
+    Ok(())
+}
+
+struct EventIo<I, V> {
+    inner: I,
+    sender: SyncSender<Event<V>>,
+}
+
+impl<I: BfIo, V> BfIo for EventIo<I, V> {
+    fn read(&mut self) -> Result<Option<u8>> {
+        self.sender
+            .send(Event::InputRequested)
+            .map_err(|_| Error::Stopped)?;
+        self.inner.read()
+    }
+    fn write(&mut self, byte: u8) -> Result<()> {
+        self.inner.write(byte)?;
+        self.sender
+            .send(Event::Output(byte))
+            .map_err(|_| Error::Stopped)
+    }
+}
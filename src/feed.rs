@@ -0,0 +1,51 @@
+use core::mem::take;
+
+use crate::{run_from, BfIo, Cell, Error, Position, Result, RunReport, State};
+
+/// Runs source that arrives a piece at a time, like lines typed into a shell
+/// or a program coming in over the network
+///
+/// Every command is run as soon as it's fed, except for loops,
+/// which are run once they have been ended.
+/// Positions count from the start of the first piece, so errors point into the whole source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Feeder {
+    position: Position,
+}
+
+impl Feeder {
+    #[inline]
+    pub fn new() -> Self {
+        Feeder::default()
+    }
+    /// Where the next piece starts in the whole source
+    #[inline]
+    pub fn position(&self) -> Position {
+        self.position
+    }
+    /// Runs the next piece of source on `state`, which ends with `EndReason::AwaitingLoopEnd`
+    /// if it leaves loops to be ended by later pieces
+    pub fn feed<C: Cell, I: BfIo>(
+        &mut self,
+        src: &[u8],
+        state: &mut State<C>,
+        io: &mut I,
+    ) -> Result<RunReport> {
+        let start = self.position;
+        for &byte in src {
+            self.position.advance(byte);
+        }
+        run_from(src.iter().copied(), start, state, io)
+    }
+    /// Ends the source, failing with `Error::UnendedLoop` if loops are still waiting to be ended,
+    /// which are then dropped so that `state` can go on with another source
+    pub fn finish<C: Cell>(&mut self, state: &mut State<C>) -> Result<()> {
+        self.position = Position::START;
+        if state.loop_nesting == 0 {
+            return Ok(());
+        }
+        state.loop_nesting = 0;
+        state.ongoing_loops.clear();
+        Err(Error::UnendedLoop(take(&mut state.loop_starts)))
+    }
+}
@@ -0,0 +1,230 @@
+//! C interface, enabled by the `ffi` feature
+//!
+//! Build the shared library from the `brainfuck-ffi` crate with `cargo build -p brainfuck-ffi`
+//! and include `include/brainfuck.h`.
+
+use std::{
+    ffi::{c_int, c_void},
+    ptr, slice,
+};
+
+use crate::{BfIo, Cell, CellsLimit, Eof, Error, Result, State};
+
+pub const BF_OK: c_int = 0;
+pub const BF_STOPPED: c_int = 1;
+pub const BF_NO_LOOP_STARTED: c_int = 2;
+pub const BF_UNENDED_LOOP: c_int = 3;
+pub const BF_POINTER_OVERFLOW: c_int = 4;
+pub const BF_END_OF_INPUT: c_int = 5;
+pub const BF_UNINITIALIZED_READ: c_int = 6;
+pub const BF_IO_ERROR: c_int = 7;
+pub const BF_INVALID_ARGUMENT: c_int = 8;
+pub const BF_INPUT_LIMIT: c_int = 9;
+pub const BF_LOOP_LIMIT: c_int = 10;
+
+pub const BF_EOF_ERROR: c_int = 0;
+pub const BF_EOF_ZERO: c_int = 1;
+pub const BF_EOF_MINUS_ONE: c_int = 2;
+pub const BF_EOF_UNCHANGED: c_int = 3;
+pub const BF_EOF_TERMINATE: c_int = 4;
+
+/// Returns the next input byte, -1 at the end of input or -2 on errors
+pub type BfReadFn = extern "C" fn(user_data: *mut c_void) -> c_int;
+/// Returns 0 on success
+pub type BfWriteFn = extern "C" fn(user_data: *mut c_void, byte: u8) -> c_int;
+
+pub struct BfInterpreter {
+    state: State,
+    io: CallbackIo,
+}
+
+struct CallbackIo {
+    read: Option<BfReadFn>,
+    write: Option<BfWriteFn>,
+    user_data: *mut c_void,
+}
+
+impl BfIo for CallbackIo {
+    fn read(&mut self) -> Result<Option<u8>> {
+        match self.read.map(|read| read(self.user_data)) {
+            None | Some(-1) => Ok(None),
+            Some(byte @ 0..=255) => Ok(Some(byte as u8)),
+            Some(_) => Err(Error::Io("read callback failed")),
+        }
+    }
+    fn write(&mut self, byte: u8) -> Result<()> {
+        match self.write.map(|write| write(self.user_data, byte)) {
+            None | Some(0) => Ok(()),
+            Some(_) => Err(Error::Io("write callback failed")),
+        }
+    }
+}
+
+fn status(result: Result<()>) -> c_int {
+    match result {
+        Ok(()) => BF_OK,
+        Err(Error::Stopped) => BF_STOPPED,
+        Err(Error::NoLoopStarted(_)) => BF_NO_LOOP_STARTED,
+        Err(Error::UnendedLoop(_)) => BF_UNENDED_LOOP,
+        Err(Error::CellPointerOverflow(_) | Error::OutOfBounds) => BF_POINTER_OVERFLOW,
+        Err(Error::EndOfInput(_)) => BF_END_OF_INPUT,
+        Err(Error::InputLimitReached(_)) => BF_INPUT_LIMIT,
+        Err(Error::LoopLimitReached(_)) => BF_LOOP_LIMIT,
+        Err(Error::UninitializedRead { .. }) => BF_UNINITIALIZED_READ,
+        Err(Error::Io(_) | Error::IoError(_)) => BF_IO_ERROR,
+    }
+}
+
+/// Creates an interpreter with `cell_limit` cells, 0 meaning no limit.
+/// It has no input and discards its output until `bf_interpreter_set_io` is called.
+///
+/// The interpreter has to be freed with `bf_interpreter_free`.
+#[no_mangle]
+pub extern "C" fn bf_interpreter_new(cell_limit: usize, wrap: bool) -> *mut BfInterpreter {
+    let limit = std::num::NonZeroUsize::new(cell_limit).map(|l| (l, wrap));
+    Box::into_raw(Box::new(BfInterpreter {
+        state: State::new(CellsLimit::new(limit)),
+        io: CallbackIo {
+            read: None,
+            write: None,
+            user_data: ptr::null_mut(),
+        },
+    }))
+}
+
+/// # Safety
+///
+/// `interpreter` must be null or have been created by `bf_interpreter_new`
+/// and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn bf_interpreter_free(interpreter: *mut BfInterpreter) {
+    if !interpreter.is_null() {
+        drop(Box::from_raw(interpreter));
+    }
+}
+
+/// Sets the callbacks used for `,` and `.`. Either can be null.
+/// `user_data` is passed to both callbacks as is.
+///
+/// # Safety
+///
+/// `interpreter` must be a valid interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn bf_interpreter_set_io(
+    interpreter: *mut BfInterpreter,
+    read: Option<BfReadFn>,
+    write: Option<BfWriteFn>,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(interpreter) = interpreter.as_mut() else {
+        return BF_INVALID_ARGUMENT;
+    };
+    interpreter.io = CallbackIo {
+        read,
+        write,
+        user_data,
+    };
+    BF_OK
+}
+
+/// Sets what `,` does once the input has ended to one of the `BF_EOF_` modes,
+/// `BF_EOF_ERROR` by default
+///
+/// # Safety
+///
+/// `interpreter` must be a valid interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn bf_interpreter_set_eof(
+    interpreter: *mut BfInterpreter,
+    eof: c_int,
+) -> c_int {
+    let Some(interpreter) = interpreter.as_mut() else {
+        return BF_INVALID_ARGUMENT;
+    };
+    let eof = match eof {
+        BF_EOF_ERROR => Eof::Error,
+        BF_EOF_ZERO => Eof::Zero,
+        BF_EOF_MINUS_ONE => Eof::MinusOne,
+        BF_EOF_UNCHANGED => Eof::Unchanged,
+        BF_EOF_TERMINATE => Eof::Terminate,
+        _ => return BF_INVALID_ARGUMENT,
+    };
+    interpreter.state.set_eof(eof);
+    BF_OK
+}
+
+/// Runs `len` bytes of source. Loops may be left open and ended by later calls.
+///
+/// # Safety
+///
+/// `interpreter` must be a valid interpreter and `src` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bf_interpreter_run(
+    interpreter: *mut BfInterpreter,
+    src: *const u8,
+    len: usize,
+) -> c_int {
+    let Some(interpreter) = interpreter.as_mut() else {
+        return BF_INVALID_ARGUMENT;
+    };
+    if src.is_null() && len != 0 {
+        return BF_INVALID_ARGUMENT;
+    }
+    let src = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(src, len)
+    };
+    status(
+        crate::run_bytes(
+            src.iter().copied(),
+            &mut interpreter.state,
+            &mut interpreter.io,
+        )
+        .map(drop),
+    )
+}
+
+/// Checks that every loop that has been started has also been ended
+///
+/// # Safety
+///
+/// `interpreter` must be a valid interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn bf_interpreter_finish(interpreter: *const BfInterpreter) -> c_int {
+    let Some(interpreter) = interpreter.as_ref() else {
+        return BF_INVALID_ARGUMENT;
+    };
+    if interpreter.state.loop_starts().is_empty() {
+        BF_OK
+    } else {
+        BF_UNENDED_LOOP
+    }
+}
+
+/// The position of the cell pointer
+///
+/// # Safety
+///
+/// `interpreter` must be a valid interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn bf_interpreter_pointer(interpreter: *const BfInterpreter) -> usize {
+    interpreter
+        .as_ref()
+        .map_or(0, |interpreter| interpreter.state.cell_pointer)
+}
+
+/// The value of the cell at `index`
+///
+/// # Safety
+///
+/// `interpreter` must be a valid interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn bf_interpreter_cell(
+    interpreter: *const BfInterpreter,
+    index: usize,
+) -> u8 {
+    interpreter
+        .as_ref()
+        .map_or(0, |interpreter| interpreter.state.get(index).to_byte())
+}
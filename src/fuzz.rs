@@ -0,0 +1,118 @@
+//! A harness for fuzzing the engines for panics and for disagreeing with each other
+//!
+//! The bytes a fuzzer comes up with are made into a `FuzzCase` that always parses,
+//! which is run with a bounded amount of fuel and cells.
+//! With `cargo fuzz` a target only has to pass its bytes on:
+//!
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| brainfuck::fuzz::run(data));
+//! ```
+//!
+//! With the `arbitrary` feature, fuzzers that generate structured data can generate
+//! `Command`s and `Program`s instead.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::num::NonZeroUsize;
+
+pub use crate::differential::{Divergence, Ending};
+use crate::{differential, CellsLimit, Eof, State};
+
+/// The most instructions a case may execute before it's given up on
+pub const FUEL: u64 = 100_000;
+/// The most cells a case with a limited tape may use
+pub const MAX_CELLS: usize = 64;
+
+/// The commands that the bytes of a program are made into
+const COMMANDS: [u8; 8] = *b"+-<>,.[]";
+
+/// A program with the input and configuration it runs with, made from arbitrary bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzCase {
+    /// Only commands with every loop ended
+    pub program: Vec<u8>,
+    pub input: Vec<u8>,
+    pub eof: Eof,
+    /// Either no limit, or at most `MAX_CELLS` that the pointer wraps around or stops at
+    pub cells: CellsLimit,
+}
+
+impl FuzzCase {
+    /// Makes a case from any bytes
+    ///
+    /// The lowest three bits of the first byte pick the EOF mode
+    /// and the two above them whether the tape is limited and wraps, the second how many cells a limited tape has,
+    /// and the bytes up to the next 0 are commands, after which comes the input.
+    /// `]` without a loop to end is left out and loops that aren't ended are ended at the end.
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let (config, rest) = data.split_first().map_or((0, data), |(&b, rest)| (b, rest));
+        let (size, rest) = rest.split_first().map_or((0, rest), |(&b, rest)| (b, rest));
+        let (commands, input) = match rest.iter().position(|&b| b == 0) {
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => (rest, &[][..]),
+        };
+
+        let program = balanced(
+            commands
+                .iter()
+                .map(|&b| COMMANDS[b as usize % COMMANDS.len()]),
+        );
+
+        let eof = match config & 0b111 {
+            0 => Eof::Error,
+            1 => Eof::Zero,
+            2 => Eof::MinusOne,
+            3 => Eof::Unchanged,
+            _ => Eof::Terminate,
+        };
+        let size = NonZeroUsize::new(1 + size as usize % MAX_CELLS).unwrap();
+        let cells = CellsLimit::new(match (config >> 3) & 0b11 {
+            0 => Some((size, true)),
+            1 => Some((size, false)),
+            _ => None,
+        });
+        FuzzCase {
+            program,
+            input: input.to_vec(),
+            eof,
+            cells,
+        }
+    }
+
+    /// Runs the case on every engine, checking that they end the same way as the reference,
+    /// see `differential::check`
+    pub fn check(&self) -> core::result::Result<(), Box<Divergence>> {
+        differential::check(&self.program, &self.state(), &self.input, FUEL).map(drop)
+    }
+
+    fn state(&self) -> State {
+        let mut state = State::new(self.cells);
+        state.set_eof(self.eof);
+        state
+    }
+}
+
+/// The `commands` with every loop ended, leaving out `]` without a loop to end
+/// and ending loops that aren't ended at the end
+pub(crate) fn balanced(commands: impl IntoIterator<Item = u8>) -> Vec<u8> {
+    let mut program = Vec::new();
+    let mut depth = 0usize;
+    for command in commands {
+        match command {
+            b'[' => depth += 1,
+            b']' if depth == 0 => continue,
+            b']' => depth -= 1,
+            _ => (),
+        }
+        program.push(command);
+    }
+    program.extend(core::iter::repeat_n(b']', depth));
+    program
+}
+
+/// Makes a case from `data` and checks it, panicking if the engines disagree
+pub fn run(data: &[u8]) {
+    let case = FuzzCase::from_bytes(data);
+    if let Err(divergence) = case.check() {
+        panic!("{case:?}: {divergence}");
+    }
+}
@@ -0,0 +1,123 @@
+use alloc::vec::Vec;
+
+/// Cells used for holding characters, to the right of the cell used for loop counters
+pub(crate) const CELLS: usize = 4;
+const MAX_FACTOR: u8 = 16;
+
+/// Generates a program that prints `text`
+///
+/// The program keeps a few cells with recently printed bytes around, changing whichever
+/// is closest to the next byte, and uses multiplication loops for large changes.
+pub fn text(text: &[u8]) -> Vec<u8> {
+    text_ending_at(text).0
+}
+
+/// Like `text`, but also returns the cell the pointer ends at
+pub(crate) fn text_ending_at(text: &[u8]) -> (Vec<u8>, usize) {
+    let mut out = Vec::new();
+    let mut values = [0u8; CELLS];
+    let mut pointer = 0;
+
+    for &byte in text {
+        let best = (1..=CELLS)
+            .map(|cell| {
+                let mut code = Vec::new();
+                change(&mut code, pointer, cell, values[cell - 1], byte);
+                (cell, code)
+            })
+            .min_by_key(|(_, code)| code.len());
+        if let Some((cell, code)) = best {
+            out.extend(code);
+            out.push(b'.');
+            values[cell - 1] = byte;
+            pointer = cell;
+        }
+    }
+
+    (out, pointer)
+}
+
+/// Generates a snippet that sets the current cell from 0 to `n`, for 8-bit wrapping cells
+///
+/// Large values are made with a multiplication loop that uses the cell to the right
+/// as its counter, which has to be 0 and is left at 0.
+/// Every factorization and either direction of wrapping is tried,
+/// so the snippet is the shortest of these forms.
+/// The pointer ends where it started.
+pub fn constant(n: u8) -> Vec<u8> {
+    let cost = |value: u8| difference(0, value).unsigned_abs() as usize;
+    let mut best = (cost(n), None);
+    for counter in 1..=u8::MAX {
+        for step in 1..=u8::MAX {
+            let rest = n.wrapping_sub(counter.wrapping_mul(step));
+            // `>`, `[<`, `>-]` and `<` around the changes
+            let len = 7 + cost(counter) + cost(step) + cost(rest);
+            if len < best.0 {
+                best = (len, Some((counter, step, rest)));
+            }
+        }
+    }
+
+    let mut code = Vec::with_capacity(best.0);
+    match best.1 {
+        None => add(&mut code, difference(0, n)),
+        Some((counter, step, rest)) => {
+            code.push(b'>');
+            add(&mut code, difference(0, counter));
+            code.extend(b"[<");
+            add(&mut code, difference(0, step));
+            code.extend(b">-]<");
+            add(&mut code, difference(0, rest));
+        }
+    }
+    code
+}
+
+fn move_pointer(code: &mut Vec<u8>, from: usize, to: usize) {
+    if to > from {
+        code.extend(core::iter::repeat_n(b'>', to - from));
+    } else {
+        code.extend(core::iter::repeat_n(b'<', from - to));
+    }
+}
+
+/// The shortest change either way around, as cells wrap
+fn difference(from: u8, to: u8) -> i16 {
+    to.wrapping_sub(from) as i8 as i16
+}
+
+fn add(code: &mut Vec<u8>, n: i16) {
+    let cmd = if n < 0 { b'-' } else { b'+' };
+    code.extend(core::iter::repeat_n(cmd, n.unsigned_abs() as usize));
+}
+
+/// Moves the pointer from `pointer` to `cell` and changes it from `from` to `to`,
+/// using cell 0 as a loop counter
+fn change(code: &mut Vec<u8>, pointer: usize, cell: usize, from: u8, to: u8) {
+    let d = difference(from, to);
+    let mut best = Vec::new();
+    move_pointer(&mut best, pointer, cell);
+    add(&mut best, d);
+
+    for factor in 2..=MAX_FACTOR as i16 {
+        let times = d / factor;
+        if times == 0 {
+            continue;
+        }
+        let mut candidate = Vec::new();
+        move_pointer(&mut candidate, pointer, 0);
+        add(&mut candidate, factor);
+        candidate.push(b'[');
+        move_pointer(&mut candidate, 0, cell);
+        add(&mut candidate, times);
+        move_pointer(&mut candidate, cell, 0);
+        candidate.extend(b"-]");
+        move_pointer(&mut candidate, 0, cell);
+        add(&mut candidate, d - factor * times);
+        if candidate.len() < best.len() {
+            best = candidate;
+        }
+    }
+
+    code.extend(best);
+}
@@ -0,0 +1,340 @@
+use std::{
+    io::{empty, sink, Empty, Read, Sink, Write},
+    marker::PhantomData,
+    num::{NonZeroU64, NonZeroUsize, Wrapping},
+};
+
+use crate::{
+    bytecode::{Bytecode, OptLevel},
+    run_program, run_slice, run_with_state,
+    tiered::run_tiered_with,
+    Cell, CellsIntoIter, CellsLimit, Eof, Feeder, Flush, InOuter, InputLimit, Newline, Program,
+    Result, RunReport, State, UninitReads,
+};
+
+/// Which engine `Interpreter::run` and `Interpreter::run_slice` run a source with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Runs commands as they're read, see `run_slice`
+    #[default]
+    Stream,
+    /// Parses the whole source first and steps through it, see `run_program`
+    Parsed,
+    /// Compiles the source to bytecode first, see `Bytecode`
+    Bytecode,
+    /// Compiles loops once they have run `threshold` times, see `run_tiered`
+    Tiered { threshold: u32 },
+}
+
+/// A `State` together with the I/O it runs against
+pub struct Interpreter<W: Write, R: Read, C: Cell = Wrapping<u8>> {
+    state: State<C>,
+    io: InOuter<W, R>,
+    feeder: Feeder,
+    engine: Engine,
+    opt_level: OptLevel,
+}
+
+impl Interpreter<Sink, Empty> {
+    #[inline]
+    pub fn builder() -> InterpreterBuilder<Sink, Empty> {
+        InterpreterBuilder::default()
+    }
+}
+
+impl<W: Write, R: Read, C: Cell> Interpreter<W, R, C> {
+    /// Runs `src` with the engine it was built with and writes out all of its output,
+    /// even if it fails.
+    /// Only `Engine::Stream` runs it as it's read, the others read all of it first.
+    pub fn run<S: Read>(&mut self, mut src: S) -> Result<RunReport> {
+        if self.engine != Engine::Stream {
+            let mut buf = Vec::new();
+            src.read_to_end(&mut buf)?;
+            return self.run_slice(&buf);
+        }
+        let result = run_with_state(src, &mut self.state, &mut self.io);
+        let flushed = self.io.flush();
+        let report = result?;
+        flushed?;
+        Ok(report)
+    }
+    /// Runs `src` from memory with the engine it was built with
+    /// and writes out all of its output, even if it fails
+    pub fn run_slice(&mut self, src: &[u8]) -> Result<RunReport> {
+        let (state, io, level) = (&mut self.state, &mut self.io, self.opt_level);
+        let result = match self.engine {
+            Engine::Stream => run_slice(src, state, io),
+            Engine::Parsed => {
+                Program::parse(src).and_then(|program| run_program(&program, state, io))
+            }
+            Engine::Bytecode => Program::parse(src)
+                .and_then(|program| Bytecode::compile_with(&program, level).run(state, io)),
+            Engine::Tiered { threshold } => Program::parse(src)
+                .and_then(|program| run_tiered_with(&program, state, io, threshold, level)),
+        };
+        let flushed = self.io.flush();
+        let report = result?;
+        flushed?;
+        Ok(report)
+    }
+    /// Runs the next piece of a source arriving a piece at a time, see `Feeder`,
+    /// and writes out all of its output, even if it fails
+    pub fn feed(&mut self, src: &[u8]) -> Result<RunReport> {
+        let result = self.feeder.feed(src, &mut self.state, &mut self.io);
+        let flushed = self.io.flush();
+        let report = result?;
+        flushed?;
+        Ok(report)
+    }
+    /// Ends the source given to `feed`, failing if it left loops unended
+    pub fn finish(&mut self) -> Result<()> {
+        self.feeder.finish(&mut self.state)
+    }
+    /// Runs `program` with `run_tiered_with` at the optimization level it was built with
+    /// and writes out all of its output, even if it fails
+    pub fn run_tiered(&mut self, program: &Program, threshold: u32) -> Result<RunReport> {
+        let (state, io) = (&mut self.state, &mut self.io);
+        let result = run_tiered_with(program, state, io, threshold, self.opt_level);
+        let flushed = self.io.flush();
+        let report = result?;
+        flushed?;
+        Ok(report)
+    }
+    /// Runs `bytecode` and writes out all of its output, even if it fails
+    pub fn run_bytecode(&mut self, bytecode: &Bytecode) -> Result<RunReport> {
+        let result = bytecode.run(&mut self.state, &mut self.io);
+        let flushed = self.io.flush();
+        let report = result?;
+        flushed?;
+        Ok(report)
+    }
+    #[inline]
+    pub fn state(&self) -> &State<C> {
+        &self.state
+    }
+    #[inline]
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+    #[inline]
+    pub fn opt_level(&self) -> OptLevel {
+        self.opt_level
+    }
+    #[inline]
+    pub fn state_mut(&mut self) -> &mut State<C> {
+        &mut self.state
+    }
+    #[inline]
+    pub fn parts_mut(&mut self) -> (&mut State<C>, &mut InOuter<W, R>) {
+        (&mut self.state, &mut self.io)
+    }
+    /// See `State::evaluate`
+    pub fn evaluate(self) -> Result<CellsIntoIter<C>> {
+        self.state.evaluate()
+    }
+    pub fn into_parts(self) -> (State<C>, InOuter<W, R>) {
+        (self.state, self.io)
+    }
+}
+
+/// Configures an `Interpreter`, created by `Interpreter::builder`
+///
+/// Without any configuration the tape is unlimited, reading past the end
+/// of input is an error, and the interpreter has no input and discards its output.
+#[must_use]
+pub struct InterpreterBuilder<W, R, C = Wrapping<u8>> {
+    limit: Option<NonZeroUsize>,
+    capacity: usize,
+    wrap: bool,
+    eof: Eof,
+    input_limit: Option<InputLimit>,
+    loop_limit: Option<NonZeroU64>,
+    uninit_reads: UninitReads,
+    recover: bool,
+    start_cell: usize,
+    flush: Flush,
+    newlines: (Newline, Newline),
+    engine: Engine,
+    opt_level: OptLevel,
+    output: W,
+    input: R,
+    cell: PhantomData<fn() -> C>,
+}
+
+impl Default for InterpreterBuilder<Sink, Empty> {
+    fn default() -> Self {
+        InterpreterBuilder {
+            limit: None,
+            capacity: 0,
+            wrap: false,
+            eof: Eof::default(),
+            input_limit: None,
+            loop_limit: None,
+            uninit_reads: UninitReads::default(),
+            recover: false,
+            start_cell: 0,
+            flush: Flush::default(),
+            newlines: Default::default(),
+            engine: Engine::default(),
+            opt_level: OptLevel::default(),
+            output: sink(),
+            input: empty(),
+            cell: PhantomData,
+        }
+    }
+}
+
+impl<W: Write, R: Read, C: Cell> InterpreterBuilder<W, R, C> {
+    /// The amount of cells the program can use, 0 meaning no limit
+    pub fn cell_limit(mut self, limit: usize) -> Self {
+        self.limit = NonZeroUsize::new(limit);
+        self
+    }
+    /// The amount of cells to make room for up front, see `State::with_capacity`
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+    /// Whether the cell pointer wraps around at the cell limit.
+    /// Without a `cell_limit` there is nothing to wrap around, so it has no effect.
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+    pub fn eof(mut self, eof: Eof) -> Self {
+        self.eof = eof;
+        self
+    }
+    /// See `State::set_input_limit`
+    pub fn input_limit(mut self, input_limit: Option<InputLimit>) -> Self {
+        self.input_limit = input_limit;
+        self
+    }
+    /// See `State::set_loop_limit`
+    pub fn loop_limit(mut self, loop_limit: Option<NonZeroU64>) -> Self {
+        self.loop_limit = loop_limit;
+        self
+    }
+    pub fn uninit_reads(mut self, uninit_reads: UninitReads) -> Self {
+        self.uninit_reads = uninit_reads;
+        self
+    }
+    /// See `State::set_recover`
+    pub fn recover(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+    /// The cell the pointer starts at, see `State::set_start_cell`.
+    /// Building fails with `Error::OutOfBounds` if it's past the cell limit.
+    pub fn start_cell(mut self, cell: usize) -> Self {
+        self.start_cell = cell;
+        self
+    }
+    /// When output is written, after every line by default
+    pub fn flush(mut self, flush: Flush) -> Self {
+        self.flush = flush;
+        self
+    }
+    /// The newlines of the input and the output, see `InOuter::set_newlines`
+    pub fn newlines(mut self, input: Newline, output: Newline) -> Self {
+        self.newlines = (input, output);
+        self
+    }
+    /// The engine sources are run with, `Engine::Stream` by default
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+    /// How much the `Engine::Bytecode` and `Engine::Tiered` engines optimize what they compile,
+    /// `OptLevel::Full` by default
+    pub fn opt_level(mut self, opt_level: OptLevel) -> Self {
+        self.opt_level = opt_level;
+        self
+    }
+    /// The type of the cells on the tape, `Wrapping<u8>` by default
+    pub fn cell_type<C2: Cell>(self) -> InterpreterBuilder<W, R, C2> {
+        InterpreterBuilder {
+            limit: self.limit,
+            capacity: self.capacity,
+            wrap: self.wrap,
+            eof: self.eof,
+            input_limit: self.input_limit,
+            loop_limit: self.loop_limit,
+            uninit_reads: self.uninit_reads,
+            recover: self.recover,
+            start_cell: self.start_cell,
+            flush: self.flush,
+            newlines: self.newlines,
+            engine: self.engine,
+            opt_level: self.opt_level,
+            output: self.output,
+            input: self.input,
+            cell: PhantomData,
+        }
+    }
+    pub fn output<W2: Write>(self, output: W2) -> InterpreterBuilder<W2, R, C> {
+        InterpreterBuilder {
+            limit: self.limit,
+            capacity: self.capacity,
+            wrap: self.wrap,
+            eof: self.eof,
+            input_limit: self.input_limit,
+            loop_limit: self.loop_limit,
+            uninit_reads: self.uninit_reads,
+            recover: self.recover,
+            start_cell: self.start_cell,
+            flush: self.flush,
+            newlines: self.newlines,
+            engine: self.engine,
+            opt_level: self.opt_level,
+            output,
+            input: self.input,
+            cell: PhantomData,
+        }
+    }
+    pub fn input<R2: Read>(self, input: R2) -> InterpreterBuilder<W, R2, C> {
+        InterpreterBuilder {
+            limit: self.limit,
+            capacity: self.capacity,
+            wrap: self.wrap,
+            eof: self.eof,
+            input_limit: self.input_limit,
+            loop_limit: self.loop_limit,
+            uninit_reads: self.uninit_reads,
+            recover: self.recover,
+            start_cell: self.start_cell,
+            flush: self.flush,
+            newlines: self.newlines,
+            engine: self.engine,
+            opt_level: self.opt_level,
+            output: self.output,
+            input,
+            cell: PhantomData,
+        }
+    }
+    pub fn build_state(&self) -> Result<State<C>> {
+        let mut state = State::with_limit(CellsLimit::new(self.limit.map(|l| (l, self.wrap))));
+        state.set_eof(self.eof);
+        state.set_input_limit(self.input_limit);
+        state.set_loop_limit(self.loop_limit);
+        state.set_uninit_reads(self.uninit_reads);
+        state.set_recover(self.recover);
+        state.set_start_cell(self.start_cell)?;
+        // After setting how uninitialized reads are checked, so their tracking is reserved too
+        state.reserve_cells(self.capacity);
+        Ok(state)
+    }
+    pub fn build(self) -> Result<Interpreter<W, R, C>> {
+        let state = self.build_state()?;
+        let mut io = InOuter::new(self.output, self.input);
+        io.set_flush(self.flush);
+        io.set_newlines(self.newlines.0, self.newlines.1);
+        Ok(Interpreter {
+            state,
+            io,
+            feeder: Feeder::new(),
+            engine: self.engine,
+            opt_level: self.opt_level,
+        })
+    }
+}
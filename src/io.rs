@@ -0,0 +1,170 @@
+use crate::Result;
+
+/// The input and output a program runs against
+///
+/// This is all the interpreter needs from its environment, so it can be
+/// implemented without `std`.
+pub trait BfIo {
+    /// Reads the next byte of input, `None` meaning the input has ended
+    fn read(&mut self) -> Result<Option<u8>>;
+    fn write(&mut self, byte: u8) -> Result<()>;
+}
+
+impl<T: BfIo + ?Sized> BfIo for &mut T {
+    #[inline]
+    fn read(&mut self) -> Result<Option<u8>> {
+        (**self).read()
+    }
+    #[inline]
+    fn write(&mut self, byte: u8) -> Result<()> {
+        (**self).write(byte)
+    }
+}
+
+/// I/O made from two closures, one called by `,` and one called by `.`
+pub struct FnIo<F, G> {
+    input: F,
+    output: G,
+}
+
+impl<F, G> FnIo<F, G>
+where
+    F: FnMut() -> Option<u8>,
+    G: FnMut(u8),
+{
+    /// `input` returning `None` means the input has ended
+    #[inline]
+    pub fn new(input: F, output: G) -> Self {
+        FnIo { input, output }
+    }
+    #[inline]
+    pub fn into_inner(self) -> (F, G) {
+        (self.input, self.output)
+    }
+}
+
+impl<F, G> BfIo for FnIo<F, G>
+where
+    F: FnMut() -> Option<u8>,
+    G: FnMut(u8),
+{
+    #[inline]
+    fn read(&mut self) -> Result<Option<u8>> {
+        Ok((self.input)())
+    }
+    #[inline]
+    fn write(&mut self, byte: u8) -> Result<()> {
+        (self.output)(byte);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+pub use self::std_io::{Flush, InOuter, Newline};
+
+#[cfg(feature = "std")]
+mod std_io {
+    use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+
+    use super::BfIo;
+    use crate::Result;
+
+    /// When buffered output is written to the underlying writer
+    ///
+    /// Output is always written before input is read, so that prompts show up.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Flush {
+        /// After every byte
+        Byte,
+        /// After every newline
+        #[default]
+        Line,
+        /// Only when the buffer is full or the program is done
+        Full,
+    }
+
+    /// The newlines used outside the program, which only reads and prints `\n`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Newline {
+        /// `\n`, passed through as it is
+        #[default]
+        Lf,
+        /// `\r\n`, read as `\n` from input and printed for `\n` in output
+        Crlf,
+    }
+
+    pub struct InOuter<W: Write, R: Read> {
+        o: BufWriter<W>,
+        i: BufReader<R>,
+        flush: Flush,
+        input_newline: Newline,
+        output_newline: Newline,
+    }
+
+    impl<W: Write, R: Read> InOuter<W, R> {
+        pub fn new(o: W, i: R) -> Self {
+            InOuter {
+                o: BufWriter::new(o),
+                i: BufReader::new(i),
+                flush: Flush::default(),
+                input_newline: Newline::default(),
+                output_newline: Newline::default(),
+            }
+        }
+        #[inline]
+        pub fn set_flush(&mut self, flush: Flush) {
+            self.flush = flush;
+        }
+        /// The newlines of the input and the output, `\n` for both by default
+        #[inline]
+        pub fn set_newlines(&mut self, input: Newline, output: Newline) {
+            self.input_newline = input;
+            self.output_newline = output;
+        }
+        /// Writes all buffered output to the underlying writer
+        pub fn flush(&mut self) -> Result<()> {
+            self.o.flush()?;
+            Ok(())
+        }
+        /// Fails if the buffered output couldn't be written
+        pub fn extract(self) -> Result<(W, R)> {
+            let InOuter { i, o, .. } = self;
+            let o = o.into_inner().map_err(|e| e.into_error())?;
+            Ok((o, i.into_inner()))
+        }
+    }
+
+    impl<W: Write, R: Read> BfIo for InOuter<W, R> {
+        fn read(&mut self) -> Result<Option<u8>> {
+            self.o.flush()?;
+            let mut byte = [0];
+            match self.i.read_exact(&mut byte) {
+                Ok(()) => (),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+            // A lone `\r` is kept, since it isn't a newline
+            if byte[0] == b'\r'
+                && self.input_newline == Newline::Crlf
+                && self.i.fill_buf()?.first() == Some(&b'\n')
+            {
+                self.i.consume(1);
+                byte[0] = b'\n';
+            }
+            Ok(Some(byte[0]))
+        }
+        #[inline]
+        fn write(&mut self, byte: u8) -> Result<()> {
+            if byte == b'\n' && self.output_newline == Newline::Crlf {
+                self.o.write_all(b"\r")?;
+            }
+            self.o.write_all(&[byte])?;
+            match self.flush {
+                Flush::Byte => self.o.flush()?,
+                Flush::Line if byte == b'\n' => self.o.flush()?,
+                _ => (),
+            }
+            Ok(())
+        }
+    }
+}
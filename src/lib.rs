@@ -1,22 +1,97 @@
 #![warn(clippy::all)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use std::{
+extern crate alloc;
+
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use core::{
     default::Default,
-    fmt::{self, Debug},
-    io::{BufReader, Read, Write},
-    num::{NonZeroUsize, Wrapping},
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
+    fmt::{self, Debug, Display},
+    mem::take,
+    num::{NonZeroU64, NonZeroUsize, Wrapping},
 };
+#[cfg(feature = "std")]
+use std::io::Read;
 
 use self::Command::*;
 
+pub mod analysis;
+pub mod asm;
+#[cfg(any(feature = "wasm", feature = "python"))]
+mod bindings;
+pub mod bytecode;
+mod cell;
+pub mod conformance;
+mod control;
+pub mod diagnostic;
+pub mod dialect;
+pub mod differential;
+mod dump;
+pub mod equivalence;
 mod err;
-pub use crate::err::{Error, Result};
+// Threads can't be spawned on WASI
+#[cfg(all(feature = "std", not(target_os = "wasi")))]
+mod events;
+mod feed;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fuzz;
+pub mod generate;
+#[cfg(feature = "std")]
+mod interpreter;
+mod io;
+pub mod lint;
+mod metrics;
+pub mod minify;
+pub mod obfuscate;
+#[cfg(all(feature = "std", not(target_os = "wasi")))]
+pub mod pool;
+mod pos;
+pub mod preprocess;
+#[cfg(all(feature = "std", not(target_os = "wasi")))]
+mod process;
+mod program;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "render")]
+pub mod render;
+mod report;
+pub mod sandbox;
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+pub mod telemetry;
+pub mod tiered;
+#[cfg(feature = "std")]
+mod usage;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub use crate::cell::Cell;
+use crate::control::Shared;
+pub use crate::control::{ControlHandle, Stopper};
+pub use crate::dump::{Layout, TapeDump};
+pub use crate::err::{Error, Result, StageError, Warning, Warnings, MAX_WARNINGS};
+#[cfg(all(feature = "std", not(target_os = "wasi")))]
+pub use crate::events::{Event, EventStream};
+pub use crate::feed::Feeder;
+#[cfg(feature = "std")]
+pub use crate::interpreter::{Engine, Interpreter, InterpreterBuilder};
+pub use crate::io::{BfIo, FnIo};
+#[cfg(feature = "std")]
+pub use crate::io::{Flush, InOuter, Newline};
+pub use crate::metrics::Metrics;
+pub use crate::pos::Position;
+#[cfg(all(feature = "std", not(target_os = "wasi")))]
+pub use crate::process::{BfProcess, Exit, ProcessStdin, ProcessStdout};
+pub use crate::program::{run_program, Instruction, Outputs, Program};
+pub use crate::report::{EndReason, RunReport};
+#[cfg(feature = "std")]
+pub use crate::usage::{Meter, ResourceUsage};
+/// Runs a program at compile time, expanding to its output
+#[cfg(feature = "macros")]
+pub use brainfuck_macros::brainfuck;
 
 #[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Command {
     PtrIncr,
@@ -62,6 +137,18 @@ impl Command {
             _ => return None,
         })
     }
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Incr => b'+',
+            Decr => b'-',
+            PtrIncr => b'>',
+            PtrDecr => b'<',
+            Out => b'.',
+            In => b',',
+            LoopBegin => b'[',
+            LoopEnd => b']',
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -78,7 +165,7 @@ impl CellsLimit {
         self.limit.map(|(n, _)| n.get())
     }
     pub fn wraps(self) -> bool {
-        self.limit.map_or(false, |(_, b)| b)
+        self.limit.is_some_and(|(_, b)| b)
     }
     #[inline]
     fn get_limit_if_wrap(self) -> Option<usize> {
@@ -89,66 +176,316 @@ impl CellsLimit {
     }
 }
 
-pub struct State {
-    cells: Vec<Wrapping<u8>>,
+/// How to treat reading a cell that has never been written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UninitReads {
+    #[default]
+    Allow,
+    /// Record a warning, see `State::take_warnings`
+    Warn,
+    /// Stop with an error
+    Deny,
+}
+
+/// What `,` does once the input has ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Eof {
+    /// Stop with `Error::EndOfInput`
+    #[default]
+    Error,
+    /// Set the cell to 0
+    Zero,
+    /// Set the cell to -1, which is 255 for 8-bit cells
+    MinusOne,
+    /// Leave the cell as it is
+    Unchanged,
+    /// End the program as if it had reached the end of the source,
+    /// like filters that stop once their input has been read
+    Terminate,
+}
+
+/// A cap on how many bytes of input `,` may read over the life of a `State`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputLimit {
+    pub bytes: u64,
+    /// Whether reading past the limit acts like the input has ended, following the `Eof` mode,
+    /// instead of failing with `Error::InputLimitReached`
+    pub ends_input: bool,
+}
+
+/// The tape and everything else a program runs on, with cells of type `C`
+pub struct State<C: Cell = Wrapping<u8>> {
+    cells: Vec<C>,
     cells_limit: CellsLimit,
+    eof: Eof,
+    input_limit: Option<InputLimit>,
+    /// The most iterations a loop may run in a row, see `set_loop_limit`
+    loop_limit: Option<NonZeroU64>,
+    /// How many iterations in a row each loop that has been entered has run,
+    /// by the offset of its `[`, only counted with a loop limit
+    loop_iterations: BTreeMap<usize, u64>,
+    /// Set while the tiered engine moves a loop it's in the middle of into compiled code,
+    /// so that entering the compiled loop doesn't count its iterations from the start again
+    resuming_loop: bool,
+    uninit_reads: UninitReads,
+    recover: bool,
+    /// Set when `,` ends the program under `Eof::Terminate`, until the engine has stopped running it
+    terminated: bool,
+    /// Where the pointer starts and is put back by `reset`
+    start_cell: usize,
+    /// Which cells have been written to, only tracked if uninitialized reads are checked
+    written: Vec<bool>,
+    warnings: Warnings,
     pub cell_pointer: usize,
-    pub ongoing_loops: Vec<Command>,
+    pub ongoing_loops: Vec<(Command, Position)>,
     pub loop_nesting: u16,
-    running: Arc<AtomicBool>,
+    loop_starts: Vec<Position>,
+    /// The matching bracket of each bracket in a loop being run, kept to reuse its allocation
+    loop_jumps: Vec<usize>,
+    position: Position,
+    /// Index of the instruction being executed when run by a `Program`
+    pc: Option<usize>,
+    instructions: u64,
+    bytes_read: u64,
+    bytes_written: u64,
+    /// Highest pointer position since the start of the current run
+    peak_pointer: usize,
+    /// Spans of the loops being run, see `telemetry`
+    #[cfg(feature = "tracing")]
+    loop_spans: telemetry::LoopSpans,
+    shared: Arc<Shared>,
+}
+
+// Hosts move states to worker threads and control them through a `ControlHandle`
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<State<Wrapping<u8>>>();
+};
+
+/// A clone continues from the same point but is controlled on its own,
+/// handles and stoppers of the original don't reach it.
+impl<C: Cell> Clone for State<C> {
+    fn clone(&self) -> Self {
+        State {
+            cells: self.cells.clone(),
+            cells_limit: self.cells_limit,
+            eof: self.eof,
+            input_limit: self.input_limit,
+            loop_limit: self.loop_limit,
+            loop_iterations: self.loop_iterations.clone(),
+            resuming_loop: self.resuming_loop,
+            uninit_reads: self.uninit_reads,
+            recover: self.recover,
+            terminated: self.terminated,
+            start_cell: self.start_cell,
+            written: self.written.clone(),
+            warnings: self.warnings.clone(),
+            cell_pointer: self.cell_pointer,
+            ongoing_loops: self.ongoing_loops.clone(),
+            loop_nesting: self.loop_nesting,
+            loop_starts: self.loop_starts.clone(),
+            loop_jumps: Vec::new(),
+            position: self.position,
+            pc: self.pc,
+            instructions: self.instructions,
+            bytes_read: self.bytes_read,
+            bytes_written: self.bytes_written,
+            peak_pointer: self.peak_pointer,
+            #[cfg(feature = "tracing")]
+            loop_spans: self.loop_spans.clone(),
+            shared: Arc::default(),
+        }
+    }
+}
+
+impl<C: Cell> Debug for State<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tape = self.cells();
+        tape.trim_end();
+        f.debug_struct("State")
+            .field("cell_pointer", &self.cell_pointer)
+            .field("cells_limit", &self.cells_limit)
+            .field("tape", &tape.collect::<Vec<_>>())
+            .field("loop_nesting", &self.loop_nesting)
+            .field("position", &self.position)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The tape in hex up to its last non-zero cell, with the current cell in brackets
+impl<C: Cell> Display for State<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.dump(Layout::Inline).fmt(f)
+    }
+}
+
+/// States are equal if their tapes hold the same values, ignoring trailing zeroes,
+/// and their pointers and cell limits are the same.
+/// Configuration, progress and loops that haven't ended are not compared.
+impl<C: Cell> PartialEq for State<C> {
+    fn eq(&self, other: &Self) -> bool {
+        fn trimmed<C: Cell>(cells: &[C]) -> &[C] {
+            let len = cells
+                .iter()
+                .rposition(|c| !c.is_zero())
+                .map_or(0, |i| i + 1);
+            &cells[..len]
+        }
+        self.cell_pointer == other.cell_pointer
+            && self.cells_limit == other.cells_limit
+            && trimmed(&self.cells) == trimmed(&other.cells)
+    }
 }
 
 impl Default for State {
     #[inline]
     fn default() -> Self {
-        State {
-            cells: vec![Wrapping(0)],
-            cells_limit: CellsLimit::default(),
-            cell_pointer: 0,
-            ongoing_loops: Vec::new(),
-            loop_nesting: 0,
-            running: Arc::new(AtomicBool::new(false)),
-        }
+        State::with_limit(CellsLimit::default())
     }
 }
 
 impl State {
     #[inline]
     pub fn new(cells_limit: CellsLimit) -> Self {
+        State::with_limit(cells_limit)
+    }
+}
+
+impl<C: Cell> State<C> {
+    /// Creates a state with any type of cell
+    pub fn with_limit(cells_limit: CellsLimit) -> Self {
         State {
+            cells: vec![C::default()],
             cells_limit,
-            ..Self::default()
+            eof: Eof::default(),
+            input_limit: None,
+            loop_limit: None,
+            loop_iterations: BTreeMap::new(),
+            resuming_loop: false,
+            uninit_reads: UninitReads::default(),
+            recover: false,
+            terminated: false,
+            start_cell: 0,
+            written: Vec::new(),
+            warnings: Warnings::default(),
+            cell_pointer: 0,
+            ongoing_loops: Vec::new(),
+            loop_nesting: 0,
+            loop_starts: Vec::new(),
+            loop_jumps: Vec::new(),
+            position: Position::START,
+            pc: None,
+            instructions: 0,
+            bytes_read: 0,
+            bytes_written: 0,
+            peak_pointer: 0,
+            #[cfg(feature = "tracing")]
+            loop_spans: telemetry::LoopSpans::default(),
+            shared: Arc::default(),
+        }
+    }
+    /// Creates a state with room for `capacity` cells on the tape, so that programs known to
+    /// use that many cells don't have to grow it while running, even without a cell limit
+    pub fn with_capacity(cells_limit: CellsLimit, capacity: usize) -> Self {
+        let mut state = State::with_limit(cells_limit);
+        state.reserve_cells(capacity);
+        state
+    }
+    /// Makes room for at least `capacity` cells on the tape, but no more than the cell limit
+    pub fn reserve_cells(&mut self, capacity: usize) {
+        let capacity = match self.cells_limit.limit() {
+            Some(limit) => capacity.min(limit),
+            None => capacity,
+        };
+        self.cells
+            .reserve(capacity.saturating_sub(self.cells.len()));
+        if self.uninit_reads != UninitReads::Allow {
+            self.written
+                .reserve(capacity.saturating_sub(self.written.len()));
         }
     }
-    pub fn get_cur(&self) -> Wrapping<u8> {
+    pub fn get_cur(&self) -> C {
         self.cells
             .get(self.cell_pointer)
-            .copied()
+            .cloned()
             .unwrap_or_default()
     }
-    pub fn get_mut_cur(&mut self) -> &mut Wrapping<u8> {
+    /// The cell at `index`, which is zero if it has never been used
+    pub fn get(&self, index: usize) -> C {
+        self.cells.get(index).cloned().unwrap_or_default()
+    }
+    /// Sets the cell at `index`, failing with `Error::OutOfBounds` if it's past the cell limit
+    pub fn set(&mut self, index: usize, cell: C) -> Result<()> {
+        if self.cells_limit.limit().is_some_and(|limit| index >= limit) {
+            return Err(Error::OutOfBounds);
+        }
+        if self.cells.len() <= index {
+            self.cells.resize(index + 1, C::default());
+        }
+        self.cells[index] = cell;
+        if self.uninit_reads != UninitReads::Allow {
+            if self.written.len() <= index {
+                self.written.resize(index + 1, false);
+            }
+            self.written[index] = true;
+        }
+        Ok(())
+    }
+    pub fn get_mut_cur(&mut self) -> &mut C {
+        if self.uninit_reads != UninitReads::Allow {
+            self.mark_written();
+        }
         // Make sure the cells has allocated enough space
         if self.cells.len() <= self.cell_pointer {
-            self.cells.resize(self.cell_pointer + 1, Wrapping(0));
+            self.cells.resize(self.cell_pointer + 1, C::default());
         }
         // This is safe since we're checking above and making sure the `Vec` is big enough
         unsafe { self.cells.get_unchecked_mut(self.cell_pointer) }
     }
+    fn mark_written(&mut self) {
+        if self.written.len() <= self.cell_pointer {
+            self.written.resize(self.cell_pointer + 1, false);
+        }
+        self.written[self.cell_pointer] = true;
+    }
+    /// Gets the current cell for a command that observes its value
+    fn read_cur(&mut self) -> Result<C> {
+        if self.uninit_reads != UninitReads::Allow
+            && !self
+                .written
+                .get(self.cell_pointer)
+                .copied()
+                .unwrap_or(false)
+        {
+            let err = Error::UninitializedRead {
+                cell: self.cell_pointer,
+                position: self.position,
+            };
+            if self.uninit_reads == UninitReads::Deny {
+                return Err(err);
+            }
+            self.warnings.record(err);
+            // Only warn once per cell
+            self.mark_written();
+        }
+        Ok(self.get_cur())
+    }
     pub fn pointer_add(&mut self) -> Result<()> {
         let (cp, overflow) = self.cell_pointer.overflowing_add(1);
 
         match self.cells_limit.limit {
             Some((lim, true)) => self.cell_pointer = cp % lim.get(),
-            _ if overflow => return Err(Error::CellPointerOverflow),
+            _ if overflow => return self.recoverable(Error::CellPointerOverflow(self.position)),
             None => self.cell_pointer = cp,
             Some((lim, false)) => {
                 if cp >= lim.get() {
-                    return Err(Error::CellPointerOverflow);
+                    return self.recoverable(Error::CellPointerOverflow(self.position));
                 }
 
                 self.cell_pointer = cp;
             }
         }
+        self.peak_pointer = self.peak_pointer.max(self.cell_pointer);
 
         Ok(())
     }
@@ -158,8 +495,9 @@ impl State {
         if overflow {
             if let Some(limit) = self.cells_limit.get_limit_if_wrap() {
                 self.cell_pointer = limit - 1;
+                self.peak_pointer = self.peak_pointer.max(self.cell_pointer);
             } else {
-                return Err(Error::CellPointerOverflow);
+                return self.recoverable(Error::CellPointerOverflow(self.position));
             }
         } else {
             self.cell_pointer = cp;
@@ -167,76 +505,330 @@ impl State {
 
         Ok(())
     }
+    /// Executes `.`
+    fn output<I: BfIo>(&mut self, io: &mut I) -> Result<()> {
+        let byte = self.read_cur()?.to_byte();
+        io.write(byte)?;
+        self.bytes_written += 1;
+        #[cfg(feature = "tracing")]
+        self.loop_spans.output(byte);
+        Ok(())
+    }
+    /// Executes `,`
+    fn input<I: BfIo>(&mut self, io: &mut I) -> Result<()> {
+        let byte = match self.input_limit {
+            Some(limit) if self.bytes_read >= limit.bytes => {
+                if !limit.ends_input {
+                    return self.recoverable(Error::InputLimitReached(self.position));
+                }
+                None
+            }
+            _ => io.read()?,
+        };
+        #[cfg(feature = "tracing")]
+        self.loop_spans.input(byte);
+        match byte {
+            Some(byte) => {
+                *self.get_mut_cur() = C::from_byte(byte);
+                self.bytes_read += 1;
+            }
+            None => match self.eof {
+                Eof::Error => self.recoverable(Error::EndOfInput(self.position))?,
+                Eof::Zero => *self.get_mut_cur() = C::default(),
+                Eof::MinusOne => {
+                    let cell = self.get_mut_cur();
+                    *cell = C::default();
+                    cell.decrement();
+                }
+                Eof::Unchanged => (),
+                Eof::Terminate => {
+                    // Stops whatever engine is running as an error, which it then turns into
+                    // a clean end with `ended_by_input`
+                    self.terminated = true;
+                    return Err(Error::EndOfInput(self.position));
+                }
+            },
+        }
+        Ok(())
+    }
+    /// Whether `result` is `,` ending the program under `Eof::Terminate`,
+    /// passing on any other error
+    fn ended_by_input(&mut self, result: Result<()>) -> Result<bool> {
+        match result {
+            Ok(()) => Ok(false),
+            Err(_) if take(&mut self.terminated) => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+    /// Records `err` as a warning if recovering from errors, or returns it otherwise
+    fn recoverable(&mut self, err: Error) -> Result<()> {
+        if self.recover {
+            self.warnings.record(err);
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
     #[inline]
     #[must_use]
     pub fn get_stop_sender(&self) -> Stopper {
         Stopper {
-            inner: self.running.clone(),
+            inner: self.shared.clone(),
+        }
+    }
+    #[inline]
+    #[must_use]
+    pub fn control_handle(&self) -> ControlHandle {
+        ControlHandle {
+            inner: self.shared.clone(),
+        }
+    }
+    /// Starts a new run, see `Shared::start`
+    #[inline]
+    fn start(&mut self) {
+        self.shared.start();
+        #[cfg(feature = "tracing")]
+        self.loop_spans.clear();
+    }
+    /// Starts counting the iterations of the loop whose `[` is at `begin`,
+    /// which has just been entered
+    #[inline]
+    fn loop_entered(&mut self, begin: Position) {
+        if self.loop_limit.is_some() && !take(&mut self.resuming_loop) {
+            self.loop_iterations.insert(begin.offset, 1);
+        }
+        #[cfg(feature = "tracing")]
+        self.loop_spans.entered(begin);
+    }
+    /// Called when the `]` of the innermost loop that has been entered lets the program go on
+    #[inline]
+    fn loop_exited(&mut self) {
+        #[cfg(feature = "tracing")]
+        self.loop_spans.exited();
+    }
+    /// Counts another iteration of the loop whose `[` is at `begin`,
+    /// failing if it has run as many in a row as the loop limit allows
+    #[inline]
+    fn loop_repeated(&mut self, begin: Position) -> Result<()> {
+        let Some(limit) = self.loop_limit else {
+            return Ok(());
+        };
+        let iterations = self.loop_iterations.entry(begin.offset).or_insert(1);
+        if *iterations >= limit.get() {
+            return Err(Error::LoopLimitReached(begin));
+        }
+        *iterations += 1;
+        Ok(())
+    }
+    #[inline]
+    fn checkpoint(&self) -> Result<()> {
+        self.shared.checkpoint(self)
+    }
+    pub fn snapshot(&self) -> Snapshot<C> {
+        Snapshot {
+            cells: self.cells.clone(),
+            written: self.written.clone(),
+            cell_pointer: self.cell_pointer,
+            ongoing_loops: self.ongoing_loops.clone(),
+            loop_nesting: self.loop_nesting,
+            loop_starts: self.loop_starts.clone(),
+        }
+    }
+    /// Clears the tape, loops that haven't ended, warnings and counters and puts the pointer
+    /// back at the start cell, keeping the configuration like the cell limit and EOF mode.
+    /// Control handles of this state keep working.
+    pub fn reset(&mut self) {
+        self.cells.clear();
+        self.cells.push(C::default());
+        self.written.clear();
+        self.warnings = Warnings::default();
+        self.terminated = false;
+        self.cell_pointer = self.start_cell;
+        self.ongoing_loops.clear();
+        self.loop_nesting = 0;
+        self.loop_starts.clear();
+        self.loop_iterations.clear();
+        self.resuming_loop = false;
+        #[cfg(feature = "tracing")]
+        self.loop_spans.clear();
+        self.position = Position::START;
+        self.pc = None;
+        self.instructions = 0;
+        self.bytes_read = 0;
+        self.bytes_written = 0;
+        self.peak_pointer = self.start_cell;
+    }
+    pub fn restore(&mut self, snapshot: Snapshot<C>) {
+        let Snapshot {
+            cells,
+            written,
+            cell_pointer,
+            ongoing_loops,
+            loop_nesting,
+            loop_starts,
+        } = snapshot;
+        self.cells = cells;
+        self.written = written;
+        self.cell_pointer = cell_pointer;
+        self.ongoing_loops = ongoing_loops;
+        self.loop_nesting = loop_nesting;
+        self.loop_starts = loop_starts;
+    }
+    /// Forgets the loops that haven't been ended yet, keeping the tape,
+    /// so that source fed next doesn't continue them
+    pub fn abandon_loops(&mut self) {
+        self.ongoing_loops.clear();
+        self.loop_nesting = 0;
+        self.loop_starts.clear();
+        self.loop_iterations.clear();
+    }
+    /// The positions of the `[` of every loop that hasn't been ended yet
+    pub fn loop_starts(&self) -> &[Position] {
+        &self.loop_starts
+    }
+    pub fn eof(&self) -> Eof {
+        self.eof
+    }
+    pub fn set_eof(&mut self, eof: Eof) {
+        self.eof = eof;
+    }
+    pub fn input_limit(&self) -> Option<InputLimit> {
+        self.input_limit
+    }
+    /// Limits how many bytes `,` may read, counting what has been read already,
+    /// so that untrusted programs can't drain an attached stream
+    pub fn set_input_limit(&mut self, input_limit: Option<InputLimit>) {
+        self.input_limit = input_limit;
+    }
+    pub fn loop_limit(&self) -> Option<NonZeroU64> {
+        self.loop_limit
+    }
+    /// Limits how many iterations a loop may run in a row before failing with
+    /// `Error::LoopLimitReached`, to catch programs that loop forever.
+    /// The iterations are counted again every time the loop is entered.
+    ///
+    /// Loops that `Bytecode` turns into a single op, like `[-]` and `[>]`, aren't counted.
+    pub fn set_loop_limit(&mut self, loop_limit: Option<NonZeroU64>) {
+        self.loop_limit = loop_limit;
+    }
+    pub fn uninit_reads(&self) -> UninitReads {
+        self.uninit_reads
+    }
+    pub fn set_uninit_reads(&mut self, uninit_reads: UninitReads) {
+        self.uninit_reads = uninit_reads;
+    }
+    pub fn recovers(&self) -> bool {
+        self.recover
+    }
+    /// Makes pointer overflows and reading past the end of input recoverable.
+    ///
+    /// Instead of stopping the program, an overflowing pointer stays where it is,
+    /// `,` leaves the cell unchanged at the end of input and the error is recorded
+    /// as a warning.
+    pub fn set_recover(&mut self, recover: bool) {
+        self.recover = recover;
+    }
+    pub fn start_cell(&self) -> usize {
+        self.start_cell
+    }
+    /// Moves the pointer to `cell` and makes `reset` put it back there,
+    /// so that data can be put on the tape to the left of where the program starts.
+    /// Fails with `Error::OutOfBounds` if `cell` is past the cell limit.
+    pub fn set_start_cell(&mut self, cell: usize) -> Result<()> {
+        if self.cells_limit.limit().is_some_and(|limit| cell >= limit) {
+            return Err(Error::OutOfBounds);
         }
+        self.start_cell = cell;
+        self.cell_pointer = cell;
+        self.peak_pointer = self.peak_pointer.max(cell);
+        Ok(())
+    }
+    /// Takes the warnings recorded since the last call
+    pub fn take_warnings(&mut self) -> Warnings {
+        take(&mut self.warnings)
+    }
+    /// The source position of the command being executed, or the last one executed
+    pub fn position(&self) -> Position {
+        self.position
+    }
+    /// The index into its `Program` of the instruction being executed, or the last one executed.
+    /// After a failed `Program` run this is the instruction that failed.
+    ///
+    /// `None` if the state was last run from source rather than by a `Program`.
+    pub fn pc(&self) -> Option<usize> {
+        self.pc
+    }
+    /// The number of commands that have been executed on this state
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+    /// The memory the tape takes up, in bytes
+    pub fn tape_bytes(&self) -> usize {
+        self.cells.capacity() * core::mem::size_of::<C>() + self.written.capacity()
     }
     pub fn cells_limit(&self) -> &CellsLimit {
         &self.cells_limit
     }
-    #[must_use]
-    pub fn cells(&self) -> CellsIter {
+    pub fn cells(&self) -> CellsIter<'_, C> {
         CellsIter {
             size: self.cells_limit.limit().unwrap_or(self.cells.len()),
             inner: self.cells.iter(),
         }
     }
-    pub fn evaluate(self) -> Result<CellsIntoIter> {
+    pub fn evaluate(self) -> Result<CellsIntoIter<C>> {
         let State {
-            loop_nesting,
+            loop_starts,
             cells,
             cells_limit,
             ..
         } = self;
-        if loop_nesting == 0 {
+        if loop_starts.is_empty() {
             Ok(CellsIntoIter {
                 size: cells_limit.limit().unwrap_or(cells.len()),
                 inner: cells.into_iter(),
             })
         } else {
-            Err(Error::UnendedLoop)
+            Err(Error::UnendedLoop(loop_starts))
         }
     }
 }
 
-pub struct Stopper {
-    inner: Arc<AtomicBool>,
-}
-
-impl Stopper {
-    pub fn stop(self) {
-        self.inner.store(false, Ordering::SeqCst);
-    }
+/// A copy of the tape, pointer and loop bookkeeping of a `State`
+#[derive(Debug, Clone)]
+pub struct Snapshot<C: Cell = Wrapping<u8>> {
+    cells: Vec<C>,
+    written: Vec<bool>,
+    cell_pointer: usize,
+    ongoing_loops: Vec<(Command, Position)>,
+    loop_nesting: u16,
+    loop_starts: Vec<Position>,
 }
 
 #[derive(Debug, Clone)]
 #[must_use]
-pub struct CellsIter<'a> {
-    inner: std::slice::Iter<'a, Wrapping<u8>>,
+pub struct CellsIter<'a, C: Cell = Wrapping<u8>> {
+    inner: core::slice::Iter<'a, C>,
     size: usize,
 }
 
-impl CellsIter<'_> {
+impl<C: Cell> CellsIter<'_, C> {
     pub fn trim_end(&mut self) {
-        while let Some(Wrapping(0)) = self.inner.as_slice().last() {
+        while self.inner.as_slice().last().is_some_and(C::is_zero) {
             self.inner.next_back();
         }
         self.size = self.inner.len();
     }
 }
 
-impl Iterator for CellsIter<'_> {
-    type Item = u8;
+impl<C: Cell> Iterator for CellsIter<'_, C> {
+    type Item = C::Value;
     fn next(&mut self) -> Option<Self::Item> {
-        let ret = self.inner.next().map(|w| w.0);
+        let ret = self.inner.next().map(C::value);
 
         if self.size > 0 {
             self.size -= 1;
             if ret.is_none() {
-                return Some(0);
+                return Some(C::default().value());
             }
         }
 
@@ -244,20 +836,20 @@ impl Iterator for CellsIter<'_> {
     }
 }
 
-impl DoubleEndedIterator for CellsIter<'_> {
+impl<C: Cell> DoubleEndedIterator for CellsIter<'_, C> {
     fn next_back(&mut self) -> Option<Self::Item> {
         if self.size > self.inner.len() {
             self.size -= 1;
-            Some(0)
+            Some(C::default().value())
         } else {
             self.size = self.size.saturating_sub(1);
 
-            self.inner.next_back().map(|w| w.0)
+            self.inner.next_back().map(C::value)
         }
     }
 }
 
-impl ExactSizeIterator for CellsIter<'_> {
+impl<C: Cell> ExactSizeIterator for CellsIter<'_, C> {
     fn len(&self) -> usize {
         self.size
     }
@@ -265,36 +857,36 @@ impl ExactSizeIterator for CellsIter<'_> {
 
 #[derive(Debug, Clone)]
 #[must_use]
-pub struct CellsIntoIter {
-    inner: std::vec::IntoIter<Wrapping<u8>>,
+pub struct CellsIntoIter<C: Cell = Wrapping<u8>> {
+    inner: alloc::vec::IntoIter<C>,
     size: usize,
 }
 
-impl CellsIntoIter {
+impl<C: Cell> CellsIntoIter<C> {
     #[inline]
-    pub fn as_ref(&self) -> CellsIter<'_> {
+    pub fn as_ref(&self) -> CellsIter<'_, C> {
         CellsIter {
             inner: self.inner.as_slice().iter(),
             size: self.size,
         }
     }
     pub fn trim_end(&mut self) {
-        while let Some(Wrapping(0)) = self.inner.as_slice().last() {
+        while self.inner.as_slice().last().is_some_and(C::is_zero) {
             self.inner.next_back();
         }
         self.size = self.inner.len();
     }
 }
 
-impl Iterator for CellsIntoIter {
-    type Item = u8;
+impl<C: Cell> Iterator for CellsIntoIter<C> {
+    type Item = C::Value;
     fn next(&mut self) -> Option<Self::Item> {
-        let ret = self.inner.next().map(|w| w.0);
+        let ret = self.inner.next().map(|c| c.value());
 
         if self.size > 0 {
             self.size -= 1;
             if ret.is_none() {
-                return Some(0);
+                return Some(C::default().value());
             }
         }
 
@@ -302,114 +894,348 @@ impl Iterator for CellsIntoIter {
     }
 }
 
-impl DoubleEndedIterator for CellsIntoIter {
+impl<C: Cell> DoubleEndedIterator for CellsIntoIter<C> {
     fn next_back(&mut self) -> Option<Self::Item> {
         if self.size > self.inner.len() {
             self.size -= 1;
-            Some(0)
+            Some(C::default().value())
         } else {
             self.size = self.size.saturating_sub(1);
 
-            self.inner.next_back().map(|w| w.0)
+            self.inner.next_back().map(|c| c.value())
         }
     }
 }
 
-impl ExactSizeIterator for CellsIntoIter {
+impl<C: Cell> ExactSizeIterator for CellsIntoIter<C> {
     fn len(&self) -> usize {
         self.size
     }
 }
 
-pub struct InOuter<W: Write, R: Read> {
-    o: W,
-    i: BufReader<R>,
+/// Runs the source read from `src` on `state`.
+///
+/// The source is read in chunks, so `src` doesn't need to be buffered.
+#[cfg(feature = "std")]
+pub fn run_with_state<R, C, I>(src: R, state: &mut State<C>, io: &mut I) -> Result<RunReport>
+where
+    R: Read,
+    C: Cell,
+    I: BfIo,
+{
+    let mut bytes = ChunkedBytes::new(src);
+    let report = run(&mut bytes, state, io)?;
+    match bytes.error {
+        Some(e) => Err(e.into()),
+        None => Ok(report),
+    }
+}
+
+/// The bytes of a reader, read a chunk at a time
+///
+/// Reading stops at the first error, which is kept to be returned after.
+#[cfg(feature = "std")]
+struct ChunkedBytes<R> {
+    src: R,
+    buf: Vec<u8>,
+    start: usize,
+    end: usize,
+    error: Option<std::io::Error>,
 }
 
-impl<W: Write, R: Read> InOuter<W, R> {
-    pub fn new(o: W, i: R) -> Self {
-        InOuter {
-            o,
-            i: BufReader::new(i),
+#[cfg(feature = "std")]
+impl<R: Read> ChunkedBytes<R> {
+    const CHUNK_SIZE: usize = 8 * 1024;
+
+    fn new(src: R) -> Self {
+        ChunkedBytes {
+            src,
+            buf: vec![0; Self::CHUNK_SIZE],
+            start: 0,
+            end: 0,
+            error: None,
         }
     }
-    pub fn extract(self) -> (W, R) {
-        let InOuter { i, o } = self;
-        (o, i.into_inner())
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Iterator for ChunkedBytes<R> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.start == self.end {
+            if self.error.is_some() {
+                return None;
+            }
+            match self.src.read(&mut self.buf) {
+                Ok(0) => return None,
+                Ok(n) => {
+                    self.start = 0;
+                    self.end = n;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(e) => self.error = Some(e),
+            }
+        }
+        self.start += 1;
+        Some(self.buf[self.start - 1])
     }
 }
 
-pub fn run_with_state<R, R2, W>(src: R, state: &mut State, io: &mut InOuter<W, R2>) -> Result<()>
+#[cfg(feature = "std")]
+impl<C: Cell> State<C> {
+    /// Runs each of `sources` on this state in order, stopping at the first one that fails.
+    ///
+    /// The sources share everything, so a loop can start in one source and end in a later one.
+    /// On success there is a report for each source.
+    pub fn run_all<S, I>(
+        &mut self,
+        sources: S,
+        io: &mut I,
+    ) -> core::result::Result<Vec<RunReport>, StageError>
+    where
+        S: IntoIterator,
+        S::Item: Read,
+        I: BfIo,
+    {
+        sources
+            .into_iter()
+            .enumerate()
+            .map(|(stage, src)| {
+                run_with_state(src, self, &mut *io).map_err(|error| StageError { stage, error })
+            })
+            .collect()
+    }
+}
+
+/// Runs the source in `src` on `state`, without needing `std`
+pub fn run_bytes<S, C, I>(src: S, state: &mut State<C>, io: &mut I) -> Result<RunReport>
 where
-    R: Read,
-    R2: Read,
-    W: Write,
+    S: IntoIterator<Item = u8>,
+    C: Cell,
+    I: BfIo,
+{
+    run(src.into_iter(), state, io)
+}
+
+/// Runs `src` on `state` straight from memory, for when the whole program is already loaded
+#[inline]
+pub fn run_slice<C, I>(src: &[u8], state: &mut State<C>, io: &mut I) -> Result<RunReport>
+where
+    C: Cell,
+    I: BfIo,
+{
+    run(src.iter().copied(), state, io)
+}
+
+fn run<S, C, I>(src: S, state: &mut State<C>, io: &mut I) -> Result<RunReport>
+where
+    S: Iterator<Item = u8>,
+    C: Cell,
+    I: BfIo,
+{
+    run_from(src, Position::START, state, io)
+}
+
+/// Runs `src`, which starts at `pos` in the whole source
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "run", skip_all, fields(engine = "stream"), err)
+)]
+fn run_from<S, C, I>(
+    src: S,
+    mut pos: Position,
+    state: &mut State<C>,
+    io: &mut I,
+) -> Result<RunReport>
+where
+    S: Iterator<Item = u8>,
+    C: Cell,
+    I: BfIo,
 {
-    state.running.store(true, Ordering::SeqCst);
-    for cmd in src.bytes().map(|b| b.map(Command::from_byte)) {
-        if !state.running.load(Ordering::SeqCst) {
-            return Err(Error::Stopped);
+    #[cfg(feature = "metrics")]
+    let mut metered = telemetry::Run::new(state, "stream");
+    #[cfg(feature = "metrics")]
+    let state = &mut *metered;
+    state.start();
+    state.pc = None;
+    let instructions = state.instructions;
+    let bytes_read = state.bytes_read;
+    let bytes_written = state.bytes_written;
+    state.peak_pointer = state.cell_pointer;
+
+    for byte in src {
+        state.checkpoint()?;
+        if let Some(cmd) = Command::from_byte(byte) {
+            let result = run_command(state, cmd, pos, io);
+            if state.ended_by_input(result)? {
+                break;
+            }
         }
+        pos.advance(byte);
+    }
+
+    Ok(RunReport {
+        end: if state.loop_nesting == 0 {
+            EndReason::EndOfSource
+        } else {
+            EndReason::AwaitingLoopEnd
+        },
+        instructions: state.instructions - instructions,
+        bytes_read: state.bytes_read - bytes_read,
+        bytes_written: state.bytes_written - bytes_written,
+        peak_pointer: state.peak_pointer,
+    })
+}
+
+/// Runs `body` of the loop whose `[` is at `begin` while the current cell isn't zero,
+/// jumping between the brackets of the loops inside of it without allocating
+fn run_loop<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    begin: Position,
+    body: &[(Command, Position)],
+    io: &mut I,
+) -> Result<()> {
+    let mut jumps = take(&mut state.loop_jumps);
+    jumps.clear();
+    // While looking for matches, each open `[` holds the index of the one it's inside of
+    let mut open = usize::MAX;
+    for (i, &(cmd, _)) in body.iter().enumerate() {
         match cmd {
-            Ok(cmd) => {
-                if let Some(cmd) = cmd {
-                    run_command(state, cmd, io)?;
-                }
+            LoopBegin => {
+                jumps.push(open);
+                open = i;
+            }
+            LoopEnd => {
+                let begin = open;
+                open = jumps[begin];
+                jumps[begin] = i;
+                jumps.push(begin);
             }
-            Err(e) => return Err(Error::IoError(e)),
+            _ => jumps.push(0),
         }
     }
 
+    let result = run_jumping(state, begin, body, &jumps, io);
+    state.loop_jumps = jumps;
+    result
+}
+
+fn run_jumping<C: Cell, I: BfIo>(
+    state: &mut State<C>,
+    begin: Position,
+    body: &[(Command, Position)],
+    jumps: &[usize],
+    io: &mut I,
+) -> Result<()> {
+    // Counted as entered even if it's skipped, which only restarts a count that isn't used
+    state.loop_entered(begin);
+    let mut repeated = false;
+    while !state.read_cur()?.is_zero() {
+        if repeated {
+            state.loop_repeated(begin)?;
+        }
+        repeated = true;
+        state.checkpoint()?;
+        let mut pc = 0;
+        while let Some(&(cmd, pos)) = body.get(pc) {
+            match cmd {
+                // Like the outer loop, the `[` of an inner loop is counted once when it's reached
+                // and its `]` once for every iteration
+                LoopBegin => {
+                    state.instructions += 1;
+                    state.position = pos;
+                    if state.read_cur()?.is_zero() {
+                        pc = jumps[pc];
+                    } else {
+                        state.loop_entered(pos);
+                        state.checkpoint()?;
+                    }
+                }
+                LoopEnd => {
+                    state.instructions += 1;
+                    state.position = pos;
+                    if !state.read_cur()?.is_zero() {
+                        state.loop_repeated(body[jumps[pc]].1)?;
+                        state.checkpoint()?;
+                        pc = jumps[pc];
+                    } else {
+                        state.loop_exited();
+                    }
+                }
+                cmd => run_command(state, cmd, pos, io)?,
+            }
+            pc += 1;
+        }
+        // The `]` ending the iteration
+        state.instructions += 1;
+    }
+    state.loop_exited();
     Ok(())
 }
 
-use std::mem::take;
+/// Checks that every loop in `src` is both started and ended
+pub fn check(src: &[u8]) -> Result<()> {
+    Program::parse(src).map(drop)
+}
+
+/// Blanks out a `#!` line at the start of `src`, so that a program can be made
+/// an executable script with a line like `#!/usr/bin/env bf` without it being run.
+/// It's overwritten with spaces rather than removed so the positions after it don't move.
+pub fn blank_shebang(src: &mut [u8]) {
+    if src.starts_with(b"#!") {
+        let end = src.iter().position(|&b| b == b'\n').unwrap_or(src.len());
+        src[..end].fill(b' ');
+    }
+}
 
-fn run_command<W: Write, R: Read>(
-    state: &mut State,
+fn run_command<C: Cell, I: BfIo>(
+    state: &mut State<C>,
     cmd: Command,
-    io: &mut InOuter<W, R>,
+    pos: Position,
+    io: &mut I,
 ) -> Result<()> {
+    // Commands in loops are counted when the loop runs
+    if state.loop_nesting == 0 && cmd != LoopEnd {
+        state.instructions += 1;
+    }
+    if state.loop_nesting == 0 {
+        state.position = pos;
+    }
     match cmd {
         LoopEnd => match state.loop_nesting {
-            0 => return Err(Error::NoLoopStarted),
+            0 => return Err(Error::NoLoopStarted(pos)),
             1 => {
                 state.loop_nesting = 0;
+                let begin = state.loop_starts.pop().unwrap_or(Position::START);
 
-                let cmds = take(&mut state.ongoing_loops);
-                let mut cur = state.get_cur();
-                while cur != Wrapping(0) {
-                    if !state.running.load(Ordering::SeqCst) {
-                        return Err(Error::Stopped);
-                    }
-                    for &cmd in &cmds {
-                        run_command(state, cmd, io)?;
-                    }
-                    cur = state.get_cur();
-                }
+                let body = take(&mut state.ongoing_loops);
+                let result = run_loop(state, begin, &body, io);
+                // Keep the allocation for the next loop
+                state.ongoing_loops = body;
+                state.ongoing_loops.clear();
+                result?;
             }
             _ => {
                 state.loop_nesting -= 1;
-                state.ongoing_loops.push(LoopEnd);
+                state.loop_starts.pop();
+                state.ongoing_loops.push((LoopEnd, pos));
             }
         },
         LoopBegin => {
             state.loop_nesting += 1;
+            state.loop_starts.push(pos);
             if state.loop_nesting > 1 {
-                state.ongoing_loops.push(LoopBegin);
+                state.ongoing_loops.push((LoopBegin, pos));
             }
         }
-        cmd if state.loop_nesting > 0 => state.ongoing_loops.push(cmd),
+        cmd if state.loop_nesting > 0 => state.ongoing_loops.push((cmd, pos)),
         PtrIncr => state.pointer_add()?,
         PtrDecr => state.pointer_sub()?,
-        Incr => *state.get_mut_cur() += Wrapping(1),
-        Decr => *state.get_mut_cur() -= Wrapping(1),
-        Out => io.o.write_all(&[state.get_cur().0])?,
-        In => {
-            let mut byte = [0];
-            io.i.read_exact(&mut byte)?;
-            *state.get_mut_cur() = Wrapping(byte[0]);
-        }
+        Incr => state.get_mut_cur().increment(),
+        Decr => state.get_mut_cur().decrement(),
+        Out => state.output(io)?,
+        In => state.input(io)?,
     }
 
     Ok(())
@@ -0,0 +1,155 @@
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+use crate::{Command, Position};
+
+/// A suspicious but legal pattern found in a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lint {
+    pub kind: LintKind,
+    /// Where the pattern starts
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    /// `+` or `-` whose result is immediately replaced by `,`
+    OverwrittenByInput,
+    /// `[]`, which either does nothing or never terminates
+    EmptyLoop,
+    /// A loop that starts right after another loop or `[-]`, so the current cell is always zero
+    DeadLoop,
+    /// A loop whose body moves the pointer by a net amount each iteration
+    PointerDrift(isize),
+    /// A loop whose body never changes the cell it tests, found by `analysis::analyze`
+    InfiniteLoop,
+    /// Code after a loop that is entered and never ends, found by `analysis::analyze`
+    Unreachable,
+}
+
+impl Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintKind::OverwrittenByInput => {
+                write!(f, "cell is modified and then overwritten by `,`")
+            }
+            LintKind::EmptyLoop => write!(f, "empty loop either does nothing or never ends"),
+            LintKind::DeadLoop => {
+                write!(f, "loop never runs since the current cell is always zero")
+            }
+            LintKind::PointerDrift(n) => {
+                write!(f, "loop body moves the pointer by {n} each iteration")
+            }
+            LintKind::InfiniteLoop => {
+                write!(
+                    f,
+                    "loop never ends once entered as its body doesn't change its cell"
+                )
+            }
+            LintKind::Unreachable => {
+                write!(
+                    f,
+                    "code is never reached since the loop before it never ends"
+                )
+            }
+        }
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.position, self.kind)
+    }
+}
+
+/// Finds suspicious patterns in `src`. Unmatched brackets are ignored.
+pub fn lint(src: &[u8]) -> Vec<Lint> {
+    let mut lints = Vec::new();
+
+    let mut pos = Position::START;
+    let mut cmds = Vec::new();
+    for &byte in src {
+        if let Some(cmd) = Command::from_byte(byte) {
+            cmds.push((cmd, pos));
+        }
+        pos.advance(byte);
+    }
+
+    // Start of the current run of `+`/`-`
+    let mut modified = None;
+    // Whether the current cell is known to be zero
+    let mut zero = true;
+    // For every open loop: where it started, the net pointer movement
+    // of its body (`None` if unknown), and whether it only moves the pointer
+    let mut loops: Vec<(Position, Option<isize>, bool)> = Vec::new();
+
+    for (i, &(cmd, pos)) in cmds.iter().enumerate() {
+        match cmd {
+            Command::Incr | Command::Decr => {
+                modified.get_or_insert(pos);
+            }
+            Command::In => {
+                if let Some(pos) = modified {
+                    lints.push(Lint {
+                        kind: LintKind::OverwrittenByInput,
+                        position: pos,
+                    });
+                }
+            }
+            _ => (),
+        }
+        if !matches!(cmd, Command::Incr | Command::Decr) {
+            modified = None;
+        }
+
+        match cmd {
+            Command::LoopBegin => {
+                if cmds.get(i + 1).map(|&(c, _)| c) == Some(Command::LoopEnd) {
+                    lints.push(Lint {
+                        kind: LintKind::EmptyLoop,
+                        position: pos,
+                    });
+                } else if zero {
+                    lints.push(Lint {
+                        kind: LintKind::DeadLoop,
+                        position: pos,
+                    });
+                }
+                loops.push((pos, Some(0), true));
+            }
+            Command::LoopEnd => {
+                if let Some((start, drift, only_moves)) = loops.pop() {
+                    match drift {
+                        Some(0) | None => (),
+                        // Scanning loops such as `[>]` drift on purpose
+                        Some(_) if only_moves => (),
+                        Some(n) => lints.push(Lint {
+                            kind: LintKind::PointerDrift(n),
+                            position: start,
+                        }),
+                    }
+                    if let Some(outer) = loops.last_mut() {
+                        outer.2 = false;
+                        if drift != Some(0) {
+                            outer.1 = None;
+                        }
+                    }
+                }
+            }
+            Command::PtrIncr | Command::PtrDecr => {
+                if let Some((_, Some(drift), _)) = loops.last_mut() {
+                    *drift += if cmd == Command::PtrIncr { 1 } else { -1 };
+                }
+            }
+            _ => {
+                if let Some(outer) = loops.last_mut() {
+                    outer.2 = false;
+                }
+            }
+        }
+
+        zero = cmd == Command::LoopEnd;
+    }
+
+    lints
+}
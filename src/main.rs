@@ -1,99 +1,990 @@
 #![warn(clippy::all)]
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::env;
 use std::fs::File;
-use std::io::{stdin, stdout, BufReader, Write};
-use std::num::NonZeroUsize;
+use std::io::{stderr, stdin, stdout, BufWriter, IsTerminal, Read, Write};
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize, Wrapping};
+use std::path::PathBuf;
 use std::process::ExitCode;
+use std::time::Duration;
 
-use brainfuck::{run_with_state, CellsLimit, Error::*, InOuter, Result, State};
+mod cli;
+
+use brainfuck::{
+    bytecode::OptLevel, dialect::Dialect, preprocess::Preprocessor, Cell, Eof, Error, Flush,
+    InputLimit, Interpreter, InterpreterBuilder, Layout, Meter, Newline, Program, UninitReads,
+};
+use cli::{Failure, Result};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// Source code to run
-    #[arg(required_unless_present = "interactive")]
+    #[arg(required_unless_present_any = ["interactive", "example"])]
     source: Option<String>,
+    /// Run one of the built-in example programs instead, which `examples` lists
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["source", "interactive"])]
+    example: Option<String>,
 
     /// Starts interactive shell
     #[arg(short, long)]
     interactive: bool,
+    /// Replay a script recorded in the shell with `$record`, failing if it doesn't do the same
+    #[arg(long, value_name = "FILE", requires = "interactive")]
+    script: Option<String>,
+    /// Save the tape, pointer and shell settings on exit and restore them on the next start,
+    /// in the user's data directory unless given a file
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        require_equals = true,
+        requires = "interactive",
+        conflicts_with = "script"
+    )]
+    persist: Option<Option<PathBuf>>,
+    /// The prompt of the shell, with {pointer} and {cell} for where the pointer is
+    /// and the value of its cell, like "[@{pointer}={cell}]> "
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        requires = "interactive",
+        conflicts_with = "script"
+    )]
+    prompt: Option<cli::repl::Prompt>,
 
     /// The amount of cells that the program can use
     #[arg(short = 's', long = "size", value_name = "SIZE")]
     limit: Option<NonZeroUsize>,
+    /// The amount of cells to allocate up front, without limiting the program to them
+    #[arg(long, value_name = "CELLS", default_value_t = 0)]
+    reserve: usize,
+    /// The cell the pointer starts at, leaving the cells to the left of it for data
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    start_cell: usize,
     /// Whether the cell pointer should wrap around the cell size
     #[arg(short, long, requires = "limit")]
     wrap: bool,
+    /// What to do when the program reads a cell it has never written to
+    #[arg(long, value_name = "LEVEL", default_value = "allow")]
+    uninit_reads: Level,
+    /// What `,` does once the input has ended,
+    /// by default failing, or leaving the cell unchanged for examples
+    #[arg(long, value_name = "MODE")]
+    eof: Option<EofArg>,
+    /// Run this shell command and read the program's input from what it prints
+    #[arg(long, value_name = "COMMAND", conflicts_with = "inputs")]
+    input_cmd: Option<String>,
+    /// Write the program's output to the input of `--input-cmd` instead of stdout
+    #[arg(long, requires = "input_cmd")]
+    output_to_cmd: bool,
+    /// Read every key as soon as it's pressed, without waiting for Enter or echoing it,
+    /// for interactive games
+    #[arg(long, conflicts_with_all = ["interactive", "input_cmd", "inputs"])]
+    raw_input: bool,
+    /// The most bytes of input the program may read
+    #[arg(long, value_name = "BYTES")]
+    max_input: Option<u64>,
+    /// Treat the input as ended once the program has read `--max-input` bytes,
+    /// instead of failing
+    #[arg(long, requires = "max_input")]
+    max_input_ends: bool,
+    /// The most iterations any loop may run in a row before the program fails,
+    /// for catching programs that loop forever
+    #[arg(long, value_name = "N")]
+    max_loop_iterations: Option<NonZeroU64>,
+    /// Keep going after pointer overflows and reads past the end of input, reporting them as warnings
+    #[arg(long)]
+    recover: bool,
+    /// Run the program once for every file in this directory, each on a fresh tape
+    /// with the file as input
+    #[arg(
+        long,
+        value_name = "DIR",
+        requires = "outputs",
+        conflicts_with_all = ["interactive", "tiered", "compiled", "animate", "trace", "summary", "explain", "heatmap", "dump_memory", "stats"]
+    )]
+    inputs: Option<PathBuf>,
+    /// The directory to write what the program prints for each of `--inputs` to,
+    /// in a file of the same name
+    #[arg(long, value_name = "DIR", requires = "inputs")]
+    outputs: Option<PathBuf>,
+    /// Print the resources the program used to stderr once it's done
+    #[arg(long, conflicts_with = "interactive")]
+    stats: bool,
+    /// The type of number each cell holds
+    #[arg(long, value_name = "TYPE", default_value = "u8")]
+    cell: CellArg,
+    /// Interpret the program and compile loops to bytecode once they have run this many times
+    #[arg(
+        long,
+        value_name = "ITERATIONS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "64"
+    )]
+    tiered: Option<u32>,
+    /// Compile the whole program to bytecode before running it,
+    /// reusing the bytecode cached by an earlier run of the same program
+    #[arg(long, conflicts_with_all = ["interactive", "tiered"])]
+    compiled: bool,
+    /// Compile the program with `--compiled` without reading or writing the cache
+    #[arg(long, requires = "compiled")]
+    no_cache: bool,
+    /// How much `--compiled` and `--tiered` optimize the code they compile
+    #[arg(long, value_name = "LEVEL", default_value = "full")]
+    opt_level: OptLevelArg,
+    /// Show the tape after every instruction, waiting this many milliseconds between frames
+    #[arg(
+        long,
+        value_name = "DELAY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "100",
+        conflicts_with_all = ["interactive", "tiered", "compiled"]
+    )]
+    animate: Option<u64>,
+    /// How many instructions to run between frames of `--animate`
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "animate")]
+    animate_every: u64,
+    /// Write a JSON line for every executed instruction to this file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["interactive", "tiered", "compiled", "animate"])]
+    trace: Option<PathBuf>,
+    /// Explain what the program did once it's done: the steps it took, the cells it used,
+    /// how deeply nested its loops got and what it printed
+    #[arg(long, conflicts_with_all = ["interactive", "tiered", "compiled", "animate", "trace"])]
+    summary: bool,
+    /// Narrate what the program does as it runs, in plain English
+    #[arg(
+        long,
+        value_name = "DETAIL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "outer",
+        conflicts_with_all = ["interactive", "tiered", "compiled", "animate", "trace", "summary"]
+    )]
+    explain: Option<ExplainArg>,
+    /// Show how often each cell was accessed once the program is done, as colors on stderr
+    #[arg(
+        long,
+        conflicts_with_all = ["interactive", "tiered", "compiled", "animate", "trace", "summary", "explain"]
+    )]
+    heatmap: bool,
+    /// Stop at every `#` in the source, to print the tape to stderr
+    /// or to open a prompt on the terminal for looking at and changing it
+    #[arg(
+        long,
+        value_name = "ACTION",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "dump",
+        conflicts_with_all = ["interactive", "tiered", "compiled", "animate", "trace", "summary", "explain", "heatmap", "inputs"]
+    )]
+    hash: Option<HashArg>,
+    /// Print the tape to stderr once the program is done, even if it failed
+    #[arg(
+        long,
+        value_name = "LAYOUT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "rows",
+        conflicts_with = "interactive"
+    )]
+    dump_memory: Option<LayoutArg>,
+    /// Render the tape over time to this PNG file
+    #[cfg(feature = "render")]
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["interactive", "tiered", "compiled", "animate", "trace", "summary", "explain", "heatmap"])]
+    render: Option<PathBuf>,
+    /// How many instructions to run between rows of `--render`
+    #[cfg(feature = "render")]
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "render")]
+    render_every: u64,
+    /// Let the interactive shell attach to the running program with `$attach PORT`
+    #[cfg(not(target_os = "wasi"))]
+    #[arg(long, value_name = "PORT", conflicts_with = "interactive")]
+    listen: Option<u16>,
+    /// Print the pointer, instruction count and the tape around the pointer to stderr
+    /// whenever the program is sent SIGUSR1, without stopping it
+    #[cfg(unix)]
+    #[arg(long, conflicts_with = "interactive")]
+    dump_on_signal: bool,
+    /// How errors are written to stderr
+    #[arg(long, value_name = "FORMAT", default_value = "human", global = true)]
+    error_format: ErrorFormatArg,
+    /// Whether errors and warnings are colored
+    #[arg(long, value_name = "WHEN", default_value = "auto", global = true)]
+    color: ColorArg,
+    /// When output is written, output is always written before reading input
+    #[arg(long, value_name = "WHEN", default_value = "line")]
+    flush: FlushArg,
+    /// How the bytes the program prints are written
+    #[arg(long, value_name = "FORMAT", default_value = "bytes")]
+    output_format: OutputFormatArg,
+    /// Wait this many milliseconds between the bytes the program prints,
+    /// for watching animations
+    #[arg(long, value_name = "MS", conflicts_with = "bytes_per_second")]
+    delay: Option<u64>,
+    /// Print at most this many bytes a second, for watching animations
+    #[arg(long, value_name = "N")]
+    bytes_per_second: Option<NonZeroU32>,
+    /// Decode the input from base64 and encode the output to base64
+    #[arg(long, conflicts_with = "output_format")]
+    base64: bool,
+    /// The newlines of the input, crlf reading \r\n as \n
+    #[arg(long, value_name = "NEWLINE", default_value = "lf")]
+    input_newlines: NewlineArg,
+    /// The newlines of the output, crlf printing \r\n for \n
+    #[arg(long, value_name = "NEWLINE", default_value = "lf")]
+    output_newlines: NewlineArg,
+    #[command(flatten)]
+    preprocess: PreprocessArgs,
 }
 
-fn run() -> Result<()> {
-    let cli = Cli::parse();
+#[derive(Args)]
+struct PreprocessArgs {
+    /// Expand `@define name { body }` macros and `@name` invocations
+    #[arg(long)]
+    macros: bool,
+    /// Expand decimal repetition counts before commands, like `12+`
+    #[arg(long)]
+    counts: bool,
+    /// Expand `@include "file.bf"` directives
+    #[arg(long)]
+    includes: bool,
+}
 
-    let limit = CellsLimit::new(cli.limit.map(|limit| (limit, cli.wrap)));
+impl PreprocessArgs {
+    fn preprocessor(&self) -> Preprocessor {
+        Preprocessor::new()
+            .macros(self.macros)
+            .counts(self.counts)
+            .includes(self.includes)
+    }
+}
 
-    let mut state = State::new(limit);
-    let mut stdouter = InOuter::new(stdout(), stdin());
+#[derive(Clone, Copy, ValueEnum)]
+enum CellArg {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+}
 
-    if cli.interactive {
-        println!("Brainfuck Interactive Shell");
-        println!("Type $exit to exit");
-        loop {
-            print!("$> ");
-            stdout().flush().unwrap();
-
-            let mut s = String::new();
-            stdin().read_line(&mut s).unwrap();
-            if s.trim_end() == "$exit" {
-                println!();
-                break;
-            }
-            run_with_state(s.as_bytes(), &mut state, &mut stdouter)?;
+#[derive(Clone, Copy, ValueEnum)]
+enum DialectArg {
+    Bf,
+    Ook,
+    Blub,
+    Alphuck,
+}
 
-            let mut cells_iter = state.cells();
-            cells_iter.trim_end();
+impl From<DialectArg> for Dialect {
+    fn from(dialect: DialectArg) -> Self {
+        match dialect {
+            DialectArg::Bf => Dialect::Brainfuck,
+            DialectArg::Ook => Dialect::Ook,
+            DialectArg::Blub => Dialect::Blub,
+            DialectArg::Alphuck => Dialect::Alphuck,
+        }
+    }
+}
 
-            let n = (cells_iter.len()).max(state.cell_pointer + 1);
+#[derive(Clone, Copy, ValueEnum)]
+enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
 
-            if state.cell_pointer == 0 {
-                print!("[");
-            }
-            for (i, byte) in state
-                .cells()
-                .chain(std::iter::repeat(0))
-                .take(n)
-                .enumerate()
-            {
-                print!("{byte:02x}");
-                if i == state.cell_pointer {
-                    print!("]");
-                } else if i + 1 == state.cell_pointer {
-                    print!("[");
-                } else {
-                    print!(" ");
-                }
+#[derive(Clone, Copy, ValueEnum)]
+enum OptLevelArg {
+    /// Compile every command to an op of its own
+    None,
+    /// Merge runs of the same command
+    Runs,
+    /// Also run common loops like `[-]` as single ops
+    Loops,
+    /// Also fuse ops that often follow each other
+    Full,
+}
+
+impl From<OptLevelArg> for OptLevel {
+    fn from(level: OptLevelArg) -> Self {
+        match level {
+            OptLevelArg::None => OptLevel::None,
+            OptLevelArg::Runs => OptLevel::Runs,
+            OptLevelArg::Loops => OptLevel::Loops,
+            OptLevelArg::Full => OptLevel::Full,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FlushArg {
+    /// After every byte
+    Byte,
+    /// After every newline
+    Line,
+    /// Only when the buffer is full or the program is done
+    Full,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormatArg {
+    /// As they are
+    Bytes,
+    /// As two hex digits each
+    Hex,
+    /// As two hex digits each, separated by spaces
+    HexSpaced,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum NewlineArg {
+    /// \n, left as it is
+    Lf,
+    /// \r\n, translated to and from \n
+    Crlf,
+}
+
+impl From<NewlineArg> for Newline {
+    fn from(newline: NewlineArg) -> Self {
+        match newline {
+            NewlineArg::Lf => Newline::Lf,
+            NewlineArg::Crlf => Newline::Crlf,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExplainArg {
+    /// Loops that aren't inside other loops and the commands between them
+    Outer,
+    /// Every loop each time it ends
+    Loops,
+    /// Every instruction
+    Steps,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ErrorFormatArg {
+    /// With the lines of source they happened in
+    Human,
+    /// As a JSON object on one line, for editors and other tools
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorArg {
+    /// When stderr is a terminal and `NO_COLOR` isn't set
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HashArg {
+    /// Print the tape and go on
+    Dump,
+    /// Open a prompt for looking at and changing the tape
+    Prompt,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LayoutArg {
+    /// Hex cells on one line
+    Inline,
+    /// Rows of hex cells with the bytes they print
+    Rows,
+    /// Decimal cells on one line
+    Decimal,
+    /// Decimal cells with repeated values counted
+    RunLength,
+    /// Cells as the characters they print
+    Ascii,
+}
+
+impl From<LayoutArg> for Layout {
+    fn from(layout: LayoutArg) -> Self {
+        match layout {
+            LayoutArg::Inline => Layout::Inline,
+            LayoutArg::Rows => Layout::Rows,
+            LayoutArg::Decimal => Layout::Decimal,
+            LayoutArg::RunLength => Layout::RunLength,
+            LayoutArg::Ascii => Layout::Ascii,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EofArg {
+    Error,
+    Zero,
+    MinusOne,
+    Unchanged,
+    Terminate,
+}
+
+impl From<EofArg> for Eof {
+    fn from(eof: EofArg) -> Self {
+        match eof {
+            EofArg::Error => Eof::Error,
+            EofArg::Zero => Eof::Zero,
+            EofArg::MinusOne => Eof::MinusOne,
+            EofArg::Unchanged => Eof::Unchanged,
+            EofArg::Terminate => Eof::Terminate,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Compiles a program in the structured assembly language to brainfuck
+    Asm {
+        /// Source code to compile
+        source: String,
+    },
+    /// Checks a program for errors without running it
+    Check {
+        /// Source code to check
+        source: String,
+        /// Also warn about suspicious but legal patterns
+        #[arg(long)]
+        lint: bool,
+        /// Also run the program on the streaming interpreter and every optimizing engine,
+        /// failing if they don't end the same way
+        #[arg(long)]
+        differential: bool,
+        /// A file to use as input for `--differential`, can be given multiple times,
+        /// by default only running without input
+        #[arg(long = "input", value_name = "FILE", requires = "differential")]
+        inputs: Vec<String>,
+        /// The most instructions each run of `--differential` may execute
+        #[arg(long, default_value_t = 10_000_000, requires = "differential")]
+        fuel: u64,
+        /// The amount of cells the tape of `--differential` wraps around
+        #[arg(long, default_value = "30000", requires = "differential")]
+        cells: NonZeroUsize,
+        /// Make moving past either end of the tape of `--differential` an error instead
+        #[arg(long, requires = "differential")]
+        no_wrap: bool,
+        #[command(flatten)]
+        preprocess: PreprocessArgs,
+    },
+    /// Prints the optimized bytecode a program compiles to
+    Disasm {
+        /// Source code to disassemble
+        source: String,
+    },
+    /// Prints a program that prints the given text
+    GenText {
+        /// The text to print
+        text: std::ffi::OsString,
+    },
+    /// Prints a short snippet that sets the current cell from 0 to the given value,
+    /// using the cell to the right of it as a loop counter
+    GenConst {
+        /// The value to set the cell to
+        n: u8,
+    },
+    /// Lists the built-in example programs
+    Examples,
+    /// Prints statistics about a program
+    Metrics {
+        /// Source code to measure
+        source: String,
+    },
+    /// Prints a program without comments
+    Minify {
+        /// Source code to minify
+        source: String,
+        /// Also remove commands that cancel out and loops that never run
+        #[arg(long)]
+        shorten: bool,
+    },
+    /// Prints a program that behaves the same but is harder to read
+    Obfuscate {
+        /// Source code to obfuscate
+        source: String,
+        /// Seed for the changes made
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// The number of random inputs to check that the programs behave the same on
+        #[arg(long, default_value_t = 100)]
+        random: usize,
+        /// The most instructions each check may execute
+        #[arg(long, default_value_t = 10_000_000)]
+        fuel: u64,
+    },
+    /// Converts a program from one dialect to another
+    Translate {
+        /// Source code to translate
+        source: String,
+        /// The dialect the source is in
+        #[arg(long, value_name = "DIALECT", default_value = "bf")]
+        from: DialectArg,
+        /// The dialect to translate to
+        #[arg(long, value_name = "DIALECT", default_value = "bf")]
+        to: DialectArg,
+    },
+    /// Starts a language server communicating over stdin and stdout
+    Lsp,
+    /// Starts a debug adapter communicating over stdin and stdout
+    #[cfg(not(target_os = "wasi"))]
+    Dap,
+    /// Serves an HTTP API running submitted programs in sandboxes
+    #[cfg(not(target_os = "wasi"))]
+    Serve {
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// The number of programs that run at the same time
+        #[arg(long, default_value = "4")]
+        workers: NonZeroUsize,
+        /// The most instructions a program may execute
+        #[arg(long, default_value_t = 100_000_000)]
+        fuel: u64,
+        /// The most cells a program may use
+        #[arg(long, default_value = "30000")]
+        cells: NonZeroUsize,
+        /// The most bytes a program may write
+        #[arg(long, default_value_t = 1 << 20)]
+        output: usize,
+        /// The most milliseconds a program may run for
+        #[arg(long, default_value_t = 10_000)]
+        timeout: u64,
+    },
+    /// Checks that every way of running programs agrees on a suite of test programs
+    Selftest,
+    /// Runs every `name.bf` in a directory, with `name.in` as its input if there is one,
+    /// and checks that it prints `name.out` if there is one
+    Test {
+        /// The directory of tests
+        dir: PathBuf,
+        /// The most instructions each test may execute
+        #[arg(long, default_value_t = 100_000_000)]
+        fuel: u64,
+        /// The most cells each test may use
+        #[arg(long, default_value = "30000")]
+        cells: NonZeroUsize,
+        /// The most bytes each test may print
+        #[arg(long, default_value_t = 1 << 20)]
+        output: usize,
+        /// What `,` does once the input has ended
+        #[arg(long, value_name = "MODE", default_value = "error")]
+        eof: EofArg,
+        /// The most iterations any loop of a test may run in a row
+        #[arg(long, value_name = "N")]
+        max_loop_iterations: Option<NonZeroU64>,
+    },
+    /// Looks for inputs on which two programs behave differently
+    Equiv {
+        left: String,
+        right: String,
+        /// A file to use as input, can be given multiple times
+        #[arg(long = "input", value_name = "FILE")]
+        inputs: Vec<String>,
+        /// The number of random inputs to try
+        #[arg(long, default_value_t = 100)]
+        random: usize,
+        /// Seed for the random inputs
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// The most instructions each run may execute
+        #[arg(long, default_value_t = 10_000_000)]
+        fuel: u64,
+    },
+}
+
+fn run(mut cli: Cli) -> Result<ExitCode> {
+    match cli.command.take() {
+        Some(command) => run_command(command),
+        None => run_source(cli).map(|()| ExitCode::SUCCESS),
+    }
+}
+
+fn run_command(command: Commands) -> Result<ExitCode> {
+    match command {
+        Commands::Asm { source } => cli::asm::run(&source)?,
+        Commands::Check {
+            source,
+            lint,
+            differential,
+            inputs,
+            fuel,
+            cells,
+            no_wrap,
+            preprocess,
+        } => {
+            let differential = differential.then_some(cli::check::Differential {
+                inputs,
+                fuel,
+                cells,
+                wrap: !no_wrap,
+            });
+            cli::check::run(&source, lint, differential, &preprocess.preprocessor())?
+        }
+        Commands::Disasm { source } => cli::disasm::run(&source)?,
+        Commands::GenText { text } => cli::generate::text(&text)?,
+        Commands::GenConst { n } => cli::generate::constant(n)?,
+        Commands::Examples => cli::examples::list()?,
+        Commands::Metrics { source } => cli::metrics::run(&source)?,
+        Commands::Minify { source, shorten } => cli::minify::run(&source, shorten)?,
+        Commands::Obfuscate {
+            source,
+            seed,
+            random,
+            fuel,
+        } => return cli::obfuscate::run(&source, seed, random, fuel),
+        Commands::Translate { source, from, to } => {
+            cli::translate::run(&source, from.into(), to.into())?
+        }
+        Commands::Lsp => cli::lsp::run()?,
+        #[cfg(not(target_os = "wasi"))]
+        Commands::Dap => cli::dap::run()?,
+        #[cfg(not(target_os = "wasi"))]
+        Commands::Serve {
+            port,
+            host,
+            workers,
+            fuel,
+            cells,
+            output,
+            timeout,
+        } => cli::serve::run(cli::serve::Options {
+            host,
+            port,
+            workers,
+            quotas: brainfuck::sandbox::Limits {
+                fuel,
+                cells,
+                output,
+            },
+            timeout: Duration::from_millis(timeout),
+        })?,
+        Commands::Selftest => return cli::selftest::run(),
+        Commands::Test {
+            dir,
+            fuel,
+            cells,
+            output,
+            eof,
+            max_loop_iterations,
+        } => {
+            let limits = brainfuck::sandbox::Limits {
+                fuel,
+                cells,
+                output,
+            };
+            return cli::test::run(&dir, limits, eof.into(), max_loop_iterations);
+        }
+        Commands::Equiv {
+            left,
+            right,
+            inputs,
+            random,
+            seed,
+            fuel,
+        } => {
+            let options = cli::equiv::Options {
+                inputs,
+                random,
+                seed,
+                fuel,
+            };
+            return cli::equiv::run(&left, &right, options);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_source(cli: Cli) -> Result<()> {
+    if let Some(limit) = cli.limit.filter(|limit| cli.start_cell >= limit.get()) {
+        return Err(Failure::Source(format!(
+            "error: the start cell {} is past the {limit} cells of the tape\n",
+            cli.start_cell
+        )));
+    }
+    let builder = Interpreter::builder()
+        .cell_limit(cli.limit.map_or(0, NonZeroUsize::get))
+        .capacity(cli.reserve)
+        .start_cell(cli.start_cell)
+        .wrap(cli.wrap)
+        .eof(
+            cli.eof
+                .unwrap_or(match cli.example {
+                    Some(_) => EofArg::Unchanged,
+                    None => EofArg::Error,
+                })
+                .into(),
+        )
+        .uninit_reads(match cli.uninit_reads {
+            Level::Allow => UninitReads::Allow,
+            Level::Warn => UninitReads::Warn,
+            Level::Deny => UninitReads::Deny,
+        })
+        .input_limit(cli.max_input.map(|bytes| InputLimit {
+            bytes,
+            ends_input: cli.max_input_ends,
+        }))
+        .loop_limit(cli.max_loop_iterations)
+        .recover(cli.recover)
+        .opt_level(cli.opt_level.into())
+        .flush(match cli.flush {
+            FlushArg::Byte => Flush::Byte,
+            FlushArg::Line => Flush::Line,
+            FlushArg::Full => Flush::Full,
+        })
+        .newlines(cli.input_newlines.into(), cli.output_newlines.into());
+
+    let mut pipe = None;
+    let (input, output): (Input, Option<Output>) = match &cli.input_cmd {
+        Some(command) => {
+            let (started, input, output) = cli::pipe::Pipe::spawn(command, cli.output_to_cmd)?;
+            pipe = Some(started);
+            (input, output)
+        }
+        None => (Box::new(stdin()), None),
+    };
+    let output = output.unwrap_or_else(|| Box::new(stdout()));
+    let (input, output): (Input, Output) = match cli.base64 {
+        true => (
+            Box::new(cli::encode::Base64Decoder::new(input)),
+            Box::new(cli::encode::Base64Encoder::new(output)),
+        ),
+        false => (input, output),
+    };
+    let output: Output = match cli.output_format {
+        OutputFormatArg::Bytes => output,
+        OutputFormatArg::Hex => Box::new(cli::encode::Hex::new(output, false)),
+        OutputFormatArg::HexSpaced => Box::new(cli::encode::Hex::new(output, true)),
+    };
+    // Throttled last so the wait is between the bytes the program prints, however they're encoded
+    let interval = match (cli.delay, cli.bytes_per_second) {
+        (Some(ms), _) => Some(Duration::from_millis(ms)),
+        (_, Some(n)) => Some(Duration::from_secs(1) / n.get()),
+        (None, None) => None,
+    };
+    let output: Output = match interval {
+        Some(interval) => Box::new(cli::throttle::Throttle::new(output, interval)),
+        None => output,
+    };
+    let builder = builder.output(output).input(input);
+
+    // Kept until the program has ended, when the terminal is restored
+    #[cfg(unix)]
+    let _raw = match cli.raw_input {
+        true => cli::raw::RawInput::enable()?,
+        false => None,
+    };
+    #[cfg(not(unix))]
+    if cli.raw_input {
+        return Err(Failure::Source(
+            "error: --raw-input is only supported on Unix terminals\n".to_owned(),
+        ));
+    }
+
+    let result = match cli.cell {
+        CellArg::U8 => run_with::<Wrapping<u8>>(cli, builder),
+        CellArg::U16 => run_with::<Wrapping<u16>>(cli, builder),
+        CellArg::U32 => run_with::<Wrapping<u32>>(cli, builder),
+        CellArg::U64 => run_with::<Wrapping<u64>>(cli, builder),
+        CellArg::I8 => run_with::<Wrapping<i8>>(cli, builder),
+        CellArg::I16 => run_with::<Wrapping<i16>>(cli, builder),
+        CellArg::I32 => run_with::<Wrapping<i32>>(cli, builder),
+        CellArg::I64 => run_with::<Wrapping<i64>>(cli, builder),
+    };
+    if let Some(pipe) = pipe {
+        pipe.wait()?;
+    }
+    result
+}
+
+/// Where a program run from the command line writes to, stdout or `--input-cmd`
+type Output = Box<dyn Write>;
+/// Where a program run from the command line reads from, stdin or `--input-cmd`
+type Input = Box<dyn Read>;
+
+fn run_with<C: Cell>(cli: Cli, builder: InterpreterBuilder<Output, Input>) -> Result<()> {
+    let mut interpreter = builder.cell_type::<C>().build()?;
+
+    if cli.interactive {
+        let (state, io) = interpreter.parts_mut();
+        match &cli.script {
+            Some(path) => cli::repl::replay(state, io, path)?,
+            None => {
+                let persist = match &cli.persist {
+                    Some(Some(path)) => Some(path.clone()),
+                    Some(None) => Some(cli::repl::default_persist_path().ok_or_else(|| {
+                        Failure::Source(
+                            "error: there is no data directory for --persist, give it a file\n"
+                                .to_owned(),
+                        )
+                    })?),
+                    None => None,
+                };
+                let prompt = cli.prompt.clone().unwrap_or_default();
+                cli::repl::run(state, io, prompt, persist.as_deref())?
             }
-            println!();
         }
+        interpreter.evaluate().map(std::mem::drop)?;
     } else {
-        let src = cli.source.unwrap();
+        let preprocessor = cli.preprocess.preprocessor();
+        let (path, (src, expanded)) = match &cli.example {
+            Some(name) => {
+                let example = cli::examples::find(name)?;
+                let path = format!("example {name}");
+                let source = cli::expand_source(example.source.to_vec(), &path, &preprocessor)?;
+                (path, source)
+            }
+            None => {
+                let path = cli.source.clone().unwrap();
+                let source = cli::read_source(&path, &preprocessor)?;
+                (path, source)
+            }
+        };
+        let locate = |e: Error| cli::locate(e, &path, &src, &expanded);
+
+        if let (Some(inputs), Some(outputs)) = (&cli.inputs, &cli.outputs) {
+            let newlines = (cli.input_newlines.into(), cli.output_newlines.into());
+            return cli::batch::run(
+                &expanded.src,
+                interpreter.state(),
+                inputs,
+                outputs,
+                newlines,
+                |e| {
+                    let (e, path, src) = locate(e);
+                    Failure::in_source(e, path, src)
+                },
+            );
+        }
 
-        let file = BufReader::new(File::open(src).unwrap());
-        run_with_state(file, &mut state, &mut stdouter)?;
+        #[cfg(not(target_os = "wasi"))]
+        if let Some(port) = cli.listen {
+            cli::remote::listen(port, interpreter.state().control_handle())?;
+        }
+        #[cfg(unix)]
+        if cli.dump_on_signal {
+            cli::signal::watch(interpreter.state().control_handle())?;
+        }
+        let meter = Meter::start(interpreter.state());
+        #[cfg(feature = "render")]
+        if let Some(image) = &cli.render {
+            let program = Program::parse(&expanded.src).map_err(|e| {
+                let (e, path, src) = locate(e);
+                Failure::in_source(e, path, src)
+            })?;
+            let (state, io) = interpreter.parts_mut();
+            let result = cli::render::run(&program, state, io, cli.render_every.max(1), image);
+            io.flush()?;
+            return result.map_err(|e| {
+                let (e, path, src) = locate(e);
+                Failure::in_source(e, path, src)
+            });
+        }
+        let result = match (cli.animate, cli.tiered, &cli.trace) {
+            (_, _, Some(trace)) => File::create(trace).map_err(Error::from).and_then(|file| {
+                let program = Program::parse(&expanded.src)?;
+                let (state, io) = interpreter.parts_mut();
+                let result = cli::trace::run(&program, state, io, &mut BufWriter::new(file));
+                io.flush()?;
+                result
+            }),
+            _ if cli.explain.is_some() => Program::parse(&expanded.src).and_then(|program| {
+                let detail = match cli.explain.unwrap() {
+                    ExplainArg::Outer => cli::explain::Detail::Outer,
+                    ExplainArg::Loops => cli::explain::Detail::Loops,
+                    ExplainArg::Steps => cli::explain::Detail::Steps,
+                };
+                let (state, io) = interpreter.parts_mut();
+                cli::explain::run(&program, state, io, detail)
+            }),
+            _ if cli.heatmap => Program::parse(&expanded.src).and_then(|program| {
+                let (state, io) = interpreter.parts_mut();
+                let result = cli::heatmap::run(&program, state, io);
+                io.flush()?;
+                result
+            }),
+            _ if cli.summary => Program::parse(&expanded.src).and_then(|program| {
+                let (state, io) = interpreter.parts_mut();
+                cli::summary::run(&program, state, io)
+            }),
+            _ if cli.hash.is_some() => Program::parse(&expanded.src).and_then(|program| {
+                let action = match cli.hash.unwrap() {
+                    HashArg::Dump => cli::hash::Action::Dump,
+                    HashArg::Prompt => cli::hash::Action::Prompt,
+                };
+                let (state, io) = interpreter.parts_mut();
+                cli::hash::run(&program, &expanded.src, state, io, action)
+            }),
+            (Some(delay), _, _) => Program::parse(&expanded.src).and_then(|program| {
+                let options = cli::animate::Options {
+                    delay: Duration::from_millis(delay),
+                    every: cli.animate_every.max(1),
+                };
+                let (state, io) = interpreter.parts_mut();
+                cli::animate::run(&program, state, io, &options)
+            }),
+            (None, Some(threshold), _) => Program::parse(&expanded.src)
+                .and_then(|program| interpreter.run_tiered(&program, threshold))
+                .map(drop),
+            _ if cli.compiled => {
+                cli::cache::bytecode(&expanded.src, cli.opt_level.into(), !cli.no_cache)
+                    .and_then(|bytecode| interpreter.run_bytecode(&bytecode))
+                    .map(drop)
+            }
+            (None, None, _) => interpreter.run_slice(&expanded.src).map(drop),
+        };
+        if cli.stats {
+            eprintln!("{}", meter.finish(interpreter.state()));
+        }
+        if let Some(layout) = cli.dump_memory {
+            eprintln!("{}", interpreter.state().dump(layout.into()));
+        }
+        let warnings = interpreter.state_mut().take_warnings();
+        cli::print_warnings(warnings, |warning| {
+            let (warning, path, src) = locate(warning);
+            (warning, Some((path, src)))
+        });
+        let halt = cli::Halt::of(interpreter.state());
+        result
+            .and_then(|_| interpreter.evaluate().map(std::mem::drop))
+            .map_err(|e| {
+                let (e, path, src) = locate(e);
+                Failure::in_source(e, path, src).halted_at(halt)
+            })?;
     }
-    state.evaluate().map(std::mem::drop)
+
+    Ok(())
 }
 
 fn main() -> ExitCode {
-    match run() {
-        Ok(()) => return ExitCode::SUCCESS,
-        Err(IoError(e)) => eprintln!("Unexpected error:\n{e:?}"),
-        Err(Stopped) => eprintln!("Stopped"),
-        Err(OutOfBounds) => eprintln!("Error, out of bounds"),
-        Err(NoLoopStarted) => eprintln!("Error, cannot end a loop when none has been started"),
-        Err(UnendedLoop) => eprintln!("Error, ended with unended loops"),
-        Err(CellPointerOverflow) => eprintln!("Error, cell pointer overflowed limit"),
+    let cli = Cli::parse();
+    cli::set_color(match cli.color {
+        ColorArg::Auto => stderr().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        ColorArg::Always => true,
+        ColorArg::Never => false,
+    });
+    let format = match cli.error_format {
+        ErrorFormatArg::Human => cli::ErrorFormat::Human,
+        ErrorFormatArg::Json => cli::ErrorFormat::Json,
+    };
+    match run(cli) {
+        Ok(code) => code,
+        Err(failure) => {
+            failure.report_as(format);
+            ExitCode::FAILURE
+        }
     }
-
-    ExitCode::FAILURE
 }
@@ -0,0 +1,106 @@
+use core::fmt::{self, Display};
+
+use crate::{Command, Program};
+
+/// Static measurements of a `Program`, created by `Program::metrics`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    /// The number of commands
+    pub len: usize,
+    /// How many of each command there are, indexed by `Command as usize`
+    pub commands: [usize; 8],
+    pub loops: usize,
+    pub max_depth: usize,
+    /// The number of cells the program uses, if its pointer movement doesn't depend on
+    /// how often its loops run
+    pub tape_footprint: Option<usize>,
+}
+
+impl Metrics {
+    #[inline]
+    pub fn count(&self, cmd: Command) -> usize {
+        self.commands[cmd as usize]
+    }
+}
+
+const COMMANDS: [Command; 8] = [
+    Command::PtrIncr,
+    Command::PtrDecr,
+    Command::Incr,
+    Command::Decr,
+    Command::Out,
+    Command::In,
+    Command::LoopBegin,
+    Command::LoopEnd,
+];
+
+impl Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "commands       {}", self.len)?;
+        for cmd in COMMANDS {
+            writeln!(f, "  {cmd:?}            {}", self.count(cmd))?;
+        }
+        writeln!(f, "loops          {}", self.loops)?;
+        writeln!(f, "max depth      {}", self.max_depth)?;
+        match self.tape_footprint {
+            Some(cells) => writeln!(f, "tape footprint {cells} cells"),
+            None => writeln!(f, "tape footprint unknown"),
+        }
+    }
+}
+
+impl Program {
+    pub fn metrics(&self) -> Metrics {
+        let mut commands = [0; 8];
+        let mut loops = 0;
+        let mut depth = 0;
+        let mut max_depth = 0;
+
+        // Net pointer movement of each open loop's body so far
+        let mut drifts = alloc::vec::Vec::new();
+        let mut offset = 0isize;
+        let mut highest = 0isize;
+        let mut known = true;
+
+        for ins in self.instructions() {
+            let cmd = ins.command();
+            commands[cmd as usize] += 1;
+
+            match cmd {
+                Command::PtrIncr | Command::PtrDecr => {
+                    let step = if cmd == Command::PtrIncr { 1 } else { -1 };
+                    offset += step;
+                    highest = highest.max(offset);
+                    if let Some(drift) = drifts.last_mut() {
+                        *drift += step;
+                    }
+                }
+                Command::LoopBegin => {
+                    loops += 1;
+                    depth += 1;
+                    max_depth = max_depth.max(depth);
+                    drifts.push(0);
+                }
+                Command::LoopEnd => {
+                    depth -= 1;
+                    let drift = drifts.pop().unwrap_or_default();
+                    if drift != 0 {
+                        known = false;
+                    }
+                    if let Some(outer) = drifts.last_mut() {
+                        *outer += drift;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Metrics {
+            len: self.len(),
+            commands,
+            loops,
+            max_depth,
+            tape_footprint: known.then_some(highest as usize + 1),
+        }
+    }
+}
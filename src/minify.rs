@@ -0,0 +1,74 @@
+use alloc::vec::Vec;
+
+use crate::Command;
+
+/// Removes every character that isn't a command from `src`.
+///
+/// With `shorten`, it also removes commands that cancel each other out, like `+-` and `<>`,
+/// and loops that can never run because they are at the start of the program or
+/// right after another loop.
+/// The result behaves the same except that a cancelled `<>` or `><` no longer
+/// fails at the edge of the tape.
+pub fn minify(src: &[u8], shorten: bool) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::with_capacity(src.len());
+    for &byte in src {
+        if Command::from_byte(byte).is_none() {
+            continue;
+        }
+        if shorten && out.last().copied().and_then(opposite) == Some(byte) {
+            out.pop();
+        } else {
+            out.push(byte);
+        }
+    }
+
+    if shorten {
+        remove_dead_loops(&out)
+    } else {
+        out
+    }
+}
+
+fn opposite(byte: u8) -> Option<u8> {
+    match byte {
+        b'+' => Some(b'-'),
+        b'-' => Some(b'+'),
+        b'>' => Some(b'<'),
+        b'<' => Some(b'>'),
+        _ => None,
+    }
+}
+
+fn remove_dead_loops(src: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(src.len());
+    let mut i = 0;
+    while i < src.len() {
+        if src[i] == b'[' && matches!(out.last(), None | Some(b']')) {
+            if let Some(end) = matching_end(&src[i..]) {
+                i += end + 1;
+                continue;
+            }
+        }
+        out.push(src[i]);
+        i += 1;
+    }
+    out
+}
+
+/// The index of the `]` matching the `[` at the start of `src`
+fn matching_end(src: &[u8]) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, &byte) in src.iter().enumerate() {
+        match byte {
+            b'[' => depth += 1,
+            b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
@@ -0,0 +1,125 @@
+use alloc::vec::Vec;
+
+use crate::{equivalence::Rng, minify::minify, Command};
+
+/// Words to sprinkle in when the program has no comments of its own
+const WORDS: &[&[u8]] = &[
+    b"the", b"cell", b"loop", b"tape", b"value", b"pointer", b"move", b"add", b"keep", b"here",
+    b"then", b"again", b"store", b"carry", b"temp", b"done",
+];
+/// The shortest run of `+` or `-` that is turned into a loop
+const MIN_RUN: usize = 8;
+const LINE_WIDTH: usize = 72;
+
+/// Rewrites `src` into a program that behaves the same but is harder to read.
+/// The result is the same for the same `seed`.
+///
+/// The words of the comments are shuffled and scattered between the commands, pairs that
+/// cancel out, like `+-` and `><`, are inserted, and long runs of `+` or `-` at the start of
+/// the program are turned into multiplication loops using the cell to their right, where
+/// that cell is known to be zero.
+/// As with `minify`, the only difference in behaviour is that the extra `>` can fail
+/// at the edge of the tape.
+pub fn obfuscate(src: &[u8], seed: u64) -> Vec<u8> {
+    let mut rng = Rng::new(seed);
+    let commands = minify(src, false);
+
+    let mut words: Vec<&[u8]> = src
+        .split(|b| Command::from_byte(*b).is_some() || b.is_ascii_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        words.extend_from_slice(WORDS);
+    }
+
+    let mut code = Vec::with_capacity(commands.len() * 2);
+    let mut runs = Runs::default();
+    let mut i = 0;
+    while i < commands.len() {
+        let byte = commands[i];
+        let run = commands[i..].iter().take_while(|&&b| b == byte).count();
+        if matches!(byte, b'+' | b'-') && run >= MIN_RUN && runs.next_is_free() {
+            multiply(&mut code, &mut rng, byte, run);
+            runs.step(byte, run);
+            i += run;
+            continue;
+        }
+        runs.step(byte, 1);
+        code.push(byte);
+        match rng.below(6) {
+            0 => code.extend_from_slice(b"+-"),
+            1 => code.extend_from_slice(b"-+"),
+            2 => code.extend_from_slice(b"><"),
+            _ => (),
+        }
+        i += 1;
+    }
+
+    let mut out = Vec::with_capacity(code.len() * 2);
+    let mut line = 0;
+    for chunk in code.chunks(4) {
+        if rng.below(3) == 0 {
+            let w = words[rng.below(words.len())];
+            if line + w.len() + 1 > LINE_WIDTH {
+                out.push(b'\n');
+                line = 0;
+            } else if line > 0 {
+                out.push(b' ');
+                line += 1;
+            }
+            out.extend_from_slice(w);
+            out.push(b' ');
+            line += w.len() + 1;
+        }
+        if line + chunk.len() > LINE_WIDTH {
+            out.push(b'\n');
+            line = 0;
+        }
+        out.extend_from_slice(chunk);
+        line += chunk.len();
+    }
+    out.push(b'\n');
+    out
+}
+
+/// Writes `run` times `cmd` as a loop on the cell to the right, which has to be zero
+fn multiply(code: &mut Vec<u8>, rng: &mut Rng, cmd: u8, run: usize) {
+    let factor = 2 + rng.below((run / 2).min(16) - 1);
+    let repeat = |code: &mut Vec<u8>, byte, n| code.extend(core::iter::repeat_n(byte, n));
+
+    code.push(b'>');
+    repeat(code, b'+', factor);
+    code.push(b'[');
+    code.push(b'<');
+    repeat(code, cmd, run / factor);
+    code.extend_from_slice(b">-]<");
+    repeat(code, cmd, run % factor);
+}
+
+/// Follows the straight-line code at the start of a program to know which cells are still zero
+#[derive(Default)]
+struct Runs {
+    pointer: usize,
+    /// Cells that have been written to
+    touched: Vec<usize>,
+    /// Set once the cells can't be known anymore
+    lost: bool,
+}
+
+impl Runs {
+    fn next_is_free(&self) -> bool {
+        !self.lost && !self.touched.contains(&(self.pointer + 1))
+    }
+    fn step(&mut self, cmd: u8, n: usize) {
+        match cmd {
+            b'>' => self.pointer += n,
+            b'<' => match self.pointer.checked_sub(n) {
+                Some(pointer) => self.pointer = pointer,
+                None => self.lost = true,
+            },
+            b'+' | b'-' | b',' => self.touched.push(self.pointer),
+            b'[' | b']' => self.lost = true,
+            _ => (),
+        }
+    }
+}
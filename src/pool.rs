@@ -0,0 +1,130 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::sandbox::{End, Outcome, Sandbox};
+
+/// Runs many programs concurrently in sandboxes on a fixed number of worker threads,
+/// like a bot or web service evaluating submissions would
+///
+/// Every program is bounded by the limits of the `Sandbox` it's submitted with.
+/// Dropping the pool waits for the submitted programs to end.
+pub struct ExecutorPool {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+struct Job {
+    sandbox: Sandbox,
+    src: Vec<u8>,
+    input: Vec<u8>,
+    cancelled: Arc<AtomicBool>,
+    result: Sender<Outcome>,
+}
+
+impl ExecutorPool {
+    pub fn new(workers: NonZeroUsize) -> Self {
+        let (jobs, queue) = channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let workers = (0..workers.get())
+            .map(|_| {
+                let queue = queue.clone();
+                thread::spawn(move || loop {
+                    // The lock is only held while waiting for the next job
+                    let job = match queue.lock() {
+                        Ok(queue) => queue.recv(),
+                        Err(_) => return,
+                    };
+                    let Ok(job) = job else { return };
+                    let outcome = job
+                        .sandbox
+                        .run_cancellable(&job.src, &job.input, &job.cancelled);
+                    // The handle may have been dropped, which is fine
+                    let _ = job.result.send(outcome);
+                })
+            })
+            .collect();
+        ExecutorPool {
+            jobs: Some(jobs),
+            workers,
+        }
+    }
+    /// Queues `src` to run in `sandbox` with `input` on the next free worker
+    pub fn submit(&self, sandbox: Sandbox, src: Vec<u8>, input: Vec<u8>) -> JobHandle {
+        let (result, receiver) = channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let job = Job {
+            sandbox,
+            src,
+            input,
+            cancelled: cancelled.clone(),
+            result,
+        };
+        if let Some(jobs) = &self.jobs {
+            // Only fails if every worker is gone, in which case the handle reports cancellation
+            let _ = jobs.send(job);
+        }
+        JobHandle {
+            result: receiver,
+            cancelled,
+        }
+    }
+}
+
+impl Drop for ExecutorPool {
+    fn drop(&mut self) {
+        // Closing the queue makes the workers stop once it's empty
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A program submitted to an `ExecutorPool`
+pub struct JobHandle {
+    result: Receiver<Outcome>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    /// Stops the program, or keeps it from starting if it's still queued
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+    /// Waits for the program to end
+    pub fn wait(self) -> Outcome {
+        self.result.recv().unwrap_or_else(|_| cancelled())
+    }
+    /// Waits at most `timeout` for the program to end
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<Outcome> {
+        match self.result.recv_timeout(timeout) {
+            Ok(outcome) => Some(outcome),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => Some(cancelled()),
+        }
+    }
+    /// The outcome if the program has ended
+    pub fn try_result(&self) -> Option<Outcome> {
+        match self.result.try_recv() {
+            Ok(outcome) => Some(outcome),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(cancelled()),
+        }
+    }
+}
+
+fn cancelled() -> Outcome {
+    Outcome {
+        output: Vec::new(),
+        end: End::Cancelled,
+        instructions: 0,
+    }
+}
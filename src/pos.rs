@@ -0,0 +1,46 @@
+use core::fmt::{self, Display};
+
+/// A location in a program's source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Position {
+    /// Byte offset from the start of the source
+    pub offset: usize,
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column, counted in bytes
+    pub column: usize,
+}
+
+impl Position {
+    pub const START: Self = Position {
+        offset: 0,
+        line: 1,
+        column: 1,
+    };
+
+    /// Moves the position past `byte`
+    #[inline]
+    pub fn advance(&mut self, byte: u8) {
+        self.offset += 1;
+        if byte == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+impl Default for Position {
+    #[inline]
+    fn default() -> Self {
+        Self::START
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
@@ -0,0 +1,517 @@
+use alloc::{
+    borrow::ToOwned,
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Display};
+
+use crate::Position;
+
+/// Expands opt-in extensions of the source language into plain brainfuck
+///
+/// Macros are defined with `@define name { body }` and invoked with `@name`.
+/// A macro can invoke macros defined before it is invoked, but not itself.
+///
+/// With counts, a decimal number before a command repeats it, so `12+` is `++++++++++++`.
+///
+/// With includes, `@include "file.bf"` is replaced by the expansion of that file,
+/// found relative to the file that includes it. Macros defined in an included file
+/// can be invoked after the `@include`. Files can only be read with the `std` feature.
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct Preprocessor {
+    macros: bool,
+    counts: bool,
+    includes: bool,
+}
+
+/// The largest repetition count allowed
+pub const MAX_COUNT: usize = 1 << 20;
+/// The most commands that repetition counts may expand to in total
+pub const MAX_REPEATED: usize = 1 << 22;
+
+/// Plain brainfuck produced by a `Preprocessor`, with a map back to the original source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expanded {
+    pub src: Vec<u8>,
+    /// Where each byte of `src` came from, as an index into `files` and a position in it
+    origins: Vec<(usize, Position)>,
+    /// The path and contents of every included file, after the source itself
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl Expanded {
+    /// The position that `pos` in the expanded source came from.
+    /// Code expanded from a macro comes from where the macro was invoked.
+    ///
+    /// If the code came from an included file, the position is in that file, see `Expanded::file`.
+    pub fn origin(&self, pos: Position) -> Position {
+        self.origins.get(pos.offset).map_or(pos, |&(_, pos)| pos)
+    }
+    /// The path and contents of the included file that `pos` in the expanded source came from,
+    /// or `None` if it came from the source itself
+    pub fn file(&self, pos: Position) -> Option<(&str, &[u8])> {
+        match self.origins.get(pos.offset) {
+            Some(&(file, _)) if file > 0 => {
+                let (path, src) = &self.files[file - 1];
+                Some((path, src))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreprocessErrorKind {
+    /// `@` wasn't followed by a name
+    ExpectedName,
+    /// A macro definition without a `{ body }`
+    ExpectedBody,
+    /// A macro body without a closing `}`
+    UnterminatedBody,
+    NestedDefinition,
+    UnknownMacro(String),
+    Redefined(String),
+    /// A macro that ends up invoking itself
+    Recursive(String),
+    /// A repetition count above `MAX_COUNT`,
+    /// or one that makes counts expand to more than `MAX_REPEATED` commands
+    CountTooLarge,
+    /// `@include` wasn't followed by a quoted path
+    ExpectedPath,
+    IncludeFailed {
+        path: String,
+        reason: String,
+    },
+    /// A file that ends up including itself
+    CyclicInclude(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessError {
+    pub kind: PreprocessErrorKind,
+    pub position: Position,
+    /// The included file the error is in, or `None` if it is in the source itself
+    pub file: Option<String>,
+}
+
+impl Display for PreprocessErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreprocessErrorKind::ExpectedName => write!(f, "expected a name after `@`"),
+            PreprocessErrorKind::ExpectedBody => write!(f, "expected `{{` to start the macro body"),
+            PreprocessErrorKind::UnterminatedBody => write!(f, "macro body is never closed"),
+            PreprocessErrorKind::NestedDefinition => {
+                write!(f, "macros cannot be defined inside other macros")
+            }
+            PreprocessErrorKind::UnknownMacro(name) => write!(f, "no macro named `{name}`"),
+            PreprocessErrorKind::Redefined(name) => {
+                write!(f, "macro `{name}` is already defined")
+            }
+            PreprocessErrorKind::Recursive(name) => write!(f, "macro `{name}` invokes itself"),
+            PreprocessErrorKind::CountTooLarge => write!(f, "repetition count is too large"),
+            PreprocessErrorKind::ExpectedPath => {
+                write!(f, "expected a quoted path after `@include`")
+            }
+            PreprocessErrorKind::IncludeFailed { path, reason } => {
+                write!(f, "could not include `{path}`: {reason}")
+            }
+            PreprocessErrorKind::CyclicInclude(path) => write!(f, "`{path}` includes itself"),
+        }
+    }
+}
+
+impl Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at ", self.kind)?;
+        if let Some(file) = &self.file {
+            write!(f, "{file}:")?;
+        }
+        write!(f, "{}", self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PreprocessError {}
+
+type PResult<T> = Result<T, PreprocessError>;
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn macros(mut self, macros: bool) -> Self {
+        self.macros = macros;
+        self
+    }
+    pub fn counts(mut self, counts: bool) -> Self {
+        self.counts = counts;
+        self
+    }
+    pub fn includes(mut self, includes: bool) -> Self {
+        self.includes = includes;
+        self
+    }
+    /// Expands `src`, finding included files relative to the current directory
+    pub fn expand(&self, src: &[u8]) -> PResult<Expanded> {
+        self.expand_at(src, "")
+    }
+    /// Expands `src` which was read from `path`, finding included files relative to it
+    pub fn expand_at(&self, src: &[u8], path: &str) -> PResult<Expanded> {
+        let mut expander = Expander {
+            options: self,
+            files: Vec::new(),
+            macros: Vec::new(),
+            stack: Vec::new(),
+            including: Vec::new(),
+            src: Vec::with_capacity(src.len()),
+            origins: Vec::with_capacity(src.len()),
+            repeated: 0,
+        };
+        expander.include(path.to_owned(), key(path), src.into(), None)?;
+        Ok(Expanded {
+            src: expander.src,
+            origins: expander.origins,
+            files: expander
+                .files
+                .into_iter()
+                .skip(1)
+                .map(|file| (file.path, file.src.to_vec()))
+                .collect(),
+        })
+    }
+}
+
+/// A place in one of the files being expanded
+#[derive(Debug, Clone, Copy)]
+struct Origin {
+    file: usize,
+    position: Position,
+}
+
+struct File {
+    path: String,
+    /// What the file is compared by to detect cycles
+    key: String,
+    src: Rc<[u8]>,
+}
+
+struct Macro {
+    name: Vec<u8>,
+    /// The body as a range of a file and the position it starts at
+    file: usize,
+    start: usize,
+    end: usize,
+    position: Position,
+}
+
+struct Expander<'o> {
+    options: &'o Preprocessor,
+    files: Vec<File>,
+    macros: Vec<Macro>,
+    /// Names of the macros being expanded
+    stack: Vec<Vec<u8>>,
+    /// Files being expanded
+    including: Vec<usize>,
+    src: Vec<u8>,
+    origins: Vec<(usize, Position)>,
+    /// How many commands repetition counts have expanded to
+    repeated: usize,
+}
+
+impl Expander<'_> {
+    fn include(
+        &mut self,
+        path: String,
+        key: String,
+        src: Rc<[u8]>,
+        call_site: Option<Origin>,
+    ) -> PResult<()> {
+        let file = self.files.len();
+        let end = src.len();
+        self.files.push(File { path, key, src });
+        self.including.push(file);
+        self.expand(file, 0, end, Position::START, call_site)?;
+        self.including.pop();
+        Ok(())
+    }
+    /// Expands `start..end` of `file`, which starts at `pos`.
+    /// Inside a macro, `call_site` is where the outermost macro was invoked.
+    fn expand(
+        &mut self,
+        file: usize,
+        start: usize,
+        end: usize,
+        mut pos: Position,
+        call_site: Option<Origin>,
+    ) -> PResult<()> {
+        let src = Rc::clone(&self.files[file].src);
+        let src = &src[..end];
+        let here = |position| Origin { file, position };
+        let mut i = start;
+        while i < end {
+            let byte = src[i];
+            if byte == b'@' && (self.options.macros || self.options.includes) {
+                let at = here(pos);
+                let (name, after) = name(src, i + 1)
+                    .ok_or_else(|| self.error(PreprocessErrorKind::ExpectedName, at))?;
+                pos = advance(pos, &src[i..after]);
+                i = after;
+                match name {
+                    b"include" if self.options.includes => {
+                        (i, pos) = self.include_file(file, src, i, pos, call_site)?;
+                    }
+                    b"define" if self.options.macros => {
+                        if call_site.is_some() {
+                            return Err(self.error(PreprocessErrorKind::NestedDefinition, at));
+                        }
+                        (i, pos) = self.define(file, src, i, pos)?;
+                    }
+                    _ if self.options.macros => self.invoke(name, at, call_site.unwrap_or(at))?,
+                    _ => {
+                        // Without macros only `@include` means anything
+                        let origin = call_site.unwrap_or(at);
+                        self.src.extend_from_slice(&src[at.position.offset..i]);
+                        self.origins.extend(
+                            src[at.position.offset..i]
+                                .iter()
+                                .map(|_| (origin.file, origin.position)),
+                        );
+                    }
+                }
+                continue;
+            }
+            if byte.is_ascii_digit() && self.options.counts {
+                (i, pos) = self.repeat(file, src, i, pos, call_site)?;
+                continue;
+            }
+            let origin = call_site.unwrap_or(here(pos));
+            self.src.push(byte);
+            self.origins.push((origin.file, origin.position));
+            pos.advance(byte);
+            i += 1;
+        }
+        Ok(())
+    }
+    /// Expands the count starting at `i` and the command after it.
+    /// Digits that aren't followed by a command are left as they are.
+    fn repeat(
+        &mut self,
+        file: usize,
+        src: &[u8],
+        i: usize,
+        pos: Position,
+        call_site: Option<Origin>,
+    ) -> PResult<(usize, Position)> {
+        let digits = src[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+        let command = i + digits;
+        let count = match src.get(command) {
+            Some(b'+' | b'-' | b'<' | b'>' | b'.' | b',' | b'[' | b']') => src[i..command]
+                .iter()
+                .try_fold(0usize, |n, d| {
+                    Some(n.checked_mul(10)?.checked_add(usize::from(d - b'0'))?)
+                        .filter(|&n| n <= MAX_COUNT)
+                })
+                .filter(|&count| self.repeated + count <= MAX_REPEATED)
+                .ok_or_else(|| {
+                    let at = Origin {
+                        file,
+                        position: pos,
+                    };
+                    self.error(PreprocessErrorKind::CountTooLarge, at)
+                })?,
+            _ => {
+                let mut pos = pos;
+                for &byte in &src[i..command] {
+                    let origin = call_site.unwrap_or(Origin {
+                        file,
+                        position: pos,
+                    });
+                    self.src.push(byte);
+                    self.origins.push((origin.file, origin.position));
+                    pos.advance(byte);
+                }
+                return Ok((command, pos));
+            }
+        };
+        let origin = call_site.unwrap_or(Origin {
+            file,
+            position: advance(pos, &src[i..command]),
+        });
+        self.repeated += count;
+        self.src.extend(core::iter::repeat_n(src[command], count));
+        self.origins
+            .extend(core::iter::repeat_n((origin.file, origin.position), count));
+        Ok((command + 1, advance(pos, &src[i..=command])))
+    }
+    fn define(
+        &mut self,
+        file: usize,
+        src: &[u8],
+        mut i: usize,
+        mut pos: Position,
+    ) -> PResult<(usize, Position)> {
+        let here = |position| Origin { file, position };
+        let skipped = skip_whitespace(src, i);
+        pos = advance(pos, &src[i..skipped]);
+        i = skipped;
+        let name_pos = pos;
+        let (name, after) = name(src, i)
+            .ok_or_else(|| self.error(PreprocessErrorKind::ExpectedName, here(name_pos)))?;
+        pos = advance(pos, &src[i..after]);
+        i = skip_whitespace(src, after);
+        pos = advance(pos, &src[after..i]);
+
+        if src.get(i) != Some(&b'{') {
+            return Err(self.error(PreprocessErrorKind::ExpectedBody, here(pos)));
+        }
+        let open = pos;
+        let mut depth = 0usize;
+        let close = (i..src.len()).find(|&j| {
+            match src[j] {
+                b'{' => depth += 1,
+                b'}' => depth -= 1,
+                _ => (),
+            }
+            depth == 0
+        });
+        let Some(close) = close else {
+            return Err(self.error(PreprocessErrorKind::UnterminatedBody, here(open)));
+        };
+
+        if self.macros.iter().any(|m| m.name == name) {
+            return Err(self.error(
+                PreprocessErrorKind::Redefined(String::from_utf8_lossy(name).into_owned()),
+                here(name_pos),
+            ));
+        }
+        let mut body_pos = open;
+        body_pos.advance(b'{');
+        self.macros.push(Macro {
+            name: name.to_vec(),
+            file,
+            start: i + 1,
+            end: close,
+            position: body_pos,
+        });
+
+        Ok((close + 1, advance(pos, &src[i..=close])))
+    }
+    fn invoke(&mut self, name: &[u8], at: Origin, call_site: Origin) -> PResult<()> {
+        let lossy = || String::from_utf8_lossy(name).into_owned();
+        let Some(m) = self.macros.iter().find(|m| m.name == name) else {
+            return Err(self.error(PreprocessErrorKind::UnknownMacro(lossy()), at));
+        };
+        if self.stack.iter().any(|n| n == name) {
+            return Err(self.error(PreprocessErrorKind::Recursive(lossy()), at));
+        }
+        let (file, start, end, position) = (m.file, m.start, m.end, m.position);
+        self.stack.push(name.to_vec());
+        self.expand(file, start, end, position, Some(call_site))?;
+        self.stack.pop();
+        Ok(())
+    }
+    /// Reads the path after an `@include` in `file` and expands the file it names
+    fn include_file(
+        &mut self,
+        file: usize,
+        src: &[u8],
+        mut i: usize,
+        mut pos: Position,
+        call_site: Option<Origin>,
+    ) -> PResult<(usize, Position)> {
+        let here = |position| Origin { file, position };
+        let skipped = skip_whitespace(src, i);
+        pos = advance(pos, &src[i..skipped]);
+        i = skipped;
+        let at = here(pos);
+
+        let path = match src.get(i) {
+            Some(b'"') => src[i + 1..].iter().position(|&b| b == b'"' || b == b'\n'),
+            _ => None,
+        };
+        let path = match path {
+            Some(len) if src[i + 1 + len] == b'"' => &src[i + 1..i + 1 + len],
+            _ => return Err(self.error(PreprocessErrorKind::ExpectedPath, at)),
+        };
+        let after = i + path.len() + 2;
+        let path = String::from_utf8_lossy(path).into_owned();
+
+        let (path, key, contents) = load(&self.files[file].path, &path).map_err(|reason| {
+            self.error(PreprocessErrorKind::IncludeFailed { path, reason }, at)
+        })?;
+        if self
+            .including
+            .iter()
+            .any(|&including| self.files[including].key == key)
+        {
+            return Err(self.error(PreprocessErrorKind::CyclicInclude(path), at));
+        }
+        self.include(path, key, contents.into(), call_site)?;
+
+        Ok((after, advance(pos, &src[i..after])))
+    }
+    fn error(&self, kind: PreprocessErrorKind, at: Origin) -> PreprocessError {
+        PreprocessError {
+            kind,
+            position: at.position,
+            file: (at.file > 0).then(|| self.files[at.file].path.clone()),
+        }
+    }
+}
+
+/// Reads the file at `path` relative to the file `from`,
+/// returning its path, what to compare it by and its contents
+#[cfg(feature = "std")]
+fn load(from: &str, path: &str) -> Result<(String, String, Vec<u8>), String> {
+    use std::path::Path;
+
+    let path = Path::new(from).parent().unwrap_or(Path::new("")).join(path);
+    let contents = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let path = path.to_string_lossy().into_owned();
+    let key = key(&path);
+    Ok((path, key, contents))
+}
+
+/// What the file at `path` is compared by, which is the same however the path is written
+#[cfg(feature = "std")]
+fn key(path: &str) -> String {
+    match std::path::Path::new(path).canonicalize() {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(_) => path.to_owned(),
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn key(path: &str) -> String {
+    path.to_owned()
+}
+
+#[cfg(not(feature = "std"))]
+fn load(_from: &str, _path: &str) -> Result<(String, String, Vec<u8>), String> {
+    Err("files cannot be read without the `std` feature".to_string())
+}
+
+/// Reads a name starting at `i`, returning it and the index after it
+fn name(src: &[u8], i: usize) -> Option<(&[u8], usize)> {
+    let len = src[i..]
+        .iter()
+        .take_while(|b| b.is_ascii_alphanumeric() || **b == b'_')
+        .count();
+    (len > 0).then(|| (&src[i..i + len], i + len))
+}
+
+fn advance(mut pos: Position, bytes: &[u8]) -> Position {
+    for &byte in bytes {
+        pos.advance(byte);
+    }
+    pos
+}
+
+fn skip_whitespace(src: &[u8], mut i: usize) -> usize {
+    while src.get(i).is_some_and(u8::is_ascii_whitespace) {
+        i += 1;
+    }
+    i
+}
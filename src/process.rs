@@ -0,0 +1,193 @@
+use std::{
+    io::{self, Read, Write},
+    num::Wrapping,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{BfIo, Cell, ControlHandle, Error, Program, Result, State};
+
+/// How often a program waiting for input checks whether it has been killed
+const KILL_POLL: Duration = Duration::from_millis(10);
+
+/// A program running on its own thread with pipes for its input and output,
+/// which can be handled like a child process
+///
+/// Like with a child process, dropping it leaves the program running.
+pub struct BfProcess<C: Cell = Wrapping<u8>> {
+    stdin: Option<ProcessStdin>,
+    stdout: Option<ProcessStdout>,
+    killed: Arc<AtomicBool>,
+    handle: ControlHandle,
+    thread: JoinHandle<Exit<C>>,
+}
+
+/// How a `BfProcess` ended
+#[derive(Debug, Clone)]
+pub struct Exit<C: Cell = Wrapping<u8>> {
+    pub state: State<C>,
+    /// `Err(Error::Stopped)` if it was killed
+    pub result: Result<()>,
+}
+
+impl<C: Cell> Exit<C> {
+    #[inline]
+    pub fn success(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl<C: Cell> BfProcess<C>
+where
+    C: Send + 'static,
+{
+    /// Starts running `program` on `state`
+    pub fn spawn(program: Program, mut state: State<C>) -> Self {
+        let (stdin, input) = channel();
+        let (output, stdout) = channel();
+        let killed = Arc::new(AtomicBool::new(false));
+        let handle = state.control_handle();
+
+        let mut io = ProcessIo {
+            input,
+            output,
+            killed: killed.clone(),
+        };
+        let thread = thread::spawn(move || {
+            let result = run(&program, &mut state, &mut io);
+            Exit { state, result }
+        });
+
+        BfProcess {
+            stdin: Some(ProcessStdin { sender: stdin }),
+            stdout: Some(ProcessStdout { receiver: stdout }),
+            killed,
+            handle,
+            thread,
+        }
+    }
+    /// Takes the pipe to the program's input, which ends its input once dropped.
+    /// `None` if it has already been taken.
+    #[inline]
+    pub fn stdin(&mut self) -> Option<ProcessStdin> {
+        self.stdin.take()
+    }
+    /// Takes the pipe from the program's output, which ends once the program has.
+    /// `None` if it has already been taken.
+    #[inline]
+    pub fn stdout(&mut self) -> Option<ProcessStdout> {
+        self.stdout.take()
+    }
+    /// For pausing the program and seeing how far it has come
+    #[inline]
+    pub fn control_handle(&self) -> &ControlHandle {
+        &self.handle
+    }
+    /// Stops the program, even if it's waiting for input or paused
+    pub fn kill(&self) {
+        self.killed.store(true, Ordering::SeqCst);
+        self.handle.stop();
+    }
+    /// Whether the program has ended
+    #[inline]
+    pub fn has_exited(&self) -> bool {
+        self.thread.is_finished()
+    }
+    /// Waits for the program to end.
+    /// Its input is ended first if the pipe to it hasn't been taken, so it doesn't wait for it.
+    pub fn wait(mut self) -> Exit<C> {
+        drop(self.stdin.take());
+        self.thread.join().expect("process thread panicked")
+    }
+}
+
+fn run<C: Cell>(program: &Program, state: &mut State<C>, io: &mut ProcessIo) -> Result<()> {
+    let mut pc = 0;
+    while pc < program.len() {
+        // Checked here too since the first step starts the run over,
+        // which would undo a kill that came before it
+        if io.killed.load(Ordering::Relaxed) {
+            return Err(Error::Stopped);
+        }
+        pc = program.step(state, pc, io)?;
+    }
+    Ok(())
+}
+
+struct ProcessIo {
+    input: Receiver<u8>,
+    output: Sender<u8>,
+    killed: Arc<AtomicBool>,
+}
+
+impl BfIo for ProcessIo {
+    fn read(&mut self) -> Result<Option<u8>> {
+        loop {
+            match self.input.recv_timeout(KILL_POLL) {
+                Ok(byte) => return Ok(Some(byte)),
+                Err(RecvTimeoutError::Disconnected) => return Ok(None),
+                Err(RecvTimeoutError::Timeout) if self.killed.load(Ordering::Relaxed) => {
+                    return Err(Error::Stopped)
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+            }
+        }
+    }
+    fn write(&mut self, byte: u8) -> Result<()> {
+        self.output
+            .send(byte)
+            .map_err(|_| Error::Io("the output pipe was closed"))
+    }
+}
+
+/// The pipe to the input of a `BfProcess`
+pub struct ProcessStdin {
+    sender: Sender<u8>,
+}
+
+impl Write for ProcessStdin {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.sender
+                .send(byte)
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the program has ended"))?;
+        }
+        Ok(buf.len())
+    }
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The pipe from the output of a `BfProcess`
+pub struct ProcessStdout {
+    receiver: Receiver<u8>,
+}
+
+impl Read for ProcessStdout {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some((first, rest)) = buf.split_first_mut() else {
+            return Ok(0);
+        };
+        // Waits for the first byte, which fails once the program has ended and it's all been read
+        let Ok(byte) = self.receiver.recv() else {
+            return Ok(0);
+        };
+        *first = byte;
+        let mut n = 1;
+        for slot in rest {
+            match self.receiver.try_recv() {
+                Ok(byte) => *slot = byte,
+                Err(_) => break,
+            }
+            n += 1;
+        }
+        Ok(n)
+    }
+}
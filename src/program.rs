@@ -0,0 +1,378 @@
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+#[cfg(feature = "metrics")]
+use crate::telemetry;
+use crate::{BfIo, Cell, Command, EndReason, Error, Position, Result, RunReport, State};
+
+/// A single executable instruction of a `Program`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Instruction {
+    PtrIncr,
+    PtrDecr,
+    Incr,
+    Decr,
+    Out,
+    In,
+    /// `[`, jumping to the matching `]` if the current cell is zero
+    LoopBegin(usize),
+    /// `]`, jumping back to the matching `[` if the current cell isn't zero
+    LoopEnd(usize),
+}
+
+impl Instruction {
+    /// The command this instruction was parsed from
+    pub fn command(self) -> Command {
+        match self {
+            Instruction::PtrIncr => Command::PtrIncr,
+            Instruction::PtrDecr => Command::PtrDecr,
+            Instruction::Incr => Command::Incr,
+            Instruction::Decr => Command::Decr,
+            Instruction::Out => Command::Out,
+            Instruction::In => Command::In,
+            Instruction::LoopBegin(_) => Command::LoopBegin,
+            Instruction::LoopEnd(_) => Command::LoopEnd,
+        }
+    }
+}
+
+/// A parsed program with matched loops, which can be executed one instruction at a time
+///
+/// Deserializing a program fails unless its loops jump to each other like matching brackets.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "UncheckedProgram")
+)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    positions: Vec<Position>,
+}
+
+/// A `Program` as it's deserialized, before its loops are checked
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct UncheckedProgram {
+    instructions: Vec<Instruction>,
+    positions: Vec<Position>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedProgram> for Program {
+    type Error = &'static str;
+
+    fn try_from(program: UncheckedProgram) -> core::result::Result<Self, Self::Error> {
+        let UncheckedProgram {
+            instructions,
+            positions,
+        } = program;
+        if positions.len() != instructions.len() {
+            return Err("a program needs a position for every instruction");
+        }
+        let mut loop_starts = Vec::new();
+        for (i, &instruction) in instructions.iter().enumerate() {
+            match instruction {
+                Instruction::LoopBegin(end) => {
+                    if end <= i || instructions.get(end) != Some(&Instruction::LoopEnd(i)) {
+                        return Err("a loop doesn't jump to its end");
+                    }
+                    loop_starts.push(i);
+                }
+                Instruction::LoopEnd(start) if loop_starts.pop() != Some(start) => {
+                    return Err("a loop doesn't jump back to its start");
+                }
+                _ => (),
+            }
+        }
+        if !loop_starts.is_empty() {
+            return Err("a loop is never ended");
+        }
+        Ok(Program {
+            instructions,
+            positions,
+        })
+    }
+}
+
+/// Programs made of arbitrary commands, with brackets balanced like `FuzzCase::from_bytes` does
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Program {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let commands: Vec<Command> = u.arbitrary()?;
+        let src = crate::fuzz::balanced(commands.into_iter().map(Command::to_byte));
+        // Balanced commands always parse
+        Program::parse(&src).map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl Program {
+    /// Parses `src`, failing if a loop is either never started or never ended
+    pub fn parse(src: &[u8]) -> Result<Self> {
+        let mut instructions = Vec::new();
+        let mut positions = Vec::new();
+        let mut loop_starts = Vec::new();
+
+        let mut pos = Position::START;
+        for &byte in src {
+            if let Some(cmd) = Command::from_byte(byte) {
+                let instruction = match cmd {
+                    Command::PtrIncr => Instruction::PtrIncr,
+                    Command::PtrDecr => Instruction::PtrDecr,
+                    Command::Incr => Instruction::Incr,
+                    Command::Decr => Instruction::Decr,
+                    Command::Out => Instruction::Out,
+                    Command::In => Instruction::In,
+                    Command::LoopBegin => {
+                        loop_starts.push(instructions.len());
+                        // Patched once the end is found
+                        Instruction::LoopBegin(0)
+                    }
+                    Command::LoopEnd => {
+                        let start = loop_starts.pop().ok_or(Error::NoLoopStarted(pos))?;
+                        instructions[start] = Instruction::LoopBegin(instructions.len());
+                        Instruction::LoopEnd(start)
+                    }
+                };
+                instructions.push(instruction);
+                positions.push(pos);
+            }
+            pos.advance(byte);
+        }
+
+        if loop_starts.is_empty() {
+            Ok(Program {
+                instructions,
+                positions,
+            })
+        } else {
+            Err(Error::UnendedLoop(
+                loop_starts.into_iter().map(|i| positions[i]).collect(),
+            ))
+        }
+    }
+    #[inline]
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+    /// The source position of the instruction at `pc`
+    #[inline]
+    pub fn position(&self, pc: usize) -> Option<Position> {
+        self.positions.get(pc).copied()
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+    /// Runs the program lazily, producing output bytes as they are written.
+    ///
+    /// `,` reads from `input`, which behaves like an ended input once it runs out.
+    pub fn outputs<'a, C, I>(
+        &'a self,
+        state: &'a mut State<C>,
+        input: I,
+    ) -> Outputs<'a, C, I::IntoIter>
+    where
+        C: Cell,
+        I: IntoIterator<Item = u8>,
+    {
+        state.start();
+        Outputs {
+            program: self,
+            state,
+            io: Capture {
+                input: input.into_iter(),
+                output: None,
+            },
+            pc: 0,
+            failed: false,
+        }
+    }
+    /// Executes the instruction at `pc` and returns the index of the next instruction to execute,
+    /// which is `self.len()` once the program has ended.
+    ///
+    /// Stepping the first instruction starts a new run, so a stopped state can run again.
+    ///
+    /// Panics if `pc` is not below `self.len()`.
+    pub fn step<C: Cell, I: BfIo>(
+        &self,
+        state: &mut State<C>,
+        pc: usize,
+        io: &mut I,
+    ) -> Result<usize> {
+        if pc == 0 {
+            state.start();
+        }
+        state.instructions += 1;
+        state.position = self.positions[pc];
+        state.pc = Some(pc);
+        match self.instructions[pc] {
+            Instruction::PtrIncr => state.pointer_add()?,
+            Instruction::PtrDecr => state.pointer_sub()?,
+            Instruction::Incr => state.get_mut_cur().increment(),
+            Instruction::Decr => state.get_mut_cur().decrement(),
+            Instruction::Out => state.output(io)?,
+            Instruction::In => {
+                let result = state.input(io);
+                if state.ended_by_input(result)? {
+                    return Ok(self.len());
+                }
+            }
+            Instruction::LoopBegin(end) => {
+                if state.read_cur()?.is_zero() {
+                    return Ok(end + 1);
+                }
+                state.loop_entered(self.positions[pc]);
+            }
+            Instruction::LoopEnd(start) => {
+                if !state.read_cur()?.is_zero() {
+                    state.loop_repeated(self.positions[start])?;
+                    state.checkpoint()?;
+                    return Ok(start + 1);
+                }
+                state.loop_exited();
+            }
+        }
+
+        Ok(pc + 1)
+    }
+    /// Runs the whole program, calling `tracer` with the index of each instruction
+    /// and the state just before it is executed
+    pub fn run_traced<C, I, F>(&self, state: &mut State<C>, io: &mut I, mut tracer: F) -> Result<()>
+    where
+        C: Cell,
+        I: BfIo,
+        F: FnMut(usize, Instruction, &State<C>),
+    {
+        state.start();
+        let mut pc = 0;
+        while pc < self.len() {
+            tracer(pc, self.instructions[pc], state);
+            pc = self.step(state, pc, io)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the source with `Program::parse`, so that `src.parse::<Program>()?` works.
+/// The error points at the brackets that don't match,
+/// which `Diagnostic::from` turns into a report with a label for each of them.
+impl FromStr for Program {
+    type Err = Error;
+
+    #[inline]
+    fn from_str(src: &str) -> Result<Self> {
+        Program::parse(src.as_bytes())
+    }
+}
+
+/// Parses the source with `Program::parse`
+impl TryFrom<&[u8]> for Program {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(src: &[u8]) -> Result<Self> {
+        Program::parse(src)
+    }
+}
+
+/// Runs all of an already parsed `program` on `state`,
+/// which avoids reading and parsing the source again when running it many times
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "run", skip_all, fields(engine = "step"), err)
+)]
+pub fn run_program<C: Cell, I: BfIo>(
+    program: &Program,
+    state: &mut State<C>,
+    io: &mut I,
+) -> Result<RunReport> {
+    #[cfg(feature = "metrics")]
+    let mut metered = telemetry::Run::new(state, "step");
+    #[cfg(feature = "metrics")]
+    let state = &mut *metered;
+    state.start();
+    let instructions = state.instructions;
+    let bytes_read = state.bytes_read;
+    let bytes_written = state.bytes_written;
+    state.peak_pointer = state.cell_pointer;
+
+    let mut pc = 0;
+    while pc < program.len() {
+        pc = program.step(state, pc, io)?;
+    }
+
+    Ok(RunReport {
+        end: EndReason::EndOfSource,
+        instructions: state.instructions - instructions,
+        bytes_read: state.bytes_read - bytes_read,
+        bytes_written: state.bytes_written - bytes_written,
+        peak_pointer: state.peak_pointer,
+    })
+}
+
+/// Output of a lazily running program, created by `Program::outputs`
+pub struct Outputs<'a, C: Cell, I> {
+    program: &'a Program,
+    state: &'a mut State<C>,
+    io: Capture<I>,
+    pc: usize,
+    failed: bool,
+}
+
+impl<C: Cell, I> Outputs<'_, C, I> {
+    /// The index of the next instruction to execute
+    #[inline]
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+    #[inline]
+    pub fn state(&self) -> &State<C> {
+        self.state
+    }
+}
+
+impl<C: Cell, I: Iterator<Item = u8>> Iterator for Outputs<'_, C, I> {
+    type Item = Result<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        while self.pc < self.program.len() {
+            match self.program.step(self.state, self.pc, &mut self.io) {
+                Ok(pc) => self.pc = pc,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+            if let Some(byte) = self.io.output.take() {
+                return Some(Ok(byte));
+            }
+        }
+        None
+    }
+}
+
+struct Capture<I> {
+    input: I,
+    output: Option<u8>,
+}
+
+impl<I: Iterator<Item = u8>> BfIo for Capture<I> {
+    #[inline]
+    fn read(&mut self) -> Result<Option<u8>> {
+        Ok(self.input.next())
+    }
+    #[inline]
+    fn write(&mut self, byte: u8) -> Result<()> {
+        self.output = Some(byte);
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+//! Python bindings made with PyO3, enabled by the `python` feature
+//!
+//! Build the extension module from the `brainfuck-python` crate with
+//! `maturin develop -m brainfuck-python/Cargo.toml`.
+//! Reading past the end of the input reads 0, like playgrounds usually do.
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::bindings::{self, Stepper};
+
+create_exception!(
+    brainfuck,
+    BrainfuckError,
+    PyException,
+    "A program that failed to parse or run"
+);
+
+fn error(e: crate::Error) -> PyErr {
+    BrainfuckError::new_err(e.to_string())
+}
+
+/// Runs `source` with `input`, returning what it printed
+#[pyfunction]
+#[pyo3(signature = (source, input = None))]
+fn run<'py>(py: Python<'py>, source: &str, input: Option<&[u8]>) -> PyResult<Bound<'py, PyBytes>> {
+    let output = bindings::run(source.as_bytes(), input.unwrap_or_default()).map_err(error)?;
+    Ok(PyBytes::new(py, &output))
+}
+
+/// A program that runs an instruction at a time, for showing what it does as it goes
+#[pyclass]
+struct Interpreter(Stepper);
+
+#[pymethods]
+impl Interpreter {
+    /// Parses `source`, failing if its loops don't match
+    #[new]
+    #[pyo3(signature = (source, input = None))]
+    fn new(source: &str, input: Option<&[u8]>) -> PyResult<Self> {
+        let stepper = Stepper::new(source.as_bytes(), input.unwrap_or_default());
+        Ok(Interpreter(stepper.map_err(error)?))
+    }
+    /// Runs the next instruction, returning whether there are any left
+    fn step(&mut self) -> PyResult<bool> {
+        self.0.step().map_err(error)
+    }
+    #[getter]
+    fn finished(&self) -> bool {
+        self.0.finished()
+    }
+    /// The index of the next instruction
+    #[getter]
+    fn pc(&self) -> usize {
+        self.0.pc()
+    }
+    #[getter]
+    fn pointer(&self) -> usize {
+        self.0.pointer()
+    }
+    fn cell(&self, index: usize) -> u8 {
+        self.0.cell(index)
+    }
+    /// Takes what the program has printed since the last time
+    fn take_output<'py>(&mut self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.take_output())
+    }
+}
+
+/// The `brainfuck` Python module
+#[pymodule]
+#[pyo3(name = "brainfuck")]
+pub fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(run, m)?)?;
+    m.add_class::<Interpreter>()?;
+    m.add("BrainfuckError", m.py().get_type::<BrainfuckError>())?;
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use alloc::vec::Vec;
+
+use crate::{Cell, State};
+
+/// The color of the cell the pointer is at
+const POINTER: [u8; 3] = [0x30, 0xa0, 0xff];
+
+/// The tape recorded over time, which can be rendered as an image
+/// with time going down and the cells going right
+#[derive(Debug, Clone, Default)]
+pub struct TapeHistory {
+    rows: Vec<Vec<u8>>,
+    pointers: Vec<usize>,
+}
+
+impl TapeHistory {
+    pub fn new() -> Self {
+        TapeHistory::default()
+    }
+    /// Records the tape of `state` as the next row, with each cell as the byte `.` would write
+    pub fn record<C: Cell>(&mut self, state: &State<C>) {
+        let mut cells = state.cells();
+        cells.trim_end();
+        let len = cells.len().max(state.cell_pointer + 1);
+        let row = (0..len).map(|i| state.get(i).to_byte()).collect();
+        self.rows.push(row);
+        self.pointers.push(state.cell_pointer);
+    }
+    /// The number of recorded rows
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+    /// Renders the history as a PNG image with a pixel for every cell of every row,
+    /// going from black for 0 through red and yellow to white for 255.
+    /// The cell the pointer is at is blue.
+    pub fn to_png(&self) -> Vec<u8> {
+        let width = self.rows.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        let height = self.rows.len().max(1);
+
+        let mut pixels = Vec::with_capacity(height * (1 + 3 * width));
+        for y in 0..height {
+            // No filter
+            pixels.push(0);
+            let row = self.rows.get(y).map_or(&[][..], Vec::as_slice);
+            for x in 0..width {
+                if self.pointers.get(y) == Some(&x) {
+                    pixels.extend_from_slice(&POINTER);
+                } else {
+                    pixels.extend_from_slice(&heat(row.get(x).copied().unwrap_or(0)));
+                }
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, default compression, filtering and no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut png = Vec::from(*b"\x89PNG\r\n\x1a\n");
+        chunk(&mut png, b"IHDR", &header);
+        chunk(&mut png, b"IDAT", &zlib_stored(&pixels));
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+fn heat(value: u8) -> [u8; 3] {
+    let v = u32::from(value) * 3;
+    let channel = |start: u32| v.saturating_sub(start).min(255) as u8;
+    [channel(0), channel(255), channel(510)]
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// `data` in the zlib format without compressing it, which keeps the encoder simple
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xffff;
+
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 5 + 11);
+    // Deflate with a 32K window and no preset dictionary
+    out.extend_from_slice(&[0x78, 0x01]);
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        out.push(u8::from(last));
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
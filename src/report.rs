@@ -0,0 +1,22 @@
+/// Why a run ended without an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndReason {
+    /// Every command in the source was executed
+    EndOfSource,
+    /// The source ended inside a loop, which is waiting for more source to end it
+    AwaitingLoopEnd,
+}
+
+/// What happened during a single run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunReport {
+    pub end: EndReason,
+    /// The number of commands executed, each one counted every time it's executed.
+    /// A `[` counts when its loop is reached and its `]` at the end of every iteration,
+    /// so `[-]` on a cell holding 2 counts 5 in every engine, however it's optimized.
+    pub instructions: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// The highest cell the pointer was at
+    pub peak_pointer: usize,
+}
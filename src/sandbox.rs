@@ -0,0 +1,169 @@
+use alloc::vec::Vec;
+use core::{
+    num::{NonZeroU64, NonZeroUsize},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(feature = "metrics")]
+use crate::telemetry;
+use crate::{BfIo, CellsLimit, Eof, Error, Program, Result, State};
+
+/// Limits that every sandboxed run has to stay within
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The most instructions that may be executed, counted like `RunReport::instructions`
+    pub fuel: u64,
+    /// The amount of cells the program can use, without wrapping around
+    pub cells: NonZeroUsize,
+    /// The most bytes the program may write
+    pub output: usize,
+}
+
+/// How a sandboxed run ended
+#[derive(Debug, Clone)]
+pub enum End {
+    Finished,
+    /// Parsing or running the program failed
+    Failed(Error),
+    OutOfFuel,
+    /// The program tried to write more output than allowed
+    OutputLimitReached,
+    /// The run was cancelled before it ended
+    Cancelled,
+}
+
+/// The result of a sandboxed run
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    /// Everything written until the run ended
+    pub output: Vec<u8>,
+    pub end: End,
+    pub instructions: u64,
+}
+
+/// Runs untrusted programs, like submissions to an online judge, deterministically
+///
+/// Input and output only go through memory, and nothing depends on the clock or on randomness,
+/// so the same program and input always end the same way.
+/// Every run is bounded by the `Limits`, which can't be left out.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    limits: Limits,
+    eof: Eof,
+    loop_limit: Option<NonZeroU64>,
+}
+
+impl Sandbox {
+    pub fn new(limits: Limits) -> Self {
+        Sandbox {
+            limits,
+            eof: Eof::default(),
+            loop_limit: None,
+        }
+    }
+    /// What `,` does once `input` has been read
+    #[must_use]
+    pub fn eof(mut self, eof: Eof) -> Self {
+        self.eof = eof;
+        self
+    }
+    /// The most iterations a loop may run in a row, see `State::set_loop_limit`
+    #[must_use]
+    pub fn loop_limit(mut self, loop_limit: Option<NonZeroU64>) -> Self {
+        self.loop_limit = loop_limit;
+        self
+    }
+    #[inline]
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+    /// Runs `src` with `input`
+    #[inline]
+    pub fn run(&self, src: &[u8], input: &[u8]) -> Outcome {
+        self.run_cancellable(src, input, &AtomicBool::new(false))
+    }
+    /// Runs `src` with `input` until it ends or `cancelled` is set
+    pub fn run_cancellable(&self, src: &[u8], input: &[u8], cancelled: &AtomicBool) -> Outcome {
+        let program = match Program::parse(src) {
+            Ok(program) => program,
+            Err(e) => {
+                return Outcome {
+                    output: Vec::new(),
+                    end: End::Failed(e),
+                    instructions: 0,
+                }
+            }
+        };
+
+        let mut state = State::new(CellsLimit::new(Some((self.limits.cells, false))));
+        state.set_eof(self.eof);
+        state.set_loop_limit(self.loop_limit);
+        let mut io = SandboxIo {
+            input: input.iter(),
+            output: Vec::new(),
+            output_limit: self.limits.output,
+            limit_reached: false,
+        };
+
+        let mut end = End::Finished;
+        let mut pc = 0;
+        while pc < program.len() {
+            if state.instructions == self.limits.fuel {
+                end = End::OutOfFuel;
+                break;
+            }
+            if cancelled.load(Ordering::Relaxed) {
+                end = End::Cancelled;
+                break;
+            }
+            match program.step(&mut state, pc, &mut io) {
+                Ok(next) => pc = next,
+                Err(_) if io.limit_reached => {
+                    end = End::OutputLimitReached;
+                    break;
+                }
+                Err(e) => {
+                    end = End::Failed(e);
+                    break;
+                }
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            telemetry::ran("sandbox", state.instructions, state.cells.len());
+            if matches!(end, End::OutOfFuel) {
+                telemetry::fuel_exhausted();
+            }
+        }
+
+        Outcome {
+            output: io.output,
+            end,
+            instructions: state.instructions,
+        }
+    }
+}
+
+struct SandboxIo<'a> {
+    input: core::slice::Iter<'a, u8>,
+    output: Vec<u8>,
+    output_limit: usize,
+    limit_reached: bool,
+}
+
+impl BfIo for SandboxIo<'_> {
+    #[inline]
+    fn read(&mut self) -> Result<Option<u8>> {
+        Ok(self.input.next().copied())
+    }
+    #[inline]
+    fn write(&mut self, byte: u8) -> Result<()> {
+        if self.output.len() >= self.output_limit {
+            self.limit_reached = true;
+            return Err(Error::Io("output limit reached"));
+        }
+        self.output.push(byte);
+        Ok(())
+    }
+}
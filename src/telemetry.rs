@@ -0,0 +1,169 @@
+//! Reporting what programs do to the host application's telemetry
+//!
+//! With the `tracing` feature, every run of `run_program`, `Bytecode::run`, `run_tiered`
+//! and the other ways of running a whole program is an info span named `run` with the engine
+//! running it, and a run failing is an error event in it.
+//! Each time a loop is entered is a trace span named `loop` in the span of the loop around it,
+//! and every byte read or written is a trace event in the span of the innermost loop.
+//!
+//! Loops that bytecode runs as a single op, like `[-]`, don't get spans of their own,
+//! and a loop the tiered engine compiles while it's running gets a second span for the
+//! iterations it runs compiled. Spans of loops that an error ended are closed the next time
+//! the state starts a run or is reset.
+//!
+//! With the `metrics` feature, the same runs and those of a `Sandbox` publish the metrics
+//! named here through the `metrics` facade, labelled with the `engine` that ran them,
+//! which is `sandbox` for sandboxed runs. Runs that fail count like any other, and every
+//! chunk given to a `Feeder` is a run of its own.
+//! Call `describe_metrics` once the recorder is installed to give the metrics descriptions.
+
+#[cfg(feature = "tracing")]
+use alloc::vec::Vec;
+#[cfg(feature = "metrics")]
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "metrics")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "metrics")]
+use metrics::{counter, describe_counter, describe_gauge, gauge, Unit};
+#[cfg(feature = "tracing")]
+use tracing::{trace, trace_span, Span};
+
+#[cfg(feature = "tracing")]
+use crate::Position;
+#[cfg(feature = "metrics")]
+use crate::{Cell, State};
+
+/// Counter of the instructions executed, counted like `RunReport::instructions`
+#[cfg(feature = "metrics")]
+pub const INSTRUCTIONS: &str = "brainfuck_instructions_executed";
+/// Counter of the programs run
+#[cfg(feature = "metrics")]
+pub const PROGRAMS: &str = "brainfuck_programs_run";
+/// Counter of the sandboxed runs that ran out of fuel
+#[cfg(feature = "metrics")]
+pub const FUEL_EXHAUSTED: &str = "brainfuck_fuel_exhausted";
+/// Gauge of the most cells any tape has grown to
+#[cfg(feature = "metrics")]
+pub const PEAK_TAPE: &str = "brainfuck_peak_tape_cells";
+
+/// Registers the descriptions and units of the metrics with the installed recorder
+#[cfg(feature = "metrics")]
+pub fn describe_metrics() {
+    describe_counter!(INSTRUCTIONS, "Instructions executed by programs");
+    describe_counter!(PROGRAMS, "Programs run, successfully or not");
+    describe_counter!(
+        FUEL_EXHAUSTED,
+        "Sandboxed programs stopped for running out of fuel"
+    );
+    describe_gauge!(
+        PEAK_TAPE,
+        Unit::Count,
+        "The most cells any tape has grown to"
+    );
+}
+
+/// The largest tape published to `PEAK_TAPE`
+#[cfg(feature = "metrics")]
+static PEAK_TAPE_CELLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Publishes the metrics of a run on `engine` that executed `instructions`
+/// with a tape of `tape` cells
+#[cfg(feature = "metrics")]
+pub(crate) fn ran(engine: &'static str, instructions: u64, tape: usize) {
+    counter!(INSTRUCTIONS, "engine" => engine).increment(instructions);
+    counter!(PROGRAMS, "engine" => engine).increment(1);
+    let peak = PEAK_TAPE_CELLS.fetch_max(tape, Ordering::Relaxed).max(tape);
+    gauge!(PEAK_TAPE).set(peak as f64);
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn fuel_exhausted() {
+    counter!(FUEL_EXHAUSTED).increment(1);
+}
+
+/// A state running a program on `engine`, which publishes the metrics of the run when dropped,
+/// however the run ended
+#[cfg(feature = "metrics")]
+pub(crate) struct Run<'a, C: Cell> {
+    state: &'a mut State<C>,
+    engine: &'static str,
+    instructions: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl<'a, C: Cell> Run<'a, C> {
+    pub(crate) fn new(state: &'a mut State<C>, engine: &'static str) -> Self {
+        let instructions = state.instructions;
+        Run {
+            state,
+            engine,
+            instructions,
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<C: Cell> Deref for Run<'_, C> {
+    type Target = State<C>;
+
+    fn deref(&self) -> &State<C> {
+        self.state
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<C: Cell> DerefMut for Run<'_, C> {
+    fn deref_mut(&mut self) -> &mut State<C> {
+        self.state
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<C: Cell> Drop for Run<'_, C> {
+    fn drop(&mut self) {
+        let instructions = self.state.instructions - self.instructions;
+        ran(self.engine, instructions, self.state.cells.len());
+    }
+}
+
+/// The spans of the loops a state is running, innermost last
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LoopSpans(Vec<Span>);
+
+/// Like `tracing`'s macro `$macro`, but in the innermost span of `$spans`
+/// or the current span if it's empty
+#[cfg(feature = "tracing")]
+macro_rules! innermost {
+    ($macro:ident!($spans:expr, $($args:tt)*)) => {
+        match $spans.0.last() {
+            Some(parent) => $macro!(parent: parent, $($args)*),
+            None => $macro!($($args)*),
+        }
+    };
+}
+
+#[cfg(feature = "tracing")]
+impl LoopSpans {
+    pub(crate) fn entered(&mut self, begin: Position) {
+        let span = innermost!(trace_span!(self, "loop", %begin));
+        self.0.push(span);
+    }
+    pub(crate) fn exited(&mut self) {
+        self.0.pop();
+    }
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+    pub(crate) fn output(&self, byte: u8) {
+        innermost!(trace!(self, byte, "output"));
+    }
+    /// `byte` is `None` at the end of the input
+    pub(crate) fn input(&self, byte: Option<u8>) {
+        match byte {
+            Some(byte) => innermost!(trace!(self, byte, "input")),
+            None => innermost!(trace!(self, "end of input")),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use alloc::vec::Vec;
+
+#[cfg(feature = "metrics")]
+use crate::telemetry;
+use crate::{
+    bytecode::{Bytecode, OptLevel, ThreadedCode},
+    BfIo, Cell, EndReason, Instruction, Program, Result, RunReport, State,
+};
+
+/// Runs `program` on `state` one instruction at a time like `run_program`, but compiles
+/// each loop that has run `threshold` iterations to `Bytecode` and runs that from then on.
+///
+/// Code that only runs a few times isn't compiled, while hot loops run about as fast as
+/// bytecode. The results are the same as running the bytecode once a loop is compiled.
+pub fn run_tiered<C: Cell, I: BfIo>(
+    program: &Program,
+    state: &mut State<C>,
+    io: &mut I,
+    threshold: u32,
+) -> Result<RunReport> {
+    run_tiered_with(program, state, io, threshold, OptLevel::Full)
+}
+
+/// Like `run_tiered`, but only optimizing the loops it compiles as much as `level` does
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "run", skip_all, fields(engine = "tiered"), err)
+)]
+pub fn run_tiered_with<C: Cell, I: BfIo>(
+    program: &Program,
+    state: &mut State<C>,
+    io: &mut I,
+    threshold: u32,
+    level: OptLevel,
+) -> Result<RunReport> {
+    #[cfg(feature = "metrics")]
+    let mut metered = telemetry::Run::new(state, "tiered");
+    #[cfg(feature = "metrics")]
+    let state = &mut *metered;
+    state.start();
+    let instructions = state.instructions;
+    let bytes_read = state.bytes_read;
+    let bytes_written = state.bytes_written;
+    state.peak_pointer = state.cell_pointer;
+
+    let result = run(program, state, io, threshold, level);
+    state.ended_by_input(result)?;
+
+    Ok(RunReport {
+        end: EndReason::EndOfSource,
+        instructions: state.instructions - instructions,
+        bytes_read: state.bytes_read - bytes_read,
+        bytes_written: state.bytes_written - bytes_written,
+        peak_pointer: state.peak_pointer,
+    })
+}
+
+/// Runs the program for `run_tiered`
+fn run<C: Cell, I: BfIo>(
+    program: &Program,
+    state: &mut State<C>,
+    io: &mut I,
+    threshold: u32,
+    level: OptLevel,
+) -> Result<()> {
+    // Indexed by the `[` of each loop
+    let mut iterations = alloc::vec![0u32; program.len()];
+    let mut compiled: Vec<Option<ThreadedCode<C, I>>> = Vec::new();
+    compiled.resize_with(program.len(), || None);
+
+    let mut pc = 0;
+    while pc < program.len() {
+        match program.instructions()[pc] {
+            Instruction::LoopBegin(end) => {
+                if let Some(code) = &compiled[pc] {
+                    state.pc = Some(pc);
+                    let result = code.run(state, io);
+                    state.resuming_loop = false;
+                    result?;
+                    pc = end + 1;
+                    continue;
+                }
+            }
+            Instruction::LoopEnd(start) => {
+                let count = &mut iterations[start];
+                *count = count.saturating_add(1);
+                if *count >= threshold && compiled[start].is_none() {
+                    let code = Bytecode::compile_range(program, start..pc + 1, level).threaded();
+                    compiled[start] = Some(code);
+                }
+                if compiled[start].is_some() {
+                    pc = program.step(state, pc, io)?;
+                    if pc == start + 1 {
+                        // Go on in the compiled loop, which counts its `[` again
+                        // but goes on counting its iterations
+                        state.instructions -= 1;
+                        state.resuming_loop = true;
+                        // The compiled loop has a span of its own
+                        state.loop_exited();
+                        pc = start;
+                    }
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        pc = program.step(state, pc, io)?;
+    }
+    Ok(())
+}
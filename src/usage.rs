@@ -0,0 +1,91 @@
+use std::{
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use crate::{Cell, State};
+
+/// The resources used by running programs on a `State`, measured by a `Meter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Counted like `RunReport::instructions`, so it's the same whichever engine ran
+    pub instructions: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// The most memory the tape took up, in bytes
+    pub peak_tape_bytes: usize,
+    pub wall_time: Duration,
+    /// CPU time used by the thread running the programs, if it can be measured on this platform
+    pub cpu_time: Option<Duration>,
+}
+
+impl Display for ResourceUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "instructions   {}", self.instructions)?;
+        writeln!(f, "bytes read     {}", self.bytes_read)?;
+        writeln!(f, "bytes written  {}", self.bytes_written)?;
+        writeln!(f, "tape memory    {} bytes", self.peak_tape_bytes)?;
+        writeln!(f, "wall time      {:.2?}", self.wall_time)?;
+        match self.cpu_time {
+            Some(cpu_time) => write!(f, "cpu time       {cpu_time:.2?}"),
+            None => write!(f, "cpu time       unknown"),
+        }
+    }
+}
+
+/// Measures what runs on a state use from when the meter is started until it's finished,
+/// for hosts that account for each program they run
+#[derive(Debug, Clone, Copy)]
+pub struct Meter {
+    wall: Instant,
+    cpu: Option<Duration>,
+    instructions: u64,
+    bytes_read: u64,
+    bytes_written: u64,
+}
+
+impl Meter {
+    pub fn start<C: Cell>(state: &State<C>) -> Self {
+        Meter {
+            wall: Instant::now(),
+            cpu: thread_cpu_time(),
+            instructions: state.instructions,
+            bytes_read: state.bytes_read,
+            bytes_written: state.bytes_written,
+        }
+    }
+    /// Has to be called on the thread the meter was started on to measure CPU time
+    pub fn finish<C: Cell>(self, state: &State<C>) -> ResourceUsage {
+        let wall_time = self.wall.elapsed();
+        let cpu_time = thread_cpu_time()
+            .zip(self.cpu)
+            .map(|(end, start)| end.saturating_sub(start));
+        ResourceUsage {
+            instructions: state.instructions - self.instructions,
+            bytes_read: state.bytes_read - self.bytes_read,
+            bytes_written: state.bytes_written - self.bytes_written,
+            // The tape never shrinks while running
+            peak_tape_bytes: state.tape_bytes(),
+            wall_time,
+            cpu_time,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn thread_cpu_time() -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // Safety: `time` is valid to write to
+    if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) } != 0 {
+        return None;
+    }
+    Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+#[cfg(not(unix))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
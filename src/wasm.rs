@@ -0,0 +1,53 @@
+//! JavaScript bindings made with `wasm-bindgen`, enabled by the `wasm` feature
+//!
+//! Build them for browsers from the `brainfuck-wasm` crate with
+//! `wasm-pack build brainfuck-wasm`.
+//! Reading past the end of the input reads 0, like playgrounds usually do.
+
+use wasm_bindgen::prelude::*;
+
+use crate::bindings::{self, Stepper};
+
+/// Runs `source` with `input`, returning what it printed
+#[wasm_bindgen]
+pub fn run(source: &str, input: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(bindings::run(source.as_bytes(), input)?)
+}
+
+/// A program that runs an instruction at a time, for showing what it does as it goes
+#[wasm_bindgen]
+pub struct Interpreter(Stepper);
+
+#[wasm_bindgen]
+impl Interpreter {
+    /// Parses `source`, failing if its loops don't match
+    #[wasm_bindgen(constructor)]
+    pub fn new(source: &str, input: &[u8]) -> Result<Interpreter, JsError> {
+        Ok(Interpreter(Stepper::new(source.as_bytes(), input)?))
+    }
+    /// Runs the next instruction, returning whether there are any left
+    pub fn step(&mut self) -> Result<bool, JsError> {
+        Ok(self.0.step()?)
+    }
+    #[wasm_bindgen(getter)]
+    pub fn finished(&self) -> bool {
+        self.0.finished()
+    }
+    /// The index of the next instruction
+    #[wasm_bindgen(getter)]
+    pub fn pc(&self) -> usize {
+        self.0.pc()
+    }
+    #[wasm_bindgen(getter)]
+    pub fn pointer(&self) -> usize {
+        self.0.pointer()
+    }
+    pub fn cell(&self, index: usize) -> u8 {
+        self.0.cell(index)
+    }
+    /// Takes what the program has printed since the last time
+    #[wasm_bindgen(js_name = takeOutput)]
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.0.take_output()
+    }
+}
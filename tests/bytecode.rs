@@ -0,0 +1,39 @@
+use brainfuck::{bytecode::Bytecode, Program};
+
+/// Where each saved op starts, after the magic bytes and the number of ops
+const OPS: usize = 12;
+/// How many bytes each op is saved as
+const OP_SIZE: usize = 77;
+
+/// `bytes` with the first argument of the op at `index` set to `arg`
+fn with_arg(bytes: &[u8], index: usize, arg: i64) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    let at = OPS + index * OP_SIZE + 1;
+    bytes[at..at + 8].copy_from_slice(&arg.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn corrupt_jumps_are_rejected() {
+    // Add, jump if zero, out, add, jump if non-zero
+    let bytecode = Bytecode::compile(&Program::parse(b"+[.-]>[.]").unwrap());
+    let bytes = bytecode.to_bytes();
+    assert_eq!(Bytecode::from_bytes(&bytes), Some(bytecode));
+
+    for (index, target) in [
+        // Back to an op that isn't the start of its loop
+        (4, 0),
+        (4, 2),
+        // Past the end of its loop
+        (1, 6),
+        // To the other loop
+        (1, 8),
+        (4, 6),
+        // Out of range
+        (4, -1),
+        (4, 1000),
+    ] {
+        let corrupt = with_arg(&bytes, index, target);
+        assert_eq!(Bytecode::from_bytes(&corrupt), None, "{index}: {target}");
+    }
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "clap")]
+
+use std::path::Path;
+use std::process::{self, Command};
+use std::{env, fs};
+
+/// Runs `source` with `--compiled`, caching its bytecode in `cache`, returning what it printed
+fn run_compiled(cache: &Path, source: &Path, level: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["--compiled", "--opt-level", level])
+        .arg(source)
+        .env("XDG_CACHE_HOME", cache)
+        .env("LOCALAPPDATA", cache)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn cached(cache: &Path) -> Vec<fs::DirEntry> {
+    fs::read_dir(cache.join("brainfuck"))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[test]
+fn corrupt_bytecode_is_compiled_again() {
+    let dir = env::temp_dir().join(format!("brainfuck-cache-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("hi.bf");
+    fs::write(&source, "++++++++[>+++++++++<-]>.+.").unwrap();
+
+    assert_eq!(run_compiled(&dir, &source, "full"), "HI");
+    let [file] = &cached(&dir)[..] else {
+        panic!("expected one cached file");
+    };
+    // The start of the loop jumping back to the first op instead of to its end
+    let mut bytes = fs::read(file.path()).unwrap();
+    for op in bytes[12..].chunks_exact_mut(77).filter(|op| op[0] == 7) {
+        op[1..9].fill(0);
+    }
+    fs::write(file.path(), bytes).unwrap();
+    assert_eq!(run_compiled(&dir, &source, "full"), "HI");
+
+    // Other options are cached separately
+    assert_eq!(run_compiled(&dir, &source, "none"), "HI");
+    let cached = cached(&dir).len();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(cached, 2);
+}
@@ -0,0 +1,37 @@
+#![cfg(feature = "std")]
+
+use std::thread;
+use std::time::Duration;
+
+use brainfuck::{run_slice, Error, FnIo, State};
+
+#[test]
+fn pause_view_resume_and_stop() {
+    let mut state = State::default();
+    let handle = state.control_handle();
+    let running = thread::spawn(move || {
+        let mut io = FnIo::new(|| None, drop);
+        run_slice(b"+[>+<]", &mut state, &mut io)
+    });
+    while handle.instructions() == 0 {
+        thread::yield_now();
+    }
+
+    handle.pause();
+    // The program gets to a checkpoint and waits there
+    thread::sleep(Duration::from_millis(20));
+    let paused_at = handle.instructions();
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(handle.instructions(), paused_at);
+    let view = handle.tape_view(Duration::from_secs(5)).unwrap();
+    assert!(view.starts_with("[01]"), "{view}");
+
+    handle.resume();
+    while handle.instructions() == paused_at {
+        thread::yield_now();
+    }
+    handle.pause();
+    handle.stop();
+    let result = running.join().unwrap();
+    assert!(matches!(result, Err(Error::Stopped)));
+}
@@ -0,0 +1,57 @@
+use std::num::NonZeroUsize;
+
+use brainfuck::{
+    differential::{self, Checked},
+    CellsLimit, State, UninitReads,
+};
+
+#[test]
+fn moves_past_the_end_of_the_tape_on_the_way() {
+    let cells = NonZeroUsize::new(3).unwrap();
+    let mut recovering = State::new(CellsLimit::new(Some((cells, false))));
+    recovering.set_recover(true);
+    for template in [
+        State::new(CellsLimit::new(Some((cells, false)))),
+        recovering,
+    ] {
+        for src in ["<>", ">>><", "<<>>+", "+[>>>+<<<-]", ">>[-]>><<<<<[-]"] {
+            let checked = differential::check(src.as_bytes(), &template, &[], 1000);
+            assert_eq!(
+                checked.map_err(|d| d.to_string()),
+                Ok(Checked::Agreed),
+                "{src}"
+            );
+        }
+    }
+}
+
+#[test]
+fn transfers_agree() {
+    let cells = NonZeroUsize::new(8).unwrap();
+    let mut warning = State::default();
+    warning.set_uninit_reads(UninitReads::Warn);
+    for template in [
+        State::default(),
+        State::new(CellsLimit::new(Some((cells, false)))),
+        State::new(CellsLimit::new(Some((cells, true)))),
+        warning,
+    ] {
+        for src in [
+            "+++>++[-<->]<[>>+<<-]",
+            ">+++>++>+>++++<<<[-<+>]>[-<+>]>[-<+>]>[-<+>]",
+            ">>+++>++>+>++++<<<[-<<+>>]>[-<<+>>]>[-<<+>>]>[-<<+>>]",
+            "+++>++>+>++++[->+<]<[->+<]<[->+<]<[->+<]",
+            "+++>++>+>++++[->>>+<<<]<[->>>+<<<]<[->>>+<<<]<[->>>+<<<]",
+            "+++>++>>[->>+<<]<[->>+<<]<[->>+<<]",
+            ">>>>>>>>>>+++>++>+[-<<+>>]<[->>+<<]>>>>>[-<<<<<<<<<<<+>>>>>>>>>>>]",
+            ">+>[-<+>]>[-<+>]>[-<+>]",
+        ] {
+            let checked = differential::check(src.as_bytes(), &template, &[], 100_000);
+            assert_eq!(
+                checked.map_err(|d| d.to_string()),
+                Ok(Checked::Agreed),
+                "{src}"
+            );
+        }
+    }
+}
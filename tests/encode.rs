@@ -0,0 +1,74 @@
+#![cfg(feature = "clap")]
+
+use std::io::Write;
+use std::process::{self, Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{env, fs};
+
+/// Numbers the programs written by tests running at the same time
+static PROGRAMS: AtomicUsize = AtomicUsize::new(0);
+
+/// Runs a program printing its input back, zeros included, with `args`
+fn echo(args: &[&str], input: &[u8]) -> Output {
+    let n = PROGRAMS.fetch_add(1, Ordering::Relaxed);
+    let path = env::temp_dir().join(format!("brainfuck-encode-{}-{n}.b", process::id()));
+    fs::write(&path, "+[,.[-]+]").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["--eof", "terminate"])
+        .args(args)
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(path).unwrap();
+    output
+}
+
+fn printed(args: &[&str], input: &[u8]) -> String {
+    let output = echo(args, input);
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn base64_round_trips() {
+    let every_char = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    for encoded in [every_char, "TWFu", "TWE=", "TQ==", ""] {
+        let expected = if encoded.is_empty() {
+            String::new()
+        } else {
+            format!("{encoded}\n")
+        };
+        assert_eq!(printed(&["--base64"], encoded.as_bytes()), expected);
+    }
+    // Whitespace is ignored and padding is optional
+    assert_eq!(printed(&["--base64"], b" TW\nFu\r\nTWE"), "TWFuTWE=\n");
+}
+
+#[test]
+fn invalid_base64_fails() {
+    for (input, problem) in [
+        (&b"TQ!="[..], "`!` is not base64"),
+        (b"TWFuT", "ends in the middle of a byte"),
+    ] {
+        let output = echo(&["--base64"], input);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(problem), "{stderr}");
+    }
+}
+
+#[test]
+fn hex_is_written() {
+    let input = [0, 1, 0xab, 0xff];
+    assert_eq!(printed(&["--output-format", "hex"], &input), "0001abff\n");
+    assert_eq!(
+        printed(&["--output-format", "hex-spaced"], &input),
+        "00 01 ab ff\n"
+    );
+    assert_eq!(printed(&["--output-format", "hex-spaced"], b""), "");
+}
@@ -0,0 +1,45 @@
+use brainfuck::{fuzz::FuzzCase, Eof};
+
+#[test]
+fn every_eof_mode_can_be_picked() {
+    let modes: Vec<_> = (0..8)
+        .map(|config| FuzzCase::from_bytes(&[config, 0, b',', 0]).eof)
+        .collect();
+    for eof in [
+        Eof::Error,
+        Eof::Zero,
+        Eof::MinusOne,
+        Eof::Unchanged,
+        Eof::Terminate,
+    ] {
+        assert!(modes.contains(&eof), "{eof:?}");
+    }
+}
+
+#[test]
+fn brackets_are_balanced() {
+    // `+`, `]`, `[`, `[`, `.`
+    let case = FuzzCase::from_bytes(&[0, 0, 8, 7, 6, 6, 5]);
+    assert_eq!(case.program, b"+[[.]]");
+}
+
+#[test]
+#[cfg(feature = "arbitrary")]
+fn arbitrary_programs_parse() {
+    use arbitrary::{Arbitrary, Unstructured};
+    use brainfuck::Program;
+
+    let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+    let mut lens = Vec::new();
+    for start in 0..50 {
+        let program = Program::arbitrary(&mut Unstructured::new(&data[start..])).unwrap();
+        let src: Vec<u8> = program
+            .instructions()
+            .iter()
+            .map(|ins| ins.command().to_byte())
+            .collect();
+        assert_eq!(Program::parse(&src).unwrap(), program);
+        lens.push(program.len());
+    }
+    assert!(lens.iter().any(|&len| len > 0), "{lens:?}");
+}
@@ -0,0 +1,146 @@
+//! Every engine counts each command it executes once, however it's optimized
+
+use std::num::NonZeroUsize;
+
+use brainfuck::{
+    bytecode::{Bytecode, OptLevel},
+    run_program, run_slice,
+    tiered::run_tiered,
+    CellsLimit, FnIo, Program, Result, RunReport, State, UninitReads,
+};
+
+type Engine = fn(&[u8], &mut State) -> Result<RunReport>;
+
+const ENGINES: [(&str, Engine); 8] = [
+    ("stream", |src, state| run_slice(src, state, &mut io())),
+    ("step", |src, state| {
+        run_program(&Program::parse(src)?, state, &mut io())
+    }),
+    ("bytecode", |src, state| {
+        Bytecode::compile(&Program::parse(src)?).run(state, &mut io())
+    }),
+    ("bytecode loops", |src, state| {
+        Bytecode::compile_with(&Program::parse(src)?, OptLevel::Loops).run(state, &mut io())
+    }),
+    ("bytecode runs", |src, state| {
+        Bytecode::compile_with(&Program::parse(src)?, OptLevel::Runs).run(state, &mut io())
+    }),
+    ("bytecode unoptimized", |src, state| {
+        Bytecode::compile_with(&Program::parse(src)?, OptLevel::None).run(state, &mut io())
+    }),
+    ("tiered", |src, state| {
+        run_tiered(&Program::parse(src)?, state, &mut io(), 1)
+    }),
+    ("tiered 3", |src, state| {
+        run_tiered(&Program::parse(src)?, state, &mut io(), 3)
+    }),
+];
+
+fn io() -> FnIo<impl FnMut() -> Option<u8>, impl FnMut(u8)> {
+    FnIo::new(|| Some(3), drop)
+}
+
+/// The instructions every engine counts for `src`, checking that they agree
+fn instructions(src: &str) -> u64 {
+    let states = [
+        State::default(),
+        State::new(CellsLimit::new(Some((
+            NonZeroUsize::new(64).unwrap(),
+            true,
+        )))),
+        {
+            let mut state = State::default();
+            state.set_uninit_reads(UninitReads::Warn);
+            state
+        },
+    ];
+    let mut counts = Vec::new();
+    for template in states {
+        for (engine, run) in ENGINES {
+            let mut state = template.clone();
+            let report = run(src.as_bytes(), &mut state).unwrap();
+            counts.push((engine, report.instructions));
+        }
+    }
+    let (_, expected) = counts[0];
+    for &(engine, count) in &counts {
+        assert_eq!(
+            count, expected,
+            "{engine} counted differently for {src}: {counts:?}"
+        );
+    }
+    expected
+}
+
+#[test]
+fn loops() {
+    assert_eq!(instructions("[]"), 1);
+    assert_eq!(instructions("++[>+<-]"), 13);
+    assert_eq!(instructions("++[>++[>+<-]<-]"), 2 + 1 + 2 * (4 + 2 * 5 + 3));
+}
+
+#[test]
+fn clears() {
+    assert_eq!(instructions("+[-]>[-]"), 6);
+    assert_eq!(instructions(">+[-]<>>[-]"), 9);
+    assert_eq!(instructions("+++[-]"), 10);
+    assert_eq!(instructions("-[+]"), 4);
+    assert_eq!(instructions("++>+++<[-]>[-]"), 20);
+    assert_eq!(instructions("++>+++<[-]>[+]"), 7 + 5 + 1 + 1 + 2 * 253);
+}
+
+#[test]
+fn scans() {
+    assert_eq!(
+        instructions("+>+>+<<[>]"),
+        1 + 1 + 1 + 1 + 1 + 2 + 1 + 3 * 2
+    );
+    assert_eq!(instructions(">>+[<]"), 2 + 1 + 1 + 2);
+}
+
+#[test]
+fn transfers() {
+    assert_eq!(instructions("++[->>+<<]"), 2 + 1 + 2 * 7);
+    assert_eq!(instructions("+++[>-<-]"), 3 + 1 + 3 * 5);
+    assert_eq!(
+        instructions(">>++>+++<[-<<+>>]>[-<<+>>]>[-<<+>>]"),
+        9 + 1 + 2 * 7 + 1 + 1 + 3 * 7 + 1 + 1
+    );
+    assert_eq!(
+        instructions("+>++>+++[->+<]<[->+<]<[->+<]"),
+        8 + 1 + 3 * 5 + 1 + 1 + 2 * 5 + 1 + 1 + 5
+    );
+}
+
+#[test]
+fn input_and_output() {
+    assert_eq!(instructions(",[.-]"), 1 + 1 + 3 * 3);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn fuel_and_usage() {
+    use brainfuck::{
+        sandbox::{End, Limits, Sandbox},
+        Meter,
+    };
+
+    let src = "++[>+<-]+[-]>[-]>+[-]<>>[-]";
+    let expected = instructions(src);
+
+    let mut state = State::default();
+    let meter = Meter::start(&state);
+    run_slice(src.as_bytes(), &mut state, &mut io()).unwrap();
+    assert_eq!(meter.finish(&state).instructions, expected);
+
+    let limits = |fuel| Limits {
+        fuel,
+        cells: NonZeroUsize::new(64).unwrap(),
+        output: 0,
+    };
+    let outcome = Sandbox::new(limits(expected)).run(src.as_bytes(), b"");
+    assert!(matches!(outcome.end, End::Finished));
+    assert_eq!(outcome.instructions, expected);
+    let outcome = Sandbox::new(limits(expected - 1)).run(src.as_bytes(), b"");
+    assert!(matches!(outcome.end, End::OutOfFuel));
+}
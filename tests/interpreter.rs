@@ -0,0 +1,85 @@
+#![cfg(feature = "std")]
+
+use brainfuck::{bytecode::OptLevel, Engine, Error, Interpreter};
+
+/// What the engine printed before failing on each of a few sources that every engine fails
+/// a little differently on, and the column of the failing command
+fn failures(engine: Engine) -> Vec<(Vec<u8>, usize)> {
+    ["+.]", ">>>>", "+[>>+]"]
+        .into_iter()
+        .map(|src| {
+            let mut output = Vec::new();
+            let mut interpreter = Interpreter::builder()
+                .cell_limit(4)
+                .engine(engine)
+                .output(&mut output)
+                .build()
+                .unwrap();
+            assert_eq!(interpreter.engine(), engine);
+            let e = interpreter.run_slice(src.as_bytes()).unwrap_err();
+            drop(interpreter);
+            (output, e.position().unwrap().column)
+        })
+        .collect()
+}
+
+#[test]
+fn engine_is_respected() {
+    // Only the streaming engine runs what comes before an unmatched `]`
+    assert_eq!(
+        failures(Engine::Stream),
+        [(vec![1], 3), (vec![], 4), (vec![], 4)]
+    );
+    assert_eq!(
+        failures(Engine::Parsed),
+        [(vec![], 3), (vec![], 4), (vec![], 4)]
+    );
+    // Errors in merged ops point at where the op starts
+    assert_eq!(
+        failures(Engine::Bytecode),
+        [(vec![], 3), (vec![], 1), (vec![], 3)]
+    );
+    // Only loops are compiled
+    assert_eq!(
+        failures(Engine::Tiered { threshold: 1 }),
+        [(vec![], 3), (vec![], 4), (vec![], 3)]
+    );
+}
+
+#[test]
+fn opt_level_is_respected() {
+    let columns = |level| -> Vec<_> {
+        [">>>>", "+>>+[>>]", "+>>>>"]
+            .into_iter()
+            .map(|src| {
+                let mut interpreter = Interpreter::builder()
+                    .cell_limit(4)
+                    .engine(Engine::Bytecode)
+                    .opt_level(level)
+                    .build()
+                    .unwrap();
+                assert_eq!(interpreter.opt_level(), level);
+                let e = interpreter.run_slice(src.as_bytes()).unwrap_err();
+                e.position().unwrap().column
+            })
+            .collect()
+    };
+    // Every command is an op of its own
+    assert_eq!(columns(OptLevel::None), [4, 7, 5]);
+    // The moves are merged
+    assert_eq!(columns(OptLevel::Runs), [1, 6, 2]);
+    // The scan loop is one op
+    assert_eq!(columns(OptLevel::Loops), [1, 5, 2]);
+    // The add and the moves after it are fused
+    assert_eq!(columns(OptLevel::Full), [1, 5, 1]);
+}
+
+#[test]
+fn start_cell_past_the_limit_fails_to_build() {
+    let builder = Interpreter::builder().cell_limit(4).start_cell(4);
+    assert!(matches!(builder.build_state(), Err(Error::OutOfBounds)));
+    assert!(matches!(builder.build(), Err(Error::OutOfBounds)));
+
+    let interpreter = Interpreter::builder().cell_limit(4).start_cell(3).build();
+    assert_eq!(interpreter.unwrap().state().cell_pointer, 3);
+}
@@ -0,0 +1,9 @@
+#![cfg(feature = "macros")]
+
+use brainfuck::brainfuck;
+
+#[test]
+fn re_exported() {
+    let hello: &[u8] = brainfuck!("++++++++[>+++++++++<-]>.");
+    assert_eq!(hello, b"H");
+}
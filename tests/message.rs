@@ -0,0 +1,147 @@
+#![cfg(feature = "clap")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{self, Child, ChildStdout, Command, Stdio};
+use std::{env, fs};
+
+fn start(subcommand: &str) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .arg(subcommand)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+/// `body` with the header giving its length
+fn frame(body: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+/// Reads the body of the next message, `None` once the output has ended
+fn read_message(output: &mut impl BufRead) -> Option<String> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if output.read_line(&mut line).unwrap() == 0 {
+            return None;
+        }
+        match line.trim_end().split_once(": ") {
+            Some(("Content-Length", n)) => length = Some(n.parse().unwrap()),
+            Some(header) => panic!("unexpected header {header:?}"),
+            None if line.trim_end().is_empty() => break,
+            None => panic!("not a header: {line:?}"),
+        }
+    }
+    let mut body = vec![0; length.expect("no Content-Length")];
+    output.read_exact(&mut body).unwrap();
+    Some(String::from_utf8(body).unwrap())
+}
+
+/// Reads messages until one contains `needle`, returning it
+fn read_until(output: &mut BufReader<ChildStdout>, needle: &str) -> String {
+    loop {
+        let message = read_message(output).unwrap_or_else(|| panic!("ended before {needle}"));
+        if message.contains(needle) {
+            return message;
+        }
+    }
+}
+
+#[test]
+fn language_server_answers_and_publishes_diagnostics() {
+    let mut child = start("lsp");
+    let messages = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+        // Non-ASCII text makes the length in bytes differ from the one in characters
+        r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.b","text":"ø+]"}}}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"shutdown"}"#,
+        r#"{"jsonrpc":"2.0","method":"exit"}"#,
+    ];
+    // Header names are case-insensitive and other headers are ignored
+    let mut input = format!(
+        "content-length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}",
+        messages[0].len(),
+        messages[0]
+    );
+    for message in &messages[1..] {
+        input += &frame(message);
+    }
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+
+    let mut output = BufReader::new(child.stdout.take().unwrap());
+    let initialized = read_message(&mut output).unwrap();
+    assert!(initialized.starts_with(r#"{"jsonrpc":"2.0","id":1,"result":{"capabilities""#));
+    let published = read_message(&mut output).unwrap();
+    assert!(published.contains(r#""method":"textDocument/publishDiagnostics""#));
+    assert!(published.contains(r#""uri":"file:///a.b""#));
+    assert!(published.contains(r#""character":2"#), "{published}");
+    let shut_down = read_message(&mut output).unwrap();
+    assert_eq!(shut_down, r#"{"jsonrpc":"2.0","id":2,"result":null}"#);
+    assert_eq!(read_message(&mut output), None);
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn debug_adapter_runs_a_program() {
+    let path = env::temp_dir().join(format!("brainfuck-dap-{}.b", process::id()));
+    fs::write(&path, "++++++++[>++++++++<-]>+.+.").unwrap();
+    let mut child = start("dap");
+    let mut input = child.stdin.take().unwrap();
+    let mut output = BufReader::new(child.stdout.take().unwrap());
+    let mut request = |seq: u32, command: &str, arguments: &str| {
+        let body = format!(
+            r#"{{"seq":{seq},"type":"request","command":"{command}","arguments":{arguments}}}"#
+        );
+        input.write_all(frame(&body).as_bytes()).unwrap();
+        input.flush().unwrap();
+    };
+
+    request(1, "initialize", "{}");
+    let initialized = read_message(&mut output).unwrap();
+    assert!(initialized.contains(r#""request_seq":1,"command":"initialize","success":true"#));
+    read_until(&mut output, r#""event":"initialized""#);
+
+    let program = path.to_str().unwrap().replace('\\', "\\\\");
+    request(2, "launch", &format!(r#"{{"program":"{program}"}}"#));
+    read_until(
+        &mut output,
+        r#""request_seq":2,"command":"launch","success":true"#,
+    );
+    request(3, "configurationDone", "{}");
+    let printed = read_until(&mut output, r#""event":"output""#);
+    assert!(printed.contains(r#""output":"A""#), "{printed}");
+    read_until(&mut output, r#""event":"terminated""#);
+
+    request(4, "disconnect", "{}");
+    read_until(&mut output, r#""request_seq":4"#);
+    drop(input);
+    assert!(child.wait().unwrap().success());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn huge_messages_are_refused() {
+    for subcommand in ["lsp", "dap"] {
+        let mut child = start(subcommand);
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"Content-Length: 99999999999999\r\n\r\n{}")
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(output.stdout, b"", "{subcommand}");
+        if subcommand == "lsp" {
+            assert!(!output.status.success());
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert!(stderr.contains("larger than"), "{stderr}");
+        }
+    }
+}
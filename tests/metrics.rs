@@ -0,0 +1,113 @@
+#![cfg(feature = "metrics")]
+
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use brainfuck::sandbox::{End, Limits, Sandbox};
+use brainfuck::telemetry::{FUEL_EXHAUSTED, INSTRUCTIONS, PEAK_TAPE, PROGRAMS};
+use brainfuck::{run_program, run_slice, FnIo, Program, State};
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, Key, KeyName, Metadata, SharedString, Unit,
+};
+
+#[derive(Default)]
+struct Value(Mutex<f64>);
+
+impl CounterFn for Value {
+    fn increment(&self, value: u64) {
+        *self.0.lock().unwrap() += value as f64;
+    }
+    fn absolute(&self, value: u64) {
+        *self.0.lock().unwrap() = value as f64;
+    }
+}
+
+impl GaugeFn for Value {
+    fn increment(&self, value: f64) {
+        *self.0.lock().unwrap() += value;
+    }
+    fn decrement(&self, value: f64) {
+        *self.0.lock().unwrap() -= value;
+    }
+    fn set(&self, value: f64) {
+        *self.0.lock().unwrap() = value;
+    }
+}
+
+/// A recorder keeping the value of every metric, by its name followed by its labels
+#[derive(Default)]
+struct Recorder(Mutex<BTreeMap<String, Arc<Value>>>);
+
+impl Recorder {
+    fn value(&self, key: &Key) -> Arc<Value> {
+        let mut name = key.name().to_owned();
+        for label in key.labels() {
+            name += &format!(" {}={}", label.key(), label.value());
+        }
+        self.0.lock().unwrap().entry(name).or_default().clone()
+    }
+    fn get(&self, name: &str) -> f64 {
+        self.0
+            .lock()
+            .unwrap()
+            .get(name)
+            .map_or(0.0, |value| *value.0.lock().unwrap())
+    }
+}
+
+impl metrics::Recorder for Recorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        Counter::from_arc(self.value(key))
+    }
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(self.value(key))
+    }
+    fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+        Histogram::noop()
+    }
+}
+
+#[test]
+fn runs_publish_metrics() {
+    let recorder = Recorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        let mut io = FnIo::new(|| None, drop);
+        let program = Program::parse(b"++[>+<-]").unwrap();
+        let report = run_program(&program, &mut State::default(), &mut io).unwrap();
+        assert_eq!(
+            recorder.get(&format!("{INSTRUCTIONS} engine=step")),
+            report.instructions as f64
+        );
+        assert_eq!(recorder.get(&format!("{PROGRAMS} engine=step")), 1.0);
+        assert_eq!(recorder.get(PEAK_TAPE), 2.0);
+
+        // Failed runs count too
+        run_slice(b">>>>+<<<<<", &mut State::default(), &mut io).unwrap_err();
+        assert_eq!(recorder.get(&format!("{INSTRUCTIONS} engine=stream")), 10.0);
+        assert_eq!(recorder.get(&format!("{PROGRAMS} engine=stream")), 1.0);
+        assert_eq!(recorder.get(PEAK_TAPE), 5.0);
+
+        // The gauge keeps the largest tape
+        run_slice(b">", &mut State::default(), &mut io).unwrap();
+        assert_eq!(recorder.get(&format!("{PROGRAMS} engine=stream")), 2.0);
+        assert_eq!(recorder.get(PEAK_TAPE), 5.0);
+
+        let sandbox = Sandbox::new(Limits {
+            fuel: 100,
+            cells: NonZeroUsize::new(3).unwrap(),
+            output: 10,
+        });
+        assert!(matches!(sandbox.run(b"+[]", b"").end, End::OutOfFuel));
+        assert!(matches!(sandbox.run(b"+", b"").end, End::Finished));
+        assert_eq!(
+            recorder.get(&format!("{INSTRUCTIONS} engine=sandbox")),
+            101.0
+        );
+        assert_eq!(recorder.get(&format!("{PROGRAMS} engine=sandbox")), 2.0);
+        assert_eq!(recorder.get(FUEL_EXHAUSTED), 1.0);
+    });
+}
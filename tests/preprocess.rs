@@ -0,0 +1,31 @@
+#![cfg(feature = "std")]
+
+use std::{env, fs, process};
+
+use brainfuck::preprocess::{PreprocessErrorKind, Preprocessor};
+
+#[test]
+fn cyclic_includes() {
+    let dir = env::temp_dir().join(format!("brainfuck-include-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("i1.bf"), "+@include \"i2.bf\"").unwrap();
+    fs::write(dir.join("i2.bf"), "-@include \"i1.bf\"").unwrap();
+
+    // The root file is written differently from how `i2.bf` includes it
+    let root = dir.join(".").join("i1.bf");
+    let src = fs::read(&root).unwrap();
+    let e = Preprocessor::new()
+        .includes(true)
+        .expand_at(&src, root.to_str().unwrap())
+        .unwrap_err();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        e.kind,
+        PreprocessErrorKind::CyclicInclude(dir.join("i1.bf").to_string_lossy().into_owned())
+    );
+    assert_eq!(
+        e.file,
+        Some(dir.join("i2.bf").to_string_lossy().into_owned())
+    );
+}
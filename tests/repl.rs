@@ -0,0 +1,87 @@
+#![cfg(feature = "clap")]
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{self, Command, Stdio};
+
+/// Enters `lines` into the interactive shell, returning what it printed to stdout and stderr
+fn shell(lines: &str) -> (String, String) {
+    // Keeps the history of the tests out of the user's
+    shell_with_data(&env::temp_dir().join("brainfuck-tests"), lines)
+}
+
+/// Like `shell`, keeping the history in `data`
+fn shell_with_data(data: &Path, lines: &str) -> (String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .arg("--interactive")
+        .env("XDG_DATA_HOME", data)
+        .env("APPDATA", data)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(lines.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "the shell exited with an error");
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn failing_line_can_be_undone() {
+    let (stdout, stderr) = shell("+++>++\n+<<\n$undo\n$exit\n");
+    assert!(stderr.contains("cell pointer overflowed limit"), "{stderr}");
+    assert!(stderr.contains("the tape around it was [03]03"), "{stderr}");
+    // The tape is shown after each line that doesn't fail, before the next prompt
+    let tapes: Vec<_> = stdout
+        .split("$> ")
+        .skip(1)
+        .map(str::trim)
+        .filter(|tape| !tape.is_empty())
+        .collect();
+    assert_eq!(tapes, ["03[02]", "03[02]"]);
+}
+
+#[test]
+fn timing_counts_like_the_engines() {
+    // The `[` of a loop typed over two lines counts with the first line and the rest
+    // once the loop has run, adding up to what the engines count for the whole line
+    let (stdout, _) = shell("$timing on\n++[>+<-]+[-]>[-]\n>+[\n-]<>>[-]\n$exit\n");
+    let counts: Vec<_> = stdout
+        .lines()
+        .filter_map(|line| line.split_once(", "))
+        .map(|(_, count)| count)
+        .collect();
+    assert_eq!(
+        counts,
+        ["23 instructions", "3 instructions", "6 instructions"]
+    );
+}
+
+#[test]
+fn history_keeps_the_latest_lines() {
+    let data = env::temp_dir().join(format!("brainfuck-history-{}", process::id()));
+    let path = data.join("brainfuck").join("history");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let old: String = (0..1200).map(|i| format!("+{i}\n")).collect();
+    fs::write(&path, old).unwrap();
+
+    shell_with_data(&data, "$exit\n");
+    let history = fs::read_to_string(&path).unwrap();
+    fs::remove_dir_all(&data).unwrap();
+    // Loading drops all but the last 1000 lines, then the new one is added
+    let lines: Vec<_> = history.lines().collect();
+    assert_eq!(lines.len(), 1001);
+    assert_eq!(lines[0], "+200");
+    assert_eq!(lines[1000], "$exit");
+}
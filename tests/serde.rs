@@ -0,0 +1,57 @@
+#![cfg(feature = "serde")]
+
+use brainfuck::{bytecode::Bytecode, Command, Program};
+
+#[test]
+fn programs_and_bytecode_round_trip() {
+    let program = Program::parse(b"+[->+<]>\n[.>]").unwrap();
+    let json = serde_json::to_string(&program).unwrap();
+    assert_eq!(serde_json::from_str::<Program>(&json).unwrap(), program);
+
+    let bytecode = Bytecode::compile(&program);
+    let json = serde_json::to_string(&bytecode).unwrap();
+    assert_eq!(serde_json::from_str::<Bytecode>(&json).unwrap(), bytecode);
+
+    let json = serde_json::to_string(&Command::LoopBegin).unwrap();
+    assert_eq!(json, r#""LoopBegin""#);
+    assert_eq!(
+        serde_json::from_str::<Command>(&json).unwrap(),
+        Command::LoopBegin
+    );
+}
+
+#[test]
+fn unmatched_loops_are_rejected() {
+    let json = serde_json::to_string(&Program::parse(b"[[]]").unwrap()).unwrap();
+    assert!(serde_json::from_str::<Program>(&json).is_ok());
+    for (from, to) in [
+        (r#"{"LoopBegin":3}"#, r#"{"LoopBegin":2}"#),
+        (r#"{"LoopEnd":0}"#, r#"{"LoopEnd":1}"#),
+        (r#"{"LoopEnd":0}"#, r#""Incr""#),
+    ] {
+        let tampered = json.replacen(from, to, 1);
+        assert_ne!(tampered, json);
+        assert!(
+            serde_json::from_str::<Program>(&tampered).is_err(),
+            "{tampered}"
+        );
+    }
+}
+
+#[test]
+fn invalid_bytecode_is_rejected() {
+    let bytecode = Bytecode::compile(&Program::parse(b"+[-]>[.>]").unwrap());
+    let json = serde_json::to_string(&bytecode).unwrap();
+    for (from, to) in [
+        (r#"{"Clear":-1}"#, r#"{"Clear":2}"#),
+        (r#"{"JumpIfZero":5}"#, r#"{"JumpIfZero":1}"#),
+        (r#"{"JumpIfZero":5}"#, r#""Out""#),
+    ] {
+        let tampered = json.replacen(from, to, 1);
+        assert_ne!(tampered, json);
+        assert!(
+            serde_json::from_str::<Bytecode>(&tampered).is_err(),
+            "{tampered}"
+        );
+    }
+}
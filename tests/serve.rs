@@ -0,0 +1,108 @@
+#![cfg(feature = "clap")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+
+/// A server on a free port that is killed when dropped
+struct Server {
+    child: Child,
+    addr: String,
+}
+
+impl Server {
+    fn start(quotas: &[&str]) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(["serve", "--port", "0"])
+            .args(quotas)
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stderr.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let addr = line.trim().rsplit("http://").next().unwrap().to_owned();
+        Server { child, addr }
+    }
+
+    /// Sends `request` as it is, returning the status and body of the response
+    fn send(&self, request: &[u8]) -> (u16, String) {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        stream.write_all(request).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, body.to_owned())
+    }
+
+    fn post(&self, path: &str, body: &str) -> (u16, String) {
+        let request = format!(
+            "POST {path} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        self.send(request.as_bytes())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn programs_run_within_the_limits() {
+    let server = Server::start(&["--fuel", "1000"]);
+    let (status, body) = server.post(
+        "/run",
+        r#"{"program": "+[,.]", "input": "hi", "eof": "terminate"}"#,
+    );
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""output":"hi""#), "{body}");
+    assert!(body.contains(r#""end":"finished""#), "{body}");
+
+    // A request can only lower the quotas
+    for fuel in [10, 1_000_000] {
+        let (status, body) =
+            server.post("/run", &format!(r#"{{"program": "+[]", "fuel": {fuel}}}"#));
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""end":"out of fuel""#), "{body}");
+        let instructions = fuel.min(1000);
+        assert!(
+            body.contains(&format!(r#""instructions":{instructions}"#)),
+            "{body}"
+        );
+    }
+
+    let (status, body) = server.post("/run", r#"{"program": "+]"}"#);
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""end":"failed""#), "{body}");
+}
+
+#[test]
+fn bad_requests_are_refused() {
+    let server = Server::start(&[]);
+    assert_eq!(server.post("/other", "{}").0, 404);
+    assert_eq!(server.send(b"GET /run HTTP/1.1\r\n\r\n").0, 405);
+    assert_eq!(server.post("/run", "not json").0, 400);
+    assert_eq!(server.post("/run", r#"{"program": 1}"#).0, 400);
+    assert_eq!(
+        server.post("/run", r#"{"program": "", "eof": "no"}"#).0,
+        400
+    );
+
+    // Too large bodies are refused before they're read
+    let huge = b"POST /run HTTP/1.1\r\nContent-Length: 1000000000\r\n\r\n";
+    assert_eq!(server.send(huge).0, 413);
+
+    let mut long_line = b"POST /run HTTP/1.1\r\nX: ".to_vec();
+    long_line.extend([b'x'; 10_000]);
+    long_line.extend(b"\r\n\r\n");
+    assert_eq!(server.send(&long_line).0, 431);
+
+    let many_headers = format!("POST /run HTTP/1.1\r\n{}\r\n", "X: x\r\n".repeat(200));
+    assert_eq!(server.send(many_headers.as_bytes()).0, 431);
+}
@@ -0,0 +1,166 @@
+#![cfg(feature = "tracing")]
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use brainfuck::bytecode::Bytecode;
+use brainfuck::{run_program, run_slice, FnIo, Program, State};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+/// A span or an event, with the index of the span it's in
+#[derive(Debug, Clone, PartialEq)]
+struct Recorded {
+    name: String,
+    parent: Option<usize>,
+    fields: String,
+}
+
+#[derive(Default)]
+struct Log {
+    spans: Vec<Recorded>,
+    events: Vec<(Level, Recorded)>,
+    /// The spans that have been entered, innermost last
+    entered: Vec<usize>,
+}
+
+impl Log {
+    fn parent(&self, explicit: Option<&Id>, contextual: bool) -> Option<usize> {
+        match explicit {
+            Some(id) => Some(id.into_u64() as usize - 1),
+            None if contextual => self.entered.last().copied(),
+            None => None,
+        }
+    }
+}
+
+/// A subscriber keeping everything it's sent in a log
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Log>>);
+
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if !self.0.is_empty() {
+            self.0 += " ";
+        }
+        self.0 += &format!("{field}={value:?}");
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut log = self.0.lock().unwrap();
+        let mut fields = Fields(String::new());
+        span.record(&mut fields);
+        let recorded = Recorded {
+            name: span.metadata().name().to_owned(),
+            parent: log.parent(span.parent(), span.is_contextual()),
+            fields: fields.0,
+        };
+        log.spans.push(recorded);
+        Id::from_u64(log.spans.len() as u64)
+    }
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+    fn event(&self, event: &Event<'_>) {
+        let mut log = self.0.lock().unwrap();
+        let mut fields = Fields(String::new());
+        event.record(&mut fields);
+        let recorded = Recorded {
+            name: String::new(),
+            parent: log.parent(event.parent(), event.is_contextual()),
+            fields: fields.0,
+        };
+        log.events.push((*event.metadata().level(), recorded));
+    }
+    fn enter(&self, span: &Id) {
+        let index = span.into_u64() as usize - 1;
+        self.0.lock().unwrap().entered.push(index);
+    }
+    fn exit(&self, _: &Id) {
+        self.0.lock().unwrap().entered.pop();
+    }
+}
+
+type Io = FnIo<fn() -> Option<u8>, fn(u8)>;
+
+/// Runs `run` on a state reading 2 with a recorder as the subscriber
+fn record(run: impl FnOnce(&mut State, &mut Io)) -> Log {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let input: fn() -> Option<u8> = || Some(2);
+        run(&mut State::default(), &mut FnIo::new(input, drop));
+    });
+    Arc::try_unwrap(recorder.0)
+        .ok()
+        .unwrap()
+        .into_inner()
+        .unwrap()
+}
+
+fn span(name: &str, parent: Option<usize>, fields: &str) -> Recorded {
+    Recorded {
+        name: name.to_owned(),
+        parent,
+        fields: fields.to_owned(),
+    }
+}
+
+fn event(parent: usize, fields: &str) -> (Level, Recorded) {
+    (Level::TRACE, span("", Some(parent), fields))
+}
+
+#[test]
+fn runs_and_loops_are_spans() {
+    const SRC: &[u8] = b",[>++[>+.<-]<-]";
+    let program = Program::parse(SRC).unwrap();
+    for engine in ["stream", "step", "bytecode"] {
+        let log = record(|state, io| {
+            match engine {
+                "stream" => run_slice(SRC, state, io),
+                "step" => run_program(&program, state, io),
+                _ => Bytecode::compile(&program).run(state, io),
+            }
+            .unwrap();
+        });
+        assert_eq!(
+            log.spans,
+            [
+                span("run", None, &format!("engine={engine:?}")),
+                span("loop", Some(0), "begin=1:2"),
+                span("loop", Some(1), "begin=1:6"),
+                span("loop", Some(1), "begin=1:6"),
+            ],
+            "{engine}"
+        );
+        assert_eq!(
+            log.events,
+            [
+                event(0, "message=input byte=2"),
+                event(2, "message=output byte=1"),
+                event(2, "message=output byte=2"),
+                event(3, "message=output byte=3"),
+                event(3, "message=output byte=4"),
+            ],
+            "{engine}"
+        );
+    }
+}
+
+#[test]
+fn errors_are_events_in_their_runs() {
+    let log = record(|state, io| {
+        run_slice(b"+[<]", state, io).unwrap_err();
+    });
+    assert_eq!(log.spans.len(), 2);
+    let (level, error) = &log.events[0];
+    assert_eq!(*level, Level::ERROR);
+    assert_eq!(error.parent, Some(0));
+    assert!(error.fields.starts_with("error="), "{}", error.fields);
+}
@@ -0,0 +1,27 @@
+use brainfuck::{run_slice, FnIo, State, MAX_WARNINGS};
+
+fn recovering(src: &str) -> State {
+    let mut state = State::default();
+    state.set_recover(true);
+    run_slice(src.as_bytes(), &mut state, &mut FnIo::new(|| None, drop)).unwrap();
+    state
+}
+
+#[test]
+fn repeats_are_counted() {
+    let warnings = recovering("+++[<-]<").take_warnings();
+    let counts: Vec<_> = warnings
+        .kept
+        .iter()
+        .map(|w| (w.error.position().unwrap().column, w.count))
+        .collect();
+    assert_eq!(counts, [(5, 3), (8, 1)]);
+    assert_eq!(warnings.omitted, 0);
+}
+
+#[test]
+fn distinct_ones_are_capped() {
+    let warnings = recovering(&"<".repeat(MAX_WARNINGS + 20)).take_warnings();
+    assert_eq!(warnings.kept.len(), MAX_WARNINGS);
+    assert_eq!(warnings.omitted, 20);
+}
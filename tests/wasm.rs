@@ -0,0 +1,28 @@
+#![cfg(feature = "wasm")]
+
+use brainfuck::wasm::{run, Interpreter};
+
+// Only what doesn't fail is run here, since errors become JavaScript values,
+// which only exist on WebAssembly
+
+#[test]
+fn programs_run_with_their_input() {
+    assert_eq!(run(",[.,]", b"hi").unwrap(), b"hi");
+    assert_eq!(run("++[->+++<]>.", b"").unwrap(), [6]);
+}
+
+#[test]
+fn interpreters_step() {
+    let mut interpreter = Interpreter::new("+>,.", b"a").unwrap();
+    assert!(interpreter.step().unwrap());
+    assert_eq!((interpreter.pc(), interpreter.cell(0)), (1, 1));
+    assert!(interpreter.step().unwrap());
+    assert_eq!(interpreter.pointer(), 1);
+    assert!(interpreter.step().unwrap());
+    assert_eq!(interpreter.take_output(), b"");
+    assert!(!interpreter.step().unwrap());
+    assert!(interpreter.finished());
+    assert_eq!(interpreter.take_output(), b"a");
+    assert_eq!(interpreter.take_output(), b"");
+    assert!(!interpreter.step().unwrap());
+}